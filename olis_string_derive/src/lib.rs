@@ -0,0 +1,316 @@
+//! Macros that declare `UnsafeField` structs for `olis_string`, which re-exports them from
+//! `olis_string::unsafe_field` with the `derive` feature.
+//!
+//! A field is unsafe if it has an `#[unsafe_field(invariant = "...")]` attribute, one for each
+//! invariant it has to uphold.
+//!
+//! - `#[unsafe_fields]` numbers the unsafe fields, by wrapping their types in
+//!   `UnsafeField<T, INDEX>`, and writes their invariants into a `# Safety` section on the struct
+//!   and on each field. It then derives `UnsafeFields`. A derive can't change the types of the
+//!   fields, so numbering them has to be done by an attribute.
+//! - `#[derive(UnsafeFields)]` works on structs whose fields are already `UnsafeField<T, INDEX>`.
+//!   It checks that no two unsafe fields share an index, and that every `UnsafeField` documents
+//!   its invariants, and generates a safe constructor.
+//!
+//! The constructor is called `from_fields` (or whatever `#[unsafe_field(constructor = "...")]` on
+//! the struct says). It takes every field by value, in declaration order, and a proof closure,
+//! named `__proof` so that it can't collide with a field, that is shown every field. The closure
+//! can only vouch for the invariants by returning an `unsafe_field::Proof`, which is unsafe to
+//! make, so the one `unsafe` block that checks the invariants is the only place that the safety of
+//! the struct depends on.
+//!
+//! ```rs
+//! use olis_string::unsafe_field::{unsafe_fields, Proof};
+//!
+//! #[unsafe_fields]
+//! struct Span {
+//!     #[unsafe_field(invariant = "`start <= end`")]
+//!     start: usize,
+//!     #[unsafe_field(invariant = "`start <= end`")]
+//!     end: usize,
+//! }
+//!
+//! let span = Span::from_fields(1, 3, |start, end| {
+//!     // SAFETY: `start <= end` was just checked
+//!     (start <= end).then(|| unsafe { Proof::new() })
+//! });
+//! ```
+
+use proc_macro::TokenStream;
+use proc_macro2::{Literal, Span, TokenStream as TokenStream2};
+use quote::{format_ident, quote};
+use syn::{
+    parse_macro_input, parse_quote, Attribute, Data, DataStruct, DeriveInput, Expr, ExprLit, Field,
+    Fields, FieldsNamed, GenericArgument, Ident, Lit, LitStr, PathArguments, Type,
+};
+
+/// The helper attribute, on fields and on the struct
+const HELPER: &str = "unsafe_field";
+
+/// Numbers the unsafe fields of a struct, documents their invariants, and derives
+/// `UnsafeFields`
+#[proc_macro_attribute]
+pub fn unsafe_fields(args: TokenStream, input: TokenStream) -> TokenStream {
+    let args = TokenStream2::from(args);
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_attribute(args, input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Checks the unsafe fields of a struct, and generates a safe constructor that takes a proof
+/// closure
+#[proc_macro_derive(UnsafeFields, attributes(unsafe_field))]
+pub fn derive_unsafe_fields(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_derive(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand_attribute(args: TokenStream2, mut input: DeriveInput) -> syn::Result<TokenStream2> {
+    if !args.is_empty() {
+        return Err(syn::Error::new_spanned(
+            args,
+            "`#[unsafe_fields]` takes no arguments, name the constructor with \
+             `#[unsafe_field(constructor = \"...\")]`",
+        ));
+    }
+    let mut contract = Vec::new();
+    let mut index = 0;
+    for field in &mut named_fields_mut(&mut input)?.named {
+        let Some(invariants) = field_invariants(field)? else {
+            continue;
+        };
+        let ident = field.ident.as_ref().expect("the field is named");
+        contract.extend(
+            invariants
+                .iter()
+                .map(|invariant| format!(" - `{ident}`: {}", invariant.value())),
+        );
+        let lines = invariants
+            .iter()
+            .map(|invariant| format!(" - {}", invariant.value()))
+            .collect();
+        push_safety_docs(&mut field.attrs, lines);
+
+        let ty = &field.ty;
+        let lit = Literal::usize_unsuffixed(index);
+        field.ty = parse_quote!(::olis_string::unsafe_field::UnsafeField<#ty, #lit>);
+        index += 1;
+    }
+    if index == 0 {
+        return Err(no_unsafe_fields());
+    }
+    push_safety_docs(&mut input.attrs, contract);
+
+    // the derive has to come before any `#[unsafe_field]` on the struct, since it introduces it
+    Ok(quote! {
+        #[derive(::olis_string::unsafe_field::UnsafeFields)]
+        #input
+    })
+}
+
+fn expand_derive(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let constructor = constructor_name(&input.attrs)?;
+    let mut indices: Vec<(u128, &Ident)> = Vec::new();
+    let mut contract = Vec::new();
+    let mut idents = Vec::new();
+    let mut params = Vec::new();
+    let mut proof_params = Vec::new();
+    let mut inits = Vec::new();
+    for field in &named_fields(&input)?.named {
+        let ident = field.ident.as_ref().expect("the field is named");
+        let ty = &field.ty;
+        idents.push(ident);
+        let Some(invariants) = field_invariants(field)? else {
+            if unsafe_field_args(ty).is_some() {
+                return Err(syn::Error::new_spanned(
+                    ty,
+                    format!(
+                        "`{ident}` is an `UnsafeField`, document its invariants with \
+                         `#[unsafe_field(invariant = \"...\")]`"
+                    ),
+                ));
+            }
+            params.push(quote!(#ident: #ty));
+            proof_params.push(quote!(&#ty));
+            inits.push(quote!(#ident));
+            continue;
+        };
+
+        let Some((inner, index)) = unsafe_field_args(ty) else {
+            return Err(syn::Error::new_spanned(
+                ty,
+                "an unsafe field has to be an `UnsafeField<T, INDEX>`, or be declared in a \
+                 struct with `#[unsafe_fields]`",
+            ));
+        };
+        if let Some(index) = index {
+            if let Some((_, other)) = indices.iter().find(|(i, _)| *i == index) {
+                return Err(syn::Error::new_spanned(
+                    ty,
+                    format!("`{ident}` has the same index as `{other}`"),
+                ));
+            }
+            indices.push((index, ident));
+        }
+        contract.extend(
+            invariants
+                .iter()
+                .map(|invariant| format!(" - `{ident}`: {}", invariant.value())),
+        );
+        params.push(quote!(#ident: #inner));
+        proof_params.push(quote!(&#inner));
+        // `__proof` vouched for the invariants of every unsafe field
+        inits.push(quote!(#ident: unsafe { <#ty>::new(#ident) }));
+    }
+    if contract.is_empty() {
+        return Err(no_unsafe_fields());
+    }
+
+    let name = &input.ident;
+    let vis = &input.vis;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let summary = format!(
+        " Constructs a [`{name}`] from every field, in declaration order, if `__proof` proves \
+         that the invariants of its unsafe fields hold:"
+    );
+    let proof_docs = " `__proof` is shown every field, and returns `None` if an invariant does \
+                      not hold, in which case so does this.";
+    Ok(quote! {
+        impl #impl_generics #name #ty_generics #where_clause {
+            #[doc = #summary]
+            #(#[doc = #contract])*
+            #[doc = ""]
+            #[doc = #proof_docs]
+            #[allow(clippy::too_many_arguments)]
+            #vis fn #constructor(
+                #(#params,)*
+                __proof: impl ::core::ops::FnOnce(#(#proof_params),*) -> ::core::option::Option<
+                    ::olis_string::unsafe_field::Proof<Self>,
+                >,
+            ) -> ::core::option::Option<Self> {
+                let _: ::olis_string::unsafe_field::Proof<Self> = __proof(#(&#idents),*)?;
+                ::core::option::Option::Some(Self { #(#inits),* })
+            }
+        }
+    })
+}
+
+fn named_fields(input: &DeriveInput) -> syn::Result<&FieldsNamed> {
+    match &input.data {
+        Data::Struct(DataStruct {
+            fields: Fields::Named(fields),
+            ..
+        }) => Ok(fields),
+        _ => Err(not_named_fields()),
+    }
+}
+
+fn named_fields_mut(input: &mut DeriveInput) -> syn::Result<&mut FieldsNamed> {
+    match &mut input.data {
+        Data::Struct(DataStruct {
+            fields: Fields::Named(fields),
+            ..
+        }) => Ok(fields),
+        _ => Err(not_named_fields()),
+    }
+}
+
+fn not_named_fields() -> syn::Error {
+    syn::Error::new(
+        Span::call_site(),
+        "unsafe fields are only supported on structs with named fields",
+    )
+}
+
+fn no_unsafe_fields() -> syn::Error {
+    syn::Error::new(
+        Span::call_site(),
+        "no field has an `#[unsafe_field(invariant = \"...\")]` attribute",
+    )
+}
+
+/// Returns the invariants of a field, or `None` if it isn't unsafe
+fn field_invariants(field: &Field) -> syn::Result<Option<Vec<LitStr>>> {
+    let mut invariants = None;
+    for attr in field
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident(HELPER))
+    {
+        let invariants = invariants.get_or_insert_with(Vec::new);
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("invariant") {
+                invariants.push(meta.value()?.parse()?);
+                Ok(())
+            } else {
+                Err(meta.error("expected `invariant = \"...\"`"))
+            }
+        })?;
+        if invariants.is_empty() {
+            return Err(syn::Error::new_spanned(
+                attr,
+                "an unsafe field needs an invariant",
+            ));
+        }
+    }
+    Ok(invariants)
+}
+
+/// Returns the name of the constructor, from `#[unsafe_field(constructor = "...")]` on the struct
+fn constructor_name(attrs: &[Attribute]) -> syn::Result<Ident> {
+    let mut constructor = format_ident!("from_fields");
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident(HELPER)) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("constructor") {
+                constructor = meta.value()?.parse::<LitStr>()?.parse()?;
+                Ok(())
+            } else {
+                Err(meta.error("expected `constructor = \"...\"`"))
+            }
+        })?;
+    }
+    Ok(constructor)
+}
+
+/// Returns the value type of an `UnsafeField<T, INDEX>`, and its index if it is a literal
+fn unsafe_field_args(ty: &Type) -> Option<(&Type, Option<u128>)> {
+    let Type::Path(path) = ty else {
+        return None;
+    };
+    let segment = path.path.segments.last()?;
+    if segment.ident != "UnsafeField" {
+        return None;
+    }
+    let PathArguments::AngleBracketed(args) = &segment.arguments else {
+        return None;
+    };
+    let mut args = args.args.iter();
+    let (Some(GenericArgument::Type(inner)), Some(index), None) =
+        (args.next(), args.next(), args.next())
+    else {
+        return None;
+    };
+    let index = match index {
+        GenericArgument::Const(Expr::Lit(ExprLit {
+            lit: Lit::Int(int), ..
+        })) => int.base10_parse().ok(),
+        _ => None,
+    };
+    Some((inner, index))
+}
+
+/// Appends a `# Safety` section with `lines` to the docs in `attrs`
+fn push_safety_docs(attrs: &mut Vec<Attribute>, lines: Vec<String>) {
+    if attrs.iter().any(|attr| attr.path().is_ident("doc")) {
+        attrs.push(parse_quote!(#[doc = ""]));
+    }
+    attrs.push(parse_quote!(#[doc = " # Safety"]));
+    attrs.extend(
+        lines
+            .iter()
+            .map(|line| -> Attribute { parse_quote!(#[doc = #line]) }),
+    );
+}
@@ -0,0 +1,443 @@
+use std::{
+    alloc::{handle_alloc_error, Layout},
+    borrow::Borrow,
+    cmp, fmt,
+    hash::{Hash, Hasher},
+    mem::{self, MaybeUninit},
+    ops::Deref,
+    ptr::{self, NonNull},
+    sync::atomic::{self, AtomicUsize, Ordering},
+};
+
+use crate::{
+    sso_string::{LongString, RawSsoStringN, ShortString, SsoString, SsoStringN},
+    unified_alloc::{Allocator, Global},
+    unsafe_field::UnsafeField,
+};
+
+/// The long variant of an [`ArcSsoString`], a length and a pointer to a block that starts with
+/// the bytes of the string, followed by an atomic reference count. It is laid out like the start
+/// of a [`LongString`], so the tag bit of a [`ShortString`] overlaps with its length in the same
+/// way.
+///
+/// The block is allocated like the buffer of a [`LongString`], with an alignment of 1, so that a
+/// string that isn't shared can hand it over to a [`SsoString`] without copying. The count is
+/// stored at the first address after the bytes that is aligned for it.
+///
+/// This does not implement `Drop`, the [`ArcSsoString`] that it belongs to gives up its reference.
+#[repr(C)]
+pub struct ArcLongString {
+    /// # Safety
+    /// - the bits that overlap with the tag bits of a [`ShortString`] and a static string are
+    ///   always 0
+    ///
+    /// when shifted by `>> LongString::LEN_SHIFT`:
+    /// - `len` is the number of bytes at the start of the block
+    len: UnsafeField<usize, 0>,
+    /// # Safety
+    /// - points to a live block allocated by `Global` with `ArcLongString::layout(len)`
+    /// - the block starts with `len` bytes of valid utf-8, followed by the reference count, which
+    ///   is the number of `ArcLongString`s that point to it
+    /// - the bytes are never changed while the reference count is more than 1
+    bytes: UnsafeField<NonNull<u8>, 1>,
+}
+
+impl ArcLongString {
+    /// The layout of a block with `len` bytes, and enough room after them for an aligned count.
+    /// It is never bigger than [`LongString::MAX_CAPACITY`], so that it can become the buffer of
+    /// a [`LongString`].
+    fn layout(len: usize) -> Layout {
+        len.checked_add(mem::size_of::<AtomicUsize>() + mem::align_of::<AtomicUsize>() - 1)
+            .filter(|&size| size <= <LongString>::MAX_CAPACITY)
+            .and_then(|size| Layout::array::<u8>(size).ok())
+            .expect("capacity overflow")
+    }
+
+    /// Returns a pointer to the reference count of a block with `len` bytes that starts at `bytes`
+    ///
+    /// # Safety
+    /// - `bytes` must point to a block allocated with `ArcLongString::layout(len)`
+    unsafe fn count_ptr(bytes: NonNull<u8>, len: usize) -> NonNull<AtomicUsize> {
+        let end = bytes.as_ptr() as usize + len;
+        let padding = end.wrapping_neg() & (mem::align_of::<AtomicUsize>() - 1);
+        // SAFETY: the layout leaves enough room after the bytes for the padding and the count
+        unsafe { bytes.add(len + padding).cast() }
+    }
+
+    /// Allocates a new block with a reference count of 1, and copies `s` into it
+    pub fn from_str(s: &str) -> Self {
+        let layout = Self::layout(s.len());
+        let bytes = Global
+            .allocate(layout)
+            .unwrap_or_else(|_| handle_alloc_error(layout))
+            .cast::<u8>();
+        // SAFETY:
+        // - the block is big enough for `s.len()` bytes, followed by an aligned count
+        // - `s` cannot overlap with a block we just allocated
+        unsafe {
+            ptr::copy_nonoverlapping(s.as_ptr(), bytes.as_ptr(), s.len());
+            Self::count_ptr(bytes, s.len())
+                .as_ptr()
+                .write(AtomicUsize::new(1));
+        }
+
+        Self {
+            // SAFETY: the layout checked that `s.len() <= MAX_CAPACITY`, so shifting by
+            // LEN_SHIFT clears the tag bits, and the block holds `s.len()` bytes
+            len: unsafe { UnsafeField::new(s.len() << <LongString>::LEN_SHIFT) },
+            // SAFETY: the block was allocated by `Global` with the layout for `s.len()` bytes, and
+            // holds the valid utf-8 of `s` and a count of 1
+            bytes: unsafe { UnsafeField::new(bytes) },
+        }
+    }
+
+    /// returns the length of this string in bytes
+    pub const fn len(&self) -> usize {
+        *self.len.get() >> <LongString>::LEN_SHIFT
+    }
+
+    fn count(&self) -> &AtomicUsize {
+        // SAFETY: the block was allocated with the layout for `len` bytes, and is live for as long
+        // as we hold a reference to it
+        unsafe { Self::count_ptr(*self.bytes.get(), self.len()).as_ref() }
+    }
+
+    /// Returns the number of strings that share this block. Other threads may change this at any
+    /// time, unless it is 1.
+    pub fn ref_count(&self) -> usize {
+        self.count().load(Ordering::Acquire)
+    }
+
+    fn bytes(&self) -> NonNull<u8> {
+        *self.bytes.get()
+    }
+
+    /// interpret this as a `&str`
+    pub fn as_str(&self) -> &str {
+        // SAFETY: the block holds `len` bytes of valid utf-8, which are not changed for as long as
+        // they are shared
+        unsafe {
+            let bytes = std::slice::from_raw_parts(self.bytes().as_ptr(), self.len());
+            std::str::from_utf8_unchecked(bytes)
+        }
+    }
+
+    /// # Safety
+    /// - the reference count must be 1, so that nothing else can see the changes
+    unsafe fn as_mut_str(&mut self) -> &mut str {
+        // SAFETY: the block holds `len` bytes of valid utf-8, and we are its only owner (caller
+        // contract)
+        unsafe {
+            let bytes = std::slice::from_raw_parts_mut(self.bytes().as_ptr(), self.len());
+            std::str::from_utf8_unchecked_mut(bytes)
+        }
+    }
+
+    /// Takes out another reference to the block, for a clone of the string that owns this
+    fn share(&self) -> Self {
+        // the same ordering as `Arc::clone`, a new reference can only be made from an existing
+        // one, so nothing needs to be synchronised
+        let old = self.count().fetch_add(1, Ordering::Relaxed);
+        if old > isize::MAX as usize {
+            // the count would overflow if this went on, which would free the block too early
+            std::process::abort();
+        }
+        // SAFETY: we just took out another reference for the copy
+        unsafe { ptr::read(self) }
+    }
+
+    /// Gives up a reference to the block, and frees it if it was the last one
+    ///
+    /// # Safety
+    /// - the caller owns a reference, and never uses this string again
+    unsafe fn release(&self) {
+        if self.count().fetch_sub(1, Ordering::Release) != 1 {
+            return;
+        }
+        // the same fence as `Arc`, so that every use of the block through another reference
+        // happens before it is freed
+        atomic::fence(Ordering::Acquire);
+        // SAFETY: this was the last reference, and the block was allocated by `Global` with this
+        // layout
+        unsafe { Global.deallocate(self.bytes(), Self::layout(self.len())) }
+    }
+}
+
+impl fmt::Debug for ArcLongString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+pub enum TaggedArcSsoString<'a> {
+    Short(&'a ShortString),
+    Long(&'a ArcLongString),
+}
+
+type RawSsoString = RawSsoStringN<{ ShortString::MAX_CAPACITY }>;
+
+/// An immutable string that is shared instead of copied. Short strings are stored inline, exactly
+/// like a [`SsoString`], and long strings point to a block with an atomic reference count, so
+/// cloning is O(1) either way. [`ArcSsoString::make_mut`] copies a shared string before changing
+/// it, and [`ArcSsoString::into_mut`] turns it into a [`SsoString`] that can grow, copying it only
+/// if it is shared.
+///
+/// A string is only long if it doesn't fit in a [`ShortString`].
+#[repr(C)]
+pub struct ArcSsoString {
+    /// # Safety
+    /// - the tag bit of `raw.tag` says which variant is active
+    /// - for short strings, a valid [`ShortString`] starts at `raw`
+    /// - for long strings, a valid [`ArcLongString`] that owns a reference to its block starts at
+    ///   `raw`, and it is longer than [`ShortString::MAX_CAPACITY`]
+    raw: RawSsoString,
+}
+
+const _: () = assert!(mem::size_of::<ArcSsoString>() == mem::size_of::<SsoString>());
+const _: () = assert!(mem::size_of::<ArcLongString>() <= mem::size_of::<RawSsoString>());
+const _: () = assert!(mem::size_of::<Option<ArcSsoString>>() == mem::size_of::<ArcSsoString>());
+
+impl ArcSsoString {
+    /// Constructs an empty (short) string
+    pub fn new() -> Self {
+        Self::from_short(ShortString::new())
+    }
+
+    fn from_short(short: ShortString) -> Self {
+        let mut this = MaybeUninit::<Self>::uninit();
+        // SAFETY:
+        // - raw is as big and aligned as a short string
+        // - the tag of a short string is always valid, and everything after it is allowed to be
+        //   uninitialised
+        unsafe {
+            this.as_mut_ptr().cast::<ShortString>().write(short);
+            this.assume_init()
+        }
+    }
+
+    /// # Safety
+    /// - `long` must be longer than [`ShortString::MAX_CAPACITY`]
+    unsafe fn from_long(long: ArcLongString) -> Self {
+        let mut this = MaybeUninit::<Self>::uninit();
+        // SAFETY:
+        // - raw is at least as big as a long string, and aligned for a pointer
+        // - the first byte of the length of a long string is always a valid tag, and everything
+        //   after the pointer is allowed to be uninitialised
+        unsafe {
+            this.as_mut_ptr().cast::<ArcLongString>().write(long);
+            this.assume_init()
+        }
+    }
+
+    /// Returns `true` if this string is a short string (no heap allocations), and `false` otherwise
+    pub fn is_short(&self) -> bool {
+        // the tag is the first byte of both variants
+        (self.raw.tag as u8 & ShortString::FLAG) != 0
+    }
+
+    /// Returns `!self.is_short()`
+    pub fn is_long(&self) -> bool {
+        !self.is_short()
+    }
+
+    /// Returns the underlying string as an enum, allowing you to access the underlying short or
+    /// long variant for the string
+    pub fn tagged(&self) -> TaggedArcSsoString<'_> {
+        let raw = &self.raw as *const RawSsoString;
+        if self.is_short() {
+            // SAFETY: a valid short string starts at raw
+            TaggedArcSsoString::Short(unsafe { &*raw.cast::<ShortString>() })
+        } else {
+            // SAFETY: a valid long string starts at raw
+            TaggedArcSsoString::Long(unsafe { &*raw.cast::<ArcLongString>() })
+        }
+    }
+
+    /// interpret this as a `&str`
+    pub fn as_str(&self) -> &str {
+        match self.tagged() {
+            TaggedArcSsoString::Short(short) => short.as_str(),
+            TaggedArcSsoString::Long(long) => long.as_str(),
+        }
+    }
+
+    /// Returns a mutable reference to the contents of this string. A long string that shares its
+    /// block with other strings is copied into a new block first, so that they don't see the
+    /// changes, like `Arc::make_mut`.
+    pub fn make_mut(&mut self) -> &mut str {
+        if let TaggedArcSsoString::Long(long) = self.tagged() {
+            if long.ref_count() != 1 {
+                let unique = ArcLongString::from_str(long.as_str());
+                // SAFETY: the copy is as long as the old string
+                drop(mem::replace(self, unsafe { Self::from_long(unique) }));
+            }
+        }
+
+        let raw = &mut self.raw as *mut RawSsoString;
+        if self.is_short() {
+            // SAFETY: a valid short string starts at raw, and `&mut str` cannot change its tag
+            unsafe { (*raw.cast::<ShortString>()).as_mut_str() }
+        } else {
+            // SAFETY: a valid long string starts at raw, and its reference count is 1, since
+            // `&mut self` keeps anyone else from sharing it in the meantime
+            unsafe { (*raw.cast::<ArcLongString>()).as_mut_str() }
+        }
+    }
+}
+
+impl ArcSsoString {
+    /// Converts this into a [`SsoString`] that can grow. A long string that doesn't share its
+    /// block with any other string hands the block over without copying, and the space for the
+    /// reference count becomes spare capacity. A shared one is copied, like `Arc::make_mut`.
+    pub fn into_mut(self) -> SsoString {
+        if let TaggedArcSsoString::Long(long) = self.tagged() {
+            // `Acquire` so that every use of the bytes through a reference that was given up
+            // happens before we change them
+            if long.ref_count() == 1 {
+                let (bytes, len) = (long.bytes(), long.len());
+                let capacity = ArcLongString::layout(len).size();
+                // the block belongs to the new string now
+                mem::forget(self);
+                // SAFETY:
+                // - the block was allocated by `Global` with an alignment of 1 and a size of
+                //   `capacity`, and this was its last reference, so nothing else can use it
+                // - `len <= capacity <= LongString::MAX_CAPACITY`, and the first `len` bytes are
+                //   valid utf-8
+                return unsafe { SsoString::from_raw_parts(bytes.as_ptr(), len, capacity) };
+            }
+        }
+        SsoString::from(self.as_str())
+    }
+}
+
+impl Default for ArcSsoString {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clone for ArcSsoString {
+    /// Short strings are copied, long strings share their block
+    fn clone(&self) -> Self {
+        match self.tagged() {
+            TaggedArcSsoString::Short(short) => Self::from_short(*short),
+            // SAFETY: the clone is as long as `self`
+            TaggedArcSsoString::Long(long) => unsafe { Self::from_long(long.share()) },
+        }
+    }
+}
+
+impl Drop for ArcSsoString {
+    fn drop(&mut self) {
+        if let TaggedArcSsoString::Long(long) = self.tagged() {
+            // SAFETY: the long string owns a reference, and it is never used again
+            unsafe { long.release() }
+        }
+    }
+}
+
+impl<'a> From<&'a str> for ArcSsoString {
+    fn from(value: &'a str) -> Self {
+        if value.len() <= ShortString::MAX_CAPACITY {
+            let mut short = ShortString::new();
+            // SAFETY: the short string is empty, and value fits in it
+            unsafe { short.push_str_unchecked(value) };
+            Self::from_short(short)
+        } else {
+            // SAFETY: value does not fit in a short string
+            unsafe { Self::from_long(ArcLongString::from_str(value)) }
+        }
+    }
+}
+
+impl From<String> for ArcSsoString {
+    fn from(value: String) -> Self {
+        Self::from(value.as_str())
+    }
+}
+
+impl<const N: usize, A: Allocator> From<SsoStringN<N, A>> for ArcSsoString {
+    /// The bytes of a long string are always copied, since they need a reference count in front
+    fn from(value: SsoStringN<N, A>) -> Self {
+        Self::from(value.as_str())
+    }
+}
+
+impl From<ArcSsoString> for SsoString {
+    /// Short strings stay short, long strings keep their block if it isn't shared, see
+    /// [`ArcSsoString::into_mut`]
+    fn from(value: ArcSsoString) -> Self {
+        value.into_mut()
+    }
+}
+
+impl Deref for ArcSsoString {
+    type Target = str;
+
+    fn deref(&self) -> &Self::Target {
+        self.as_str()
+    }
+}
+
+impl AsRef<str> for ArcSsoString {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl Borrow<str> for ArcSsoString {
+    fn borrow(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl PartialEq for ArcSsoString {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for ArcSsoString {}
+
+impl PartialEq<str> for ArcSsoString {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl<'a> PartialEq<&'a str> for ArcSsoString {
+    fn eq(&self, other: &&'a str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl PartialOrd for ArcSsoString {
+    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ArcSsoString {
+    fn cmp(&self, other: &Self) -> cmp::Ordering {
+        self.as_str().cmp(other.as_str())
+    }
+}
+
+impl Hash for ArcSsoString {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // exactly the same as `str`, so that lookups through `Borrow<str>` work
+        self.as_str().hash(state)
+    }
+}
+
+impl fmt::Display for ArcSsoString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Debug for ArcSsoString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
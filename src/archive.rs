@@ -0,0 +1,346 @@
+//! A zero-copy archive format for strings, for string tables that are memory-mapped instead of
+//! deserialized.
+//!
+//! Every archived string is a 24 byte header that mirrors an [`SsoString`](crate::String) on a
+//! 64-bit little-endian target:
+//! - a short string of up to 23 bytes is stored inline: the first byte is `len << 1 | 1`, and the
+//!   bytes follow it
+//! - a long string starts with `len << 1` as a little-endian `u64`, so its tag bit is 0, followed
+//!   by the offset of its bytes relative to the start of the header as a little-endian `i64`, and
+//!   8 bytes of zeroes where an in-memory string keeps its capacity
+//!
+//! The format is the same on every target, and has no alignment requirements, so an archive can
+//! be read straight out of any `&[u8]`. Nothing in an archive is trusted: [`ArchivedSsoString`]
+//! and [`ArchivedStringTable`] are only handed out after the headers, offsets and utf-8 have been
+//! checked.
+
+use std::{error::Error, fmt, ops::Deref, str::Utf8Error};
+
+use crate::sso_string::SsoString;
+
+/// The size of an archived string header
+pub const ARCHIVED_SIZE: usize = 24;
+
+/// The number of bytes that an archived string stores inline
+pub const ARCHIVED_INLINE_CAPACITY: usize = ARCHIVED_SIZE - 1;
+
+/// The tag bit in the first byte of a header, which is set for inline strings
+const FLAG: u8 = 1;
+
+/// Builds an archive, by appending archived strings to a buffer. The returned positions are what
+/// the strings are looked up by later.
+#[derive(Debug, Default, Clone)]
+pub struct ArchiveWriter {
+    bytes: Vec<u8>,
+}
+
+impl ArchiveWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends to `bytes`, positions are relative to the start of `bytes`
+    pub fn from_vec(bytes: Vec<u8>) -> Self {
+        Self { bytes }
+    }
+
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+
+    /// Appends `s` to the archive, and returns the position of its header
+    pub fn write_str(&mut self, s: &str) -> usize {
+        let data = self.write_data(s);
+        self.write_header(s, data)
+    }
+
+    /// Appends every string of `strings` to the archive, with their headers right after each
+    /// other so that they can be read back as an [`ArchivedStringTable`]. Returns the position of
+    /// the first header.
+    pub fn write_table<I, S>(&mut self, strings: I) -> usize
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let strings: Vec<S> = strings.into_iter().collect();
+        let data: Vec<usize> = strings
+            .iter()
+            .map(|s| self.write_data(s.as_ref()))
+            .collect();
+        let pos = self.len();
+        for (s, data) in strings.iter().zip(data) {
+            self.write_header(s.as_ref(), data);
+        }
+        pos
+    }
+
+    /// Writes the bytes of `s` if it does not fit inline, and returns where they start
+    fn write_data(&mut self, s: &str) -> usize {
+        let pos = self.len();
+        if s.len() > ARCHIVED_INLINE_CAPACITY {
+            self.bytes.extend_from_slice(s.as_bytes());
+        }
+        pos
+    }
+
+    fn write_header(&mut self, s: &str, data: usize) -> usize {
+        let pos = self.len();
+        let mut header = [0; ARCHIVED_SIZE];
+        if s.len() <= ARCHIVED_INLINE_CAPACITY {
+            header[0] = FLAG | ((s.len() as u8) << 1);
+            header[1..][..s.len()].copy_from_slice(s.as_bytes());
+        } else {
+            // allocations are at most isize::MAX bytes, so these can never overflow
+            let len = (s.len() as u64) << 1;
+            let offset = data as i64 - pos as i64;
+            header[0..8].copy_from_slice(&len.to_le_bytes());
+            header[8..16].copy_from_slice(&offset.to_le_bytes());
+        }
+        self.bytes.extend_from_slice(&header);
+        pos
+    }
+}
+
+/// An archived string that has been checked, and can be read without copying
+#[derive(Clone, Copy)]
+pub struct ArchivedSsoString<'a> {
+    header: &'a [u8; ARCHIVED_SIZE],
+    str: &'a str,
+}
+
+impl<'a> ArchivedSsoString<'a> {
+    /// Checks and reads the archived string whose header starts at `pos` in `archive`
+    pub fn access(archive: &'a [u8], pos: usize) -> Result<Self, CheckArchiveError> {
+        let header = header_at(archive, pos)?;
+        let bytes = match header[0] & FLAG {
+            FLAG => {
+                let len = (header[0] >> 1) as usize;
+                if len > ARCHIVED_INLINE_CAPACITY {
+                    return Err(CheckArchiveError::InvalidLength);
+                }
+                &header[1..][..len]
+            }
+            _ => {
+                let (len, offset) = decode_long(header);
+                // the archiver only writes long headers for strings that don't fit inline
+                if len <= ARCHIVED_INLINE_CAPACITY as u64 {
+                    return Err(CheckArchiveError::InvalidLength);
+                }
+                if header[16..] != [0; 8] {
+                    return Err(CheckArchiveError::NonZeroPadding);
+                }
+                let start = i64::try_from(pos)
+                    .ok()
+                    .and_then(|pos| pos.checked_add(offset))
+                    .and_then(|start| usize::try_from(start).ok())
+                    .ok_or(CheckArchiveError::OutOfBounds)?;
+                let len = usize::try_from(len).map_err(|_| CheckArchiveError::OutOfBounds)?;
+                start
+                    .checked_add(len)
+                    .and_then(|end| archive.get(start..end))
+                    .ok_or(CheckArchiveError::OutOfBounds)?
+            }
+        };
+        let str = std::str::from_utf8(bytes).map_err(CheckArchiveError::InvalidUtf8)?;
+        Ok(Self { header, str })
+    }
+
+    /// Reads the archived string whose header starts at `pos` in `archive`, without checking it
+    ///
+    /// # Safety
+    /// - [`ArchivedSsoString::access`] must succeed for the same `archive` and `pos`
+    pub unsafe fn access_unchecked(archive: &'a [u8], pos: usize) -> Self {
+        // SAFETY: the header is in bounds (caller contract)
+        let header = unsafe { &*archive.as_ptr().add(pos).cast::<[u8; ARCHIVED_SIZE]>() };
+        let bytes = if header[0] & FLAG != 0 {
+            // SAFETY: the length is at most ARCHIVED_INLINE_CAPACITY (caller contract)
+            unsafe { header.get_unchecked(1..1 + (header[0] >> 1) as usize) }
+        } else {
+            let (len, offset) = decode_long(header);
+            let start = (pos as i64 + offset) as usize;
+            // SAFETY: the bytes are in bounds (caller contract)
+            unsafe { archive.get_unchecked(start..start + len as usize) }
+        };
+        Self {
+            header,
+            // SAFETY: the bytes are valid utf-8 (caller contract)
+            str: unsafe { std::str::from_utf8_unchecked(bytes) },
+        }
+    }
+
+    /// Returns `true` if the string is stored inline in its header
+    pub fn is_inline(&self) -> bool {
+        self.header[0] & FLAG != 0
+    }
+
+    /// Returns the string, which borrows from the archive instead of from `self`
+    pub fn as_str(&self) -> &'a str {
+        self.str
+    }
+
+    /// Copies the string out of the archive, strings that were inline in the archive are inline
+    /// in the result as well on 64-bit targets
+    pub fn to_sso_string(&self) -> SsoString {
+        SsoString::from(self.str)
+    }
+}
+
+/// Returns the header that starts at `pos`
+fn header_at(archive: &[u8], pos: usize) -> Result<&[u8; ARCHIVED_SIZE], CheckArchiveError> {
+    pos.checked_add(ARCHIVED_SIZE)
+        .and_then(|end| archive.get(pos..end))
+        .and_then(|header| header.try_into().ok())
+        .ok_or(CheckArchiveError::OutOfBounds)
+}
+
+/// Returns the length and offset of a long string
+fn decode_long(header: &[u8; ARCHIVED_SIZE]) -> (u64, i64) {
+    let len = u64::from_le_bytes(header[0..8].try_into().expect("8 bytes"));
+    let offset = i64::from_le_bytes(header[8..16].try_into().expect("8 bytes"));
+    (len >> 1, offset)
+}
+
+impl Deref for ArchivedSsoString<'_> {
+    type Target = str;
+
+    fn deref(&self) -> &Self::Target {
+        self.str
+    }
+}
+
+impl AsRef<str> for ArchivedSsoString<'_> {
+    fn as_ref(&self) -> &str {
+        self.str
+    }
+}
+
+impl fmt::Debug for ArchivedSsoString<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.str, f)
+    }
+}
+
+impl fmt::Display for ArchivedSsoString<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self.str, f)
+    }
+}
+
+impl PartialEq for ArchivedSsoString<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.str == other.str
+    }
+}
+
+impl Eq for ArchivedSsoString<'_> {}
+
+impl PartialEq<str> for ArchivedSsoString<'_> {
+    fn eq(&self, other: &str) -> bool {
+        self.str == other
+    }
+}
+
+impl PartialEq<&str> for ArchivedSsoString<'_> {
+    fn eq(&self, other: &&str) -> bool {
+        self.str == *other
+    }
+}
+
+impl From<ArchivedSsoString<'_>> for SsoString {
+    fn from(value: ArchivedSsoString<'_>) -> Self {
+        value.to_sso_string()
+    }
+}
+
+/// A run of archived string headers, as written by [`ArchiveWriter::write_table`], that has been
+/// checked as a whole. Looking up a string is O(1), and never checks anything again.
+#[derive(Clone, Copy, Debug)]
+pub struct ArchivedStringTable<'a> {
+    /// # Safety
+    /// - `ArchivedSsoString::access(archive, pos + i * ARCHIVED_SIZE)` succeeds for every `i < len`
+    archive: &'a [u8],
+    pos: usize,
+    len: usize,
+}
+
+impl<'a> ArchivedStringTable<'a> {
+    /// Checks and reads the `len` archived strings whose headers start at `pos` in `archive`
+    pub fn access(archive: &'a [u8], pos: usize, len: usize) -> Result<Self, CheckArchiveError> {
+        for i in 0..len {
+            let header = i
+                .checked_mul(ARCHIVED_SIZE)
+                .and_then(|offset| pos.checked_add(offset))
+                .ok_or(CheckArchiveError::OutOfBounds)?;
+            ArchivedSsoString::access(archive, header)?;
+        }
+        Ok(Self { archive, pos, len })
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get(&self, idx: usize) -> Option<ArchivedSsoString<'a>> {
+        if idx >= self.len {
+            return None;
+        }
+        // SAFETY: every string in the table has been checked (field contract)
+        Some(unsafe {
+            ArchivedSsoString::access_unchecked(self.archive, self.pos + idx * ARCHIVED_SIZE)
+        })
+    }
+
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = ArchivedSsoString<'a>> + 'a {
+        let table = *self;
+        (0..self.len).map(move |idx| table.get(idx).expect("the index is in bounds"))
+    }
+}
+
+/// Why an archived string is invalid
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CheckArchiveError {
+    /// the header or the bytes of a long string are not within the archive
+    OutOfBounds,
+    /// the length of an inline string is larger than [`ARCHIVED_INLINE_CAPACITY`], or the length of
+    /// a long string is not
+    InvalidLength,
+    /// the last 8 bytes of a long string header are not 0
+    NonZeroPadding,
+    InvalidUtf8(Utf8Error),
+}
+
+impl fmt::Display for CheckArchiveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::OutOfBounds => f.write_str("archived string is out of bounds"),
+            Self::InvalidLength => f.write_str("archived string length does not match its kind"),
+            Self::NonZeroPadding => f.write_str("archived long string has non-zero padding"),
+            Self::InvalidUtf8(err) => write!(f, "archived string is not utf-8: {err}"),
+        }
+    }
+}
+
+impl Error for CheckArchiveError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::InvalidUtf8(err) => Some(err),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,455 @@
+//! A [`StringN`](crate::StringN) that keeps track of how many chars it holds, for callers that
+//! need `.chars().count()` or char indexing all the time.
+//!
+//! The count lives next to the string instead of in its spare bits: a short string can use every
+//! bit of its length byte that is not the tag bit (`N` goes up to 126), and the length of a long
+//! string is shifted past the tag bit on little-endian targets, so neither has a bit to spare.
+
+use std::{
+    borrow::Borrow,
+    cmp,
+    collections::TryReserveError,
+    fmt,
+    hash::{Hash, Hasher},
+    ops::{Bound, Deref, RangeBounds},
+};
+
+use crate::{
+    sso_string::{ShortString, SsoStringN},
+    unified_alloc::{Allocator, Global},
+};
+
+/// A [`StringN`](crate::StringN) that caches its char count, so that
+/// [`CharCountedStringN::char_count`] and [`CharCountedStringN::is_ascii`] are O(1), as is
+/// [`CharCountedStringN::char_at`] for ASCII strings. Every method that changes the string keeps
+/// the count up to date, and anything else can be done through [`CharCountedStringN::with_mut`],
+/// which counts again afterwards.
+pub struct CharCountedStringN<const N: usize, A: Allocator = Global> {
+    string: SsoStringN<N, A>,
+    /// always `string.chars().count()`
+    chars: usize,
+}
+
+/// The [`CharCountedStringN`] that stores as many bytes inline as a [`SsoString`]
+///
+/// [`SsoString`]: crate::String
+pub type CharCountedString<A = Global> = CharCountedStringN<{ ShortString::MAX_CAPACITY }, A>;
+
+impl<const N: usize> CharCountedStringN<N> {
+    /// Constructs an empty (short) string, this can be used to initialise a `static`
+    pub const fn new() -> Self {
+        Self {
+            string: SsoStringN::new(),
+            chars: 0,
+        }
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::from(SsoStringN::with_capacity(capacity))
+    }
+}
+
+impl<const N: usize, A: Allocator> CharCountedStringN<N, A> {
+    /// Constructs an empty (short) string that will use `alloc` if it ever has to allocate
+    pub fn new_in(alloc: A) -> Self {
+        Self::from(SsoStringN::new_in(alloc))
+    }
+
+    /// Returns the number of chars in this string, without looking at them
+    pub fn char_count(&self) -> usize {
+        self.chars
+    }
+
+    /// Returns `true` if every char of this string is ASCII, without looking at them
+    pub fn is_ascii(&self) -> bool {
+        self.chars == self.string.len()
+    }
+
+    /// Returns the char at char index `idx`, or `None` if it is out of bounds. This is O(1) for
+    /// ASCII strings, and O(n) otherwise.
+    pub fn char_at(&self, idx: usize) -> Option<char> {
+        if self.is_ascii() {
+            self.string.as_bytes().get(idx).map(|&b| char::from(b))
+        } else {
+            self.string.chars().nth(idx)
+        }
+    }
+
+    /// Counts the chars of `s`, which has to be a part of this string
+    fn count_within(&self, s: &str) -> usize {
+        // a part of an ASCII string is ASCII as well
+        if self.is_ascii() {
+            s.len()
+        } else {
+            s.chars().count()
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        self.string.as_str()
+    }
+
+    pub fn as_sso_string(&self) -> &SsoStringN<N, A> {
+        &self.string
+    }
+
+    /// Gives `f` mutable access to the string, and counts its chars again afterwards, even if `f`
+    /// panics
+    pub fn with_mut<R>(&mut self, f: impl FnOnce(&mut SsoStringN<N, A>) -> R) -> R {
+        let guard = Recount(self);
+        f(&mut guard.0.string)
+    }
+
+    pub fn into_sso_string(self) -> SsoStringN<N, A> {
+        self.string
+    }
+
+    /// Returns `true` if the string is stored inline (no heap allocations), and `false` otherwise
+    pub fn is_short(&self) -> bool {
+        self.string.is_short()
+    }
+
+    /// Returns `true` if this string owns a heap buffer. Every string is exactly one of short,
+    /// long or static.
+    pub fn is_long(&self) -> bool {
+        self.string.is_long()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.string.capacity()
+    }
+
+    pub fn push(&mut self, ch: char) {
+        self.string.push(ch);
+        self.chars += 1;
+    }
+
+    pub fn push_str(&mut self, s: &str) {
+        self.string.push_str(s);
+        self.chars += s.chars().count();
+    }
+
+    pub fn pop(&mut self) -> Option<char> {
+        let ch = self.string.pop()?;
+        self.chars -= 1;
+        Some(ch)
+    }
+
+    /// Inserts `ch` at byte index `idx`
+    ///
+    /// Panics if `idx` does not lie on a char boundary, or is out of bounds.
+    pub fn insert(&mut self, idx: usize, ch: char) {
+        self.string.insert(idx, ch);
+        self.chars += 1;
+    }
+
+    /// Inserts `s` at byte index `idx`
+    ///
+    /// Panics if `idx` does not lie on a char boundary, or is out of bounds.
+    pub fn insert_str(&mut self, idx: usize, s: &str) {
+        self.string.insert_str(idx, s);
+        self.chars += s.chars().count();
+    }
+
+    /// Removes and returns the char at byte index `idx`
+    ///
+    /// Panics if `idx` does not lie on a char boundary, or is larger than or equal to the length.
+    pub fn remove(&mut self, idx: usize) -> char {
+        let ch = self.string.remove(idx);
+        self.chars -= 1;
+        ch
+    }
+
+    /// Shortens this string to `new_len` bytes. Does nothing if `new_len` is greater than the
+    /// current length.
+    ///
+    /// Panics if `new_len` does not lie on a char boundary.
+    pub fn truncate(&mut self, new_len: usize) {
+        if new_len >= self.string.len() {
+            return;
+        }
+        let removed = self.count_within(&self.string[new_len..]);
+        self.string.truncate(new_len);
+        self.chars -= removed;
+    }
+
+    pub fn clear(&mut self) {
+        self.string.clear();
+        self.chars = 0;
+    }
+
+    /// Splits the string into two at byte index `at`, returning everything after it
+    ///
+    /// Panics if `at` does not lie on a char boundary, or is out of bounds.
+    pub fn split_off(&mut self, at: usize) -> Self
+    where
+        A: Clone,
+    {
+        let removed = self.count_within(&self.string[at..]);
+        let tail = self.string.split_off(at);
+        self.chars -= removed;
+        Self {
+            string: tail,
+            chars: removed,
+        }
+    }
+
+    /// Replaces `self[range]` with `replace_with`
+    ///
+    /// Panics if the start or end of the range do not lie on a char boundary, or if they are out of
+    /// bounds.
+    pub fn replace_range<R>(&mut self, range: R, replace_with: &str)
+    where
+        R: RangeBounds<usize>,
+    {
+        let range = bounds(&range);
+        let removed = self.count_within(&self.string[range]);
+        self.string.replace_range(range, replace_with);
+        self.chars = self.chars - removed + replace_with.chars().count();
+    }
+
+    /// Copies `self[src]` to the end of the string
+    ///
+    /// Panics if the start or end of the range do not lie on a char boundary, or if they are out of
+    /// bounds.
+    pub fn extend_from_within<R>(&mut self, src: R)
+    where
+        R: RangeBounds<usize>,
+    {
+        let src = bounds(&src);
+        let added = self.count_within(&self.string[src]);
+        self.string.extend_from_within(src);
+        self.chars += added;
+    }
+
+    /// Retains only the chars specified by the predicate
+    pub fn retain<F>(&mut self, f: F)
+    where
+        F: FnMut(char) -> bool,
+    {
+        // the count is only known once every char has been looked at, so a panicking predicate
+        // has to be covered as well
+        self.with_mut(|string| string.retain(f))
+    }
+
+    pub fn make_ascii_uppercase(&mut self) {
+        self.string.make_ascii_uppercase()
+    }
+
+    pub fn make_ascii_lowercase(&mut self) {
+        self.string.make_ascii_lowercase()
+    }
+
+    pub fn reserve(&mut self, additional: usize) {
+        self.string.reserve(additional)
+    }
+
+    pub fn reserve_exact(&mut self, additional: usize) {
+        self.string.reserve_exact(additional)
+    }
+
+    pub fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        self.string.try_reserve(additional)
+    }
+
+    pub fn try_reserve_exact(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        self.string.try_reserve_exact(additional)
+    }
+
+    pub fn shrink_to(&mut self, min_capacity: usize) {
+        self.string.shrink_to(min_capacity)
+    }
+
+    /// Shrinks the capacity of this string as much as possible, which moves it inline if it fits
+    pub fn shrink_to_fit(&mut self) {
+        self.string.shrink_to_fit()
+    }
+}
+
+/// Copies the bounds of `range`, so that it can be used to index twice
+fn bounds<R: RangeBounds<usize>>(range: &R) -> (Bound<usize>, Bound<usize>) {
+    (range.start_bound().cloned(), range.end_bound().cloned())
+}
+
+/// Counts the chars of a string again when dropped
+struct Recount<'a, const N: usize, A: Allocator>(&'a mut CharCountedStringN<N, A>);
+
+impl<const N: usize, A: Allocator> Drop for Recount<'_, N, A> {
+    fn drop(&mut self) {
+        self.0.chars = self.0.string.chars().count();
+    }
+}
+
+impl<const N: usize, A: Allocator> From<SsoStringN<N, A>> for CharCountedStringN<N, A> {
+    /// Counts the chars of `value` once
+    fn from(value: SsoStringN<N, A>) -> Self {
+        Self {
+            chars: value.chars().count(),
+            string: value,
+        }
+    }
+}
+
+impl<'a, const N: usize> From<&'a str> for CharCountedStringN<N> {
+    fn from(value: &'a str) -> Self {
+        Self::from(SsoStringN::from(value))
+    }
+}
+
+impl<const N: usize> From<String> for CharCountedStringN<N> {
+    fn from(value: String) -> Self {
+        Self::from(SsoStringN::from(value))
+    }
+}
+
+impl<const N: usize, A: Allocator> From<CharCountedStringN<N, A>> for SsoStringN<N, A> {
+    fn from(value: CharCountedStringN<N, A>) -> Self {
+        value.string
+    }
+}
+
+impl<const N: usize, A: Allocator> Deref for CharCountedStringN<N, A> {
+    type Target = str;
+
+    fn deref(&self) -> &Self::Target {
+        self.as_str()
+    }
+}
+
+impl<const N: usize, A: Allocator> AsRef<str> for CharCountedStringN<N, A> {
+    fn as_ref(&self) -> &str {
+        self
+    }
+}
+
+impl<const N: usize, A: Allocator> Borrow<str> for CharCountedStringN<N, A> {
+    fn borrow(&self) -> &str {
+        self
+    }
+}
+
+impl<const N: usize, A: Allocator + Clone> Clone for CharCountedStringN<N, A> {
+    fn clone(&self) -> Self {
+        Self {
+            string: self.string.clone(),
+            chars: self.chars,
+        }
+    }
+}
+
+impl<const N: usize, A: Allocator + Default> Default for CharCountedStringN<N, A> {
+    fn default() -> Self {
+        Self::new_in(A::default())
+    }
+}
+
+impl<const N: usize, A: Allocator> fmt::Display for CharCountedStringN<N, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self.as_str(), f)
+    }
+}
+
+impl<const N: usize, A: Allocator> fmt::Debug for CharCountedStringN<N, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl<const N: usize, A: Allocator> fmt::Write for CharCountedStringN<N, A> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.push_str(s);
+        Ok(())
+    }
+
+    fn write_char(&mut self, c: char) -> fmt::Result {
+        self.push(c);
+        Ok(())
+    }
+}
+
+impl<const N: usize, A: Allocator> Extend<char> for CharCountedStringN<N, A> {
+    fn extend<I: IntoIterator<Item = char>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        self.string.reserve_to_fit(iter.size_hint().0);
+        iter.for_each(|ch| self.push(ch));
+    }
+}
+
+impl<'a, const N: usize, A: Allocator> Extend<&'a str> for CharCountedStringN<N, A> {
+    fn extend<I: IntoIterator<Item = &'a str>>(&mut self, iter: I) {
+        iter.into_iter().for_each(|s| self.push_str(s));
+    }
+}
+
+impl<const N: usize> FromIterator<char> for CharCountedStringN<N> {
+    fn from_iter<I: IntoIterator<Item = char>>(iter: I) -> Self {
+        let mut string = Self::new();
+        string.extend(iter);
+        string
+    }
+}
+
+impl<'a, const N: usize> FromIterator<&'a str> for CharCountedStringN<N> {
+    fn from_iter<I: IntoIterator<Item = &'a str>>(iter: I) -> Self {
+        let mut string = Self::new();
+        string.extend(iter);
+        string
+    }
+}
+
+impl<const N: usize, const M: usize, A: Allocator, B: Allocator> PartialEq<CharCountedStringN<M, B>>
+    for CharCountedStringN<N, A>
+{
+    fn eq(&self, other: &CharCountedStringN<M, B>) -> bool {
+        // strings with a different char count can never be equal
+        self.chars == other.chars && self.as_str() == other.as_str()
+    }
+}
+
+impl<const N: usize, A: Allocator> Eq for CharCountedStringN<N, A> {}
+
+impl<const N: usize, const M: usize, A: Allocator, B: Allocator>
+    PartialOrd<CharCountedStringN<M, B>> for CharCountedStringN<N, A>
+{
+    fn partial_cmp(&self, other: &CharCountedStringN<M, B>) -> Option<cmp::Ordering> {
+        Some(self.as_str().cmp(other.as_str()))
+    }
+}
+
+impl<const N: usize, A: Allocator> Ord for CharCountedStringN<N, A> {
+    fn cmp(&self, other: &Self) -> cmp::Ordering {
+        self.as_str().cmp(other.as_str())
+    }
+}
+
+macro_rules! impl_eq_str {
+    ([$($generics:tt)*] $Other:ty) => {
+        impl<$($generics)* const N: usize, A: Allocator> PartialEq<$Other>
+            for CharCountedStringN<N, A>
+        {
+            fn eq(&self, other: &$Other) -> bool {
+                self.as_str() == &other[..]
+            }
+        }
+
+        impl<$($generics)* const N: usize, A: Allocator> PartialEq<CharCountedStringN<N, A>>
+            for $Other
+        {
+            fn eq(&self, other: &CharCountedStringN<N, A>) -> bool {
+                other == self
+            }
+        }
+    };
+}
+
+impl_eq_str!([] str);
+impl_eq_str!(['a,] &'a str);
+impl_eq_str!([] String);
+impl_eq_str!([const M: usize, B: Allocator,] SsoStringN<M, B>);
+
+impl<const N: usize, A: Allocator> Hash for CharCountedStringN<N, A> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // exactly the same as `str`, so that lookups through `Borrow<str>` work
+        self.as_str().hash(state)
+    }
+}
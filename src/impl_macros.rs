@@ -25,6 +25,7 @@ macro_rules! duck_impl {
             match self.tagged() {
                 TaggedSsoString64::Short(short) => short.$method($($($value),*)?),
                 TaggedSsoString64::Long(long) => long.$method($($($value),*)?),
+                TaggedSsoString64::Static(s) => s.$method($($($value),*)?),
             }
         }
     };
@@ -52,6 +53,7 @@ macro_rules! duck_impl {
             match self.tagged() {
                 TaggedSsoString64::Short(short) => short.$method($($($value),*)?),
                 TaggedSsoString64::Long(long) => long.$method($($($value),*)?),
+                TaggedSsoString64::Static(s) => s.$method($($($value),*)?),
             }
         }
     };
@@ -227,7 +229,9 @@ macro_rules! duck_impl {
     };
 }
 
-pub const TODO_IMPL_MESSAGE: &'static str =
+// only used by `todo_impl!`, which has nothing to stand in for while every method is implemented
+#[allow(dead_code)]
+pub const TODO_IMPL_MESSAGE: &str =
     "This method exists on std::string::String, but does not yet exist on SsoString";
 
 #[macro_export]
@@ -292,7 +296,7 @@ macro_rules! todo_impl {
             $($($a),+,)?
             $($TParam),*
         >)?(
-            &self, $($($value: $T),*)?
+            self, $($($value: $T),*)?
         ) $(-> $Returns)? 
         $(where $($wherett)*)?
         {
@@ -421,7 +425,9 @@ macro_rules! todo_impl {
     };
 }
 
-pub const NEVER_IMPL_MESSAGE: &'static str = concat!(
+// only used by `never_impl!`, which has nothing to stand in for now that `as_mut_vec` exists
+#[allow(dead_code)]
+pub const NEVER_IMPL_MESSAGE: &str = concat!(
     "This method exists on std::string::String, but will never exist on SsoString because of ",
     "trade-offs required to allow for the optimisation."
 );
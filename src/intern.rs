@@ -0,0 +1,403 @@
+//! Interning of strings into small [`Symbol`]s. Strings that fit in a [`ShortString`] are stored
+//! inline in their symbol, so reading them never touches the interner. Longer strings are copied
+//! into an arena that belongs to the interner, and their symbol is an index into it.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    hash::{Hash, Hasher},
+    iter::FusedIterator,
+    mem::{self, MaybeUninit},
+    slice,
+    sync::{PoisonError, RwLock, RwLockReadGuard},
+};
+
+use crate::{
+    sso_string::{LongString, RawSsoStringN, ShortString, SsoStr, SsoString},
+    unsafe_field::UnsafeField,
+};
+
+/// The size of the chunks that the arena copies long strings into. Strings that are longer than
+/// this get a chunk of their own.
+const CHUNK_CAPACITY: usize = 4096;
+
+/// The arena variant of a [`Symbol`], laid out like the start of a [`LongString`], so the tag bit
+/// of a [`ShortString`] overlaps with its length in the same way
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct ArenaSymbol {
+    /// # Safety
+    /// - the bit that overlaps with the tag bit of a [`ShortString`] is always 0
+    ///
+    /// when shifted by `>> LongString::LEN_SHIFT`:
+    /// - `len` is greater than [`ShortString::MAX_CAPACITY`]
+    len: UnsafeField<usize, 0>,
+    /// The index of the string in the arena of the interner that created this symbol
+    index: UnsafeField<usize, 1>,
+}
+
+impl ArenaSymbol {
+    fn len(&self) -> usize {
+        *self.len.get() >> <LongString>::LEN_SHIFT
+    }
+
+    fn index(&self) -> usize {
+        *self.index.get()
+    }
+}
+
+enum TaggedSymbol<'a> {
+    Inline(&'a ShortString),
+    Arena(&'a ArenaSymbol),
+}
+
+type RawSymbol = RawSsoStringN<{ ShortString::MAX_CAPACITY }>;
+
+/// A handle to an interned string, which is as big as a [`SsoString`]. Strings of up to
+/// [`ShortString::MAX_CAPACITY`] bytes are stored inline, and can be read with
+/// [`Symbol::as_inline_str`] without the interner. Longer strings are an index into the arena of
+/// the interner that created the symbol, so they have to be resolved with that interner.
+///
+/// Two symbols from the same interner are equal if and only if their strings are equal.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct Symbol {
+    /// # Safety
+    /// - the tag bit of `raw.tag` says which variant is active
+    /// - for inline symbols, a valid [`ShortString`] starts at `raw`
+    /// - for arena symbols, a valid [`ArenaSymbol`] starts at `raw`
+    raw: RawSymbol,
+}
+
+const _: () = assert!(mem::size_of::<Symbol>() == mem::size_of::<SsoString>());
+const _: () = assert!(mem::size_of::<ArenaSymbol>() <= mem::size_of::<RawSymbol>());
+const _: () = assert!(mem::size_of::<Option<Symbol>>() == mem::size_of::<Symbol>());
+
+impl Symbol {
+    /// Stores `s` inline, or returns `None` if it is longer than [`ShortString::MAX_CAPACITY`]
+    fn inline(s: &str) -> Option<Self> {
+        let short = ShortString::try_from_str(s)?;
+        let mut this = MaybeUninit::<Self>::uninit();
+        // SAFETY:
+        // - raw is as big and aligned as a short string
+        // - the tag of a short string is always valid, and everything after it is allowed to be
+        //   uninitialised
+        unsafe {
+            this.as_mut_ptr().cast::<ShortString>().write(short);
+            Some(this.assume_init())
+        }
+    }
+
+    /// # Safety
+    /// - `len` must be greater than [`ShortString::MAX_CAPACITY`], and at most
+    ///   [`LongString::MAX_CAPACITY`]
+    unsafe fn arena(len: usize, index: usize) -> Self {
+        let arena = ArenaSymbol {
+            // SAFETY: shifting by LEN_SHIFT clears the tag bit, the caller upholds the rest
+            len: unsafe { UnsafeField::new(len << <LongString>::LEN_SHIFT) },
+            // SAFETY: index has no invariants
+            index: unsafe { UnsafeField::new(index) },
+        };
+        let mut this = MaybeUninit::<Self>::uninit();
+        // SAFETY:
+        // - raw is at least as big as an arena symbol, and aligned for a usize
+        // - the first byte of the length of an arena symbol is always a valid tag, and everything
+        //   after the index is allowed to be uninitialised
+        unsafe {
+            this.as_mut_ptr().cast::<ArenaSymbol>().write(arena);
+            this.assume_init()
+        }
+    }
+
+    /// Returns `true` if the string is stored in this symbol, rather than in the arena
+    pub fn is_inline(&self) -> bool {
+        // the tag is the first byte of both variants
+        (self.raw.tag as u8 & ShortString::FLAG) != 0
+    }
+
+    fn tagged(&self) -> TaggedSymbol<'_> {
+        let raw = &self.raw as *const RawSymbol;
+        if self.is_inline() {
+            // SAFETY: a valid short string starts at raw
+            TaggedSymbol::Inline(unsafe { &*raw.cast::<ShortString>() })
+        } else {
+            // SAFETY: a valid arena symbol starts at raw
+            TaggedSymbol::Arena(unsafe { &*raw.cast::<ArenaSymbol>() })
+        }
+    }
+
+    /// Returns the length of the string in bytes, without the interner
+    pub fn len(&self) -> usize {
+        match self.tagged() {
+            TaggedSymbol::Inline(short) => short.len(),
+            TaggedSymbol::Arena(arena) => arena.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the string if it is stored inline, without the interner
+    pub fn as_inline_str(&self) -> Option<&str> {
+        match self.tagged() {
+            TaggedSymbol::Inline(short) => Some(short.as_str()),
+            TaggedSymbol::Arena(_) => None,
+        }
+    }
+}
+
+impl PartialEq for Symbol {
+    fn eq(&self, other: &Self) -> bool {
+        match (self.tagged(), other.tagged()) {
+            (TaggedSymbol::Inline(a), TaggedSymbol::Inline(b)) => a.as_str() == b.as_str(),
+            // every string is interned once, so its index is enough
+            (TaggedSymbol::Arena(a), TaggedSymbol::Arena(b)) => a.index() == b.index(),
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Symbol {}
+
+impl Hash for Symbol {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match self.tagged() {
+            TaggedSymbol::Inline(short) => short.as_str().hash(state),
+            TaggedSymbol::Arena(arena) => arena.index().hash(state),
+        }
+    }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.tagged() {
+            TaggedSymbol::Inline(short) => f.debug_tuple("Symbol").field(&short.as_str()).finish(),
+            TaggedSymbol::Arena(arena) => f.debug_tuple("Symbol").field(&arena.index()).finish(),
+        }
+    }
+}
+
+/// Interns strings into [`Symbol`]s. Strings longer than [`ShortString::MAX_CAPACITY`] are copied
+/// into chunks that are never moved or freed until the interner is dropped, so a resolved string
+/// stays valid while more strings are interned by a [`SyncInterner`].
+#[derive(Default)]
+pub struct Interner {
+    /// # Safety
+    /// - the keys point into `chunks`, this is declared first so that they are dropped first
+    map: HashMap<&'static str, Symbol>,
+    /// the inline symbols that have been interned, they are only kept to count and iterate them
+    inline: HashSet<Symbol>,
+    /// every symbol, in the order that it was first interned
+    symbols: Vec<Symbol>,
+    /// # Safety
+    /// - the strings point into `chunks`, and `arena[i]` is the string of the symbol with index `i`
+    arena: Vec<&'static str>,
+    /// # Safety
+    /// - every chunk is a long string that is never reallocated, only pushed to, so the strings in
+    ///   `arena` stay valid until the chunks are dropped
+    chunks: Vec<SsoString>,
+}
+
+impl Interner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the symbol of `s`, interning it if it hasn't been interned yet. Short strings are
+    /// never copied into the arena.
+    pub fn get_or_intern(&mut self, s: &str) -> Symbol {
+        if let Some(symbol) = Symbol::inline(s) {
+            if self.inline.insert(symbol) {
+                self.symbols.push(symbol);
+            }
+            return symbol;
+        }
+        if let Some(&symbol) = self.map.get(s) {
+            return symbol;
+        }
+
+        let stored = self.alloc(s);
+        // SAFETY: `s` did not fit inline, and it was copied into a chunk, which is a long string
+        let symbol = unsafe { Symbol::arena(s.len(), self.arena.len()) };
+        self.arena.push(stored);
+        self.map.insert(stored, symbol);
+        self.symbols.push(symbol);
+        symbol
+    }
+
+    /// Returns the symbol of `s` if it has been interned
+    pub fn get(&self, s: &str) -> Option<Symbol> {
+        match Symbol::inline(s) {
+            Some(symbol) => self.inline.get(&symbol).copied(),
+            None => self.map.get(s).copied(),
+        }
+    }
+
+    /// Returns the string of `symbol`. Inline symbols are read without a lookup.
+    ///
+    /// Panics, or returns an unrelated string, if `symbol` was created by another interner.
+    pub fn resolve<'a>(&'a self, symbol: &'a Symbol) -> &'a str {
+        match symbol.tagged() {
+            TaggedSymbol::Inline(short) => short.as_str(),
+            TaggedSymbol::Arena(arena) => self.arena[arena.index()],
+        }
+    }
+
+    /// The same as [`Interner::resolve`], but returns an `SsoStr`, which `to_owned` turns into an
+    /// `SsoString`
+    pub fn resolve_sso<'a>(&'a self, symbol: &'a Symbol) -> &'a SsoStr {
+        SsoStr::from_str(self.resolve(symbol))
+    }
+
+    /// Returns the number of distinct strings that have been interned
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    /// Iterates over every symbol and its string, in the order they were first interned
+    pub fn iter(&self) -> Iter<'_> {
+        Iter {
+            interner: self,
+            symbols: self.symbols.iter(),
+        }
+    }
+
+    /// Copies `s` into the last chunk, or into a new chunk if it doesn't fit
+    fn alloc(&mut self, s: &str) -> &'static str {
+        let fits = self
+            .chunks
+            .last()
+            .is_some_and(|chunk| chunk.capacity() - chunk.len() >= s.len());
+        if !fits {
+            let capacity = s.len().max(CHUNK_CAPACITY);
+            self.chunks.push(SsoString::with_capacity(capacity));
+        }
+
+        let chunk = self.chunks.last_mut().expect("a chunk was just pushed");
+        let start = chunk.len();
+        // never reallocates, since `s` fits in the remaining capacity
+        chunk.push_str(s);
+        let stored: *const str = &chunk[start..];
+        // SAFETY: the chunk is a long string that is never reallocated, so its buffer lives, and
+        // these bytes stay the same, until the interner is dropped
+        unsafe { &*stored }
+    }
+}
+
+impl<'a> IntoIterator for &'a Interner {
+    type Item = (Symbol, &'a str);
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl fmt::Debug for Interner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+/// An iterator over the symbols of an [`Interner`] and their strings, see [`Interner::iter`]
+pub struct Iter<'a> {
+    interner: &'a Interner,
+    symbols: slice::Iter<'a, Symbol>,
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = (Symbol, &'a str);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let symbol = self.symbols.next()?;
+        Some((*symbol, self.interner.resolve(symbol)))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.symbols.size_hint()
+    }
+}
+
+impl ExactSizeIterator for Iter<'_> {}
+
+impl FusedIterator for Iter<'_> {}
+
+/// An [`Interner`] that can be shared between threads. Symbols are interchangeable with the
+/// symbols of the interner that it wraps.
+#[derive(Debug, Default)]
+pub struct SyncInterner {
+    inner: RwLock<Interner>,
+}
+
+impl SyncInterner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The same as [`Interner::get_or_intern`]. Strings that have been interned before only take
+    /// a read lock.
+    pub fn get_or_intern(&self, s: &str) -> Symbol {
+        if let Some(symbol) = self.get(s) {
+            return symbol;
+        }
+        self.inner
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get_or_intern(s)
+    }
+
+    /// The same as [`Interner::get`]
+    pub fn get(&self, s: &str) -> Option<Symbol> {
+        self.read().get(s)
+    }
+
+    /// The same as [`Interner::resolve`], but inline symbols are read without taking the lock
+    pub fn resolve<'a>(&'a self, symbol: &'a Symbol) -> &'a str {
+        match symbol.tagged() {
+            TaggedSymbol::Inline(short) => short.as_str(),
+            TaggedSymbol::Arena(arena) => {
+                let stored: *const str = self.read().arena[arena.index()];
+                // SAFETY: the arena is never moved or freed while `self` is borrowed, and the lock
+                // only guards the tables, not the bytes of strings that are already interned
+                unsafe { &*stored }
+            }
+        }
+    }
+
+    /// The same as [`Interner::resolve_sso`], but inline symbols are read without taking the lock
+    pub fn resolve_sso<'a>(&'a self, symbol: &'a Symbol) -> &'a SsoStr {
+        SsoStr::from_str(self.resolve(symbol))
+    }
+
+    pub fn len(&self) -> usize {
+        self.read().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.read().is_empty()
+    }
+
+    /// Locks the interner for reading, for example to iterate over it
+    pub fn read(&self) -> RwLockReadGuard<'_, Interner> {
+        self.inner.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub fn into_inner(self) -> Interner {
+        self.inner
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl From<Interner> for SyncInterner {
+    fn from(value: Interner) -> Self {
+        Self {
+            inner: RwLock::new(value),
+        }
+    }
+}
@@ -1,23 +1,99 @@
 #![doc = include_str!("../README.md")]
-#![cfg_attr(feature = "nightly", feature(allocator_api))]
+#![cfg_attr(feature = "nightly", feature(allocator_api, try_reserve_kind))]
 
+// lets the tests use the paths that `#[unsafe_fields]` generates
+#[cfg(all(test, feature = "derive"))]
+extern crate self as olis_string;
+
+#[cfg(any(target_pointer_width = "32", target_pointer_width = "64"))]
+pub mod archive;
+#[cfg(any(target_pointer_width = "32", target_pointer_width = "64"))]
+mod arc_sso_string;
+#[cfg(any(target_pointer_width = "32", target_pointer_width = "64"))]
+mod char_counted;
 mod impl_macros;
+#[cfg(any(target_pointer_width = "32", target_pointer_width = "64"))]
+pub mod intern;
+#[cfg(all(feature = "serde", any(target_pointer_width = "32", target_pointer_width = "64")))]
+mod serde_impls;
+#[cfg(any(target_pointer_width = "32", target_pointer_width = "64"))]
+mod sso_bytes;
+#[cfg(all(unix, any(target_pointer_width = "32", target_pointer_width = "64")))]
+mod sso_os_string;
+#[cfg(any(target_pointer_width = "32", target_pointer_width = "64"))]
 mod sso_string;
+#[cfg(any(target_pointer_width = "32", target_pointer_width = "64"))]
+pub mod sso_vec;
 pub mod unified_alloc;
 pub mod unsafe_field;
-use sso_string::{SsoString, SsoStr};
+#[cfg(any(target_pointer_width = "32", target_pointer_width = "64"))]
+use arc_sso_string::ArcSsoString;
+#[cfg(any(target_pointer_width = "32", target_pointer_width = "64"))]
+pub use char_counted::{CharCountedString, CharCountedStringN};
+#[cfg(any(target_pointer_width = "32", target_pointer_width = "64"))]
+pub use sso_bytes::IntoStringError;
+#[cfg(any(target_pointer_width = "32", target_pointer_width = "64"))]
+use sso_bytes::{SsoBytes, SsoBytesN};
+#[cfg(all(unix, any(target_pointer_width = "32", target_pointer_width = "64")))]
+use sso_os_string::{SsoOsString, SsoPathBuf};
+#[cfg(any(target_pointer_width = "32", target_pointer_width = "64"))]
+use sso_string::{SsoStr, SsoString, SsoStringN};
+#[cfg(any(target_pointer_width = "32", target_pointer_width = "64"))]
+pub use sso_string::{TaggedSsoString64, TaggedSsoString64Mut};
+#[cfg(any(target_pointer_width = "32", target_pointer_width = "64"))]
+pub use sso_vec::SsoVec;
 
-#[cfg(test)]
+#[cfg(all(test, any(target_pointer_width = "32", target_pointer_width = "64")))]
 mod tests;
 
-#[cfg(all(target_endian = "little", target_pointer_width = "64"))]
-pub type String = SsoString;
+#[cfg(any(target_pointer_width = "32", target_pointer_width = "64"))]
+pub type String<A = unified_alloc::Global> = SsoString<A>;
+
+/// A [`String`] that stores up to `N` bytes inline instead of 23 (or 11 on 32-bit targets)
+#[cfg(any(target_pointer_width = "32", target_pointer_width = "64"))]
+pub type StringN<const N: usize, A = unified_alloc::Global> = SsoStringN<N, A>;
 
-#[cfg(all(not(target_endian = "little"), not(target_pointer_width = "64")))]
+#[cfg(not(any(target_pointer_width = "32", target_pointer_width = "64")))]
 pub type String = std::string::String;
 
-#[cfg(all(target_endian = "little", target_pointer_width = "64"))]
+/// A byte buffer with the same layout as a [`String`], which stores up to 23 bytes (or 11 on
+/// 32-bit targets) inline
+#[cfg(any(target_pointer_width = "32", target_pointer_width = "64"))]
+pub type Bytes<A = unified_alloc::Global> = SsoBytes<A>;
+
+/// A [`Bytes`] that stores up to `N` bytes inline
+#[cfg(any(target_pointer_width = "32", target_pointer_width = "64"))]
+pub type BytesN<const N: usize, A = unified_alloc::Global> = SsoBytesN<N, A>;
+
+#[cfg(not(any(target_pointer_width = "32", target_pointer_width = "64")))]
+pub type Bytes = Vec<u8>;
+
+/// An [`OsString`](std::ffi::OsString) that stores up to 23 bytes (or 11 on 32-bit targets)
+/// inline, only on Unix where an `OsString` is just bytes
+#[cfg(all(unix, any(target_pointer_width = "32", target_pointer_width = "64")))]
+pub type OsString<A = unified_alloc::Global> = SsoOsString<A>;
+
+#[cfg(all(unix, not(any(target_pointer_width = "32", target_pointer_width = "64"))))]
+pub type OsString = std::ffi::OsString;
+
+/// A [`PathBuf`](std::path::PathBuf) that stores up to 23 bytes (or 11 on 32-bit targets)
+/// inline, only on Unix where a `PathBuf` is just bytes
+#[cfg(all(unix, any(target_pointer_width = "32", target_pointer_width = "64")))]
+pub type PathBuf<A = unified_alloc::Global> = SsoPathBuf<A>;
+
+#[cfg(all(unix, not(any(target_pointer_width = "32", target_pointer_width = "64"))))]
+pub type PathBuf = std::path::PathBuf;
+
+/// An immutable [`String`] that is cheap to clone, short strings are stored inline and long ones
+/// share a reference counted buffer
+#[cfg(any(target_pointer_width = "32", target_pointer_width = "64"))]
+pub type ArcString = ArcSsoString;
+
+#[cfg(not(any(target_pointer_width = "32", target_pointer_width = "64")))]
+pub type ArcString = std::sync::Arc<str>;
+
+#[cfg(any(target_pointer_width = "32", target_pointer_width = "64"))]
 pub type Str = SsoStr;
 
-#[cfg(all(not(target_endian = "little"), not(target_pointer_width = "64")))]
+#[cfg(not(any(target_pointer_width = "32", target_pointer_width = "64")))]
 pub type Str = str;
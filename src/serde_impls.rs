@@ -0,0 +1,108 @@
+//! `Serialize` and `Deserialize` for [`SsoStringN`] and [`SsoStr`], behind the `serde` feature.
+//! Both are (de)serialized exactly like a `str`.
+
+use std::{fmt, marker::PhantomData, str};
+
+use serde::{
+    de::{self, Unexpected, Visitor},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+
+use crate::{
+    sso_string::{SsoStr, SsoStringN},
+    unified_alloc::Allocator,
+};
+
+impl<const N: usize, A: Allocator> Serialize for SsoStringN<N, A> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl Serialize for SsoStr {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self)
+    }
+}
+
+/// Builds a [`SsoStringN`] from whatever string the deserializer has at hand, without going
+/// through a `std::string::String`
+struct SsoStringVisitor<const N: usize>;
+
+impl<const N: usize> SsoStringVisitor<N> {
+    /// Copies `v` straight into a short string if it fits, and into a new long one otherwise
+    fn copy(v: &str) -> SsoStringN<N> {
+        SsoStringN::from_short_str(v).unwrap_or_else(|| SsoStringN::from(v))
+    }
+}
+
+impl<const N: usize> Visitor<'_> for SsoStringVisitor<N> {
+    type Value = SsoStringN<N>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a string")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        Ok(Self::copy(v))
+    }
+
+    fn visit_borrowed_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        Ok(Self::copy(v))
+    }
+
+    /// Adopts the buffer of `v` if it does not fit in a short string
+    fn visit_string<E: de::Error>(self, v: String) -> Result<Self::Value, E> {
+        Ok(SsoStringN::from(v))
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+        match str::from_utf8(v) {
+            Ok(s) => Ok(Self::copy(s)),
+            Err(_) => Err(E::invalid_value(Unexpected::Bytes(v), &self)),
+        }
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+        match String::from_utf8(v) {
+            Ok(s) => Ok(SsoStringN::from(s)),
+            Err(err) => Err(E::invalid_value(Unexpected::Bytes(err.as_bytes()), &self)),
+        }
+    }
+}
+
+// the visitor has nowhere to get an allocator from, so only strings that use `Global` can be
+// deserialized
+impl<'de, const N: usize> Deserialize<'de> for SsoStringN<N> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_string(SsoStringVisitor)
+    }
+}
+
+/// Borrows a [`SsoStr`] from the input, like `&str` does
+struct SsoStrVisitor<'a>(PhantomData<&'a SsoStr>);
+
+impl<'de: 'a, 'a> Visitor<'de> for SsoStrVisitor<'a> {
+    type Value = &'a SsoStr;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a borrowed string")
+    }
+
+    fn visit_borrowed_str<E: de::Error>(self, v: &'de str) -> Result<Self::Value, E> {
+        Ok(SsoStr::from_str(v))
+    }
+
+    fn visit_borrowed_bytes<E: de::Error>(self, v: &'de [u8]) -> Result<Self::Value, E> {
+        match str::from_utf8(v) {
+            Ok(s) => Ok(SsoStr::from_str(s)),
+            Err(_) => Err(E::invalid_value(Unexpected::Bytes(v), &self)),
+        }
+    }
+}
+
+impl<'de: 'a, 'a> Deserialize<'de> for &'a SsoStr {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_str(SsoStrVisitor(PhantomData))
+    }
+}
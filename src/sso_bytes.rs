@@ -0,0 +1,583 @@
+use std::{
+    borrow::{Borrow, BorrowMut},
+    cmp,
+    collections::TryReserveError,
+    error::Error,
+    fmt,
+    hash::{Hash, Hasher},
+    io,
+    mem::ManuallyDrop,
+    ops::{Deref, DerefMut},
+    ptr::{self, NonNull},
+    slice,
+    str::Utf8Error,
+};
+
+use crate::{
+    sso_string::{LongString, ShortString, SsoStringN, TaggedSsoString64, TaggedSsoString64Mut},
+    unified_alloc::{Allocator, Global},
+};
+
+/// A byte buffer that stores up to `N` bytes inline, and moves to the heap when it grows any
+/// larger. It is a [`SsoStringN`] whose bytes do not have to be valid utf-8, so it has exactly the
+/// same layout and grows in the same way, and converting between the two never copies.
+#[repr(transparent)]
+pub struct SsoBytesN<const N: usize, A: Allocator = Global> {
+    /// # Safety
+    /// - a valid [`SsoStringN`], except that its bytes may be anything. Only the methods of
+    ///   [`SsoStringN`] that never look at the bytes as a `str` may be used on it.
+    inner: SsoStringN<N, A>,
+}
+
+/// The [`SsoBytesN`] that is exactly as big as a `Vec<u8>`: 23 bytes inline on 64-bit targets and
+/// 11 bytes inline on 32-bit targets
+pub type SsoBytes<A = Global> = SsoBytesN<{ ShortString::MAX_CAPACITY }, A>;
+
+impl<const N: usize> SsoBytesN<N> {
+    /// Constructs an empty (short) buffer, this can be used to initialise a `static`
+    pub const fn new() -> Self {
+        Self {
+            inner: SsoStringN::new(),
+        }
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::with_capacity_in(capacity, Global)
+    }
+}
+
+impl<const N: usize, A: Allocator> SsoBytesN<N, A> {
+    /// Constructs an empty (short) buffer that will use `alloc` if it ever has to allocate
+    pub fn new_in(alloc: A) -> Self {
+        Self {
+            inner: SsoStringN::new_in(alloc),
+        }
+    }
+
+    /// Constructs a buffer with at least `capacity` bytes of capacity, allocated with `alloc`.
+    /// Capacities up to `N` do not allocate.
+    pub fn with_capacity_in(capacity: usize, alloc: A) -> Self {
+        Self {
+            inner: SsoStringN::with_capacity_in(capacity, alloc),
+        }
+    }
+
+    /// Returns a reference to the allocator that backs this buffer
+    pub fn allocator(&self) -> &A {
+        self.inner.allocator()
+    }
+
+    /// Returns `true` if this buffer is stored inline (no heap allocations), and `false` otherwise
+    pub const fn is_short(&self) -> bool {
+        self.inner.is_short()
+    }
+
+    /// Returns `true` if this buffer owns a heap buffer
+    pub const fn is_long(&self) -> bool {
+        self.inner.is_long()
+    }
+
+    pub const fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of bytes this buffer can hold without reallocating
+    pub fn capacity(&self) -> usize {
+        self.inner.capacity()
+    }
+
+    pub const fn as_slice(&self) -> &[u8] {
+        self.inner.as_bytes()
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        let len = self.len();
+        // SAFETY: the first `len` bytes are initialised, and the pointer is valid for writes
+        unsafe { slice::from_raw_parts_mut(self.as_mut_ptr(), len) }
+    }
+
+    pub fn as_ptr(&self) -> *const u8 {
+        self.as_slice().as_ptr()
+    }
+
+    /// Returns a pointer to the start of the buffer, which is valid for writes of
+    /// `self.capacity()` bytes until the buffer is moved or reallocated
+    pub fn as_mut_ptr(&mut self) -> *mut u8 {
+        match self.inner.tagged_mut() {
+            // derived from `&mut short`, so it covers the whole inline buffer
+            TaggedSsoString64Mut::Short(short) => short.get_sized_buf_mut().cast::<u8>().as_ptr(),
+            TaggedSsoString64Mut::Long(long) => long.buf().as_ptr(),
+        }
+    }
+
+    /// Sets the length of this buffer, like `Vec::set_len`
+    ///
+    /// # Safety
+    /// - `len` must be less than or equal to `self.capacity()`
+    /// - the first `len` bytes must be initialised
+    pub unsafe fn set_len(&mut self, len: usize) {
+        // SAFETY: caller contract, the bytes do not have to be utf-8 (field contract)
+        unsafe { self.inner.set_len(len) }
+    }
+
+    pub fn push(&mut self, byte: u8) {
+        self.extend_from_slice(&[byte]);
+    }
+
+    pub fn pop(&mut self) -> Option<u8> {
+        let len = self.len().checked_sub(1)?;
+        let byte = self.as_slice()[len];
+        // SAFETY: shrinking is always valid
+        unsafe { self.set_len(len) };
+        Some(byte)
+    }
+
+    /// Appends all bytes of `other`, a short buffer is only upgraded if they do not fit in it
+    pub fn extend_from_slice(&mut self, other: &[u8]) {
+        self.inner.reserve_to_fit(other.len());
+        let len = self.len();
+        // SAFETY:
+        // - we just made room for `other.len()` more bytes
+        // - `other` cannot overlap with the spare capacity of `self`, which is borrowed mutably
+        unsafe {
+            ptr::copy_nonoverlapping(other.as_ptr(), self.as_mut_ptr().add(len), other.len());
+            self.set_len(len + other.len());
+        }
+    }
+
+    /// Inserts `byte` at position `idx`, moving all bytes after it to the right
+    ///
+    /// Panics if `idx > len`.
+    pub fn insert(&mut self, idx: usize, byte: u8) {
+        let len = self.len();
+        assert!(
+            idx <= len,
+            "insertion index (is {idx}) should be <= len (is {len})"
+        );
+        self.inner.reserve_to_fit(1);
+        // SAFETY: we just made room for one more byte, so both ranges are within the buffer
+        unsafe {
+            let p = self.as_mut_ptr().add(idx);
+            ptr::copy(p, p.add(1), len - idx);
+            p.write(byte);
+            self.set_len(len + 1);
+        }
+    }
+
+    /// Removes and returns the byte at position `idx`, moving all bytes after it to the left
+    ///
+    /// Panics if `idx` is out of bounds.
+    pub fn remove(&mut self, idx: usize) -> u8 {
+        let len = self.len();
+        assert!(
+            idx < len,
+            "removal index (is {idx}) should be < len (is {len})"
+        );
+        // SAFETY: idx < len, so both ranges are within the initialised part of the buffer
+        unsafe {
+            let p = self.as_mut_ptr().add(idx);
+            let byte = p.read();
+            ptr::copy(p.add(1), p, len - idx - 1);
+            self.set_len(len - 1);
+            byte
+        }
+    }
+
+    /// Shortens this buffer to `new_len` bytes. Does nothing if `new_len` is greater than the
+    /// current length. The storage is never changed.
+    pub fn truncate(&mut self, new_len: usize) {
+        if new_len < self.len() {
+            // SAFETY: shrinking is always valid
+            unsafe { self.set_len(new_len) }
+        }
+    }
+
+    /// Truncates this buffer to a length of 0, keeping its storage
+    pub fn clear(&mut self) {
+        self.truncate(0);
+    }
+
+    /// Resizes this buffer to `new_len` bytes, filling any new bytes with `value`
+    pub fn resize(&mut self, new_len: usize, value: u8) {
+        let len = self.len();
+        if new_len <= len {
+            self.truncate(new_len);
+            return;
+        }
+        self.inner.reserve_to_fit(new_len - len);
+        // SAFETY: we just made room for `new_len` bytes
+        unsafe {
+            self.as_mut_ptr().add(len).write_bytes(value, new_len - len);
+            self.set_len(new_len);
+        }
+    }
+
+    /// Retains only the bytes specified by the predicate. The buffer is compacted in place, so its
+    /// storage never changes.
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&u8) -> bool,
+    {
+        let len = self.len();
+        let bytes = self.as_mut_slice();
+        let mut del = 0;
+        for idx in 0..len {
+            if !f(&bytes[idx]) {
+                del += 1;
+            } else if del > 0 {
+                bytes[idx - del] = bytes[idx];
+            }
+        }
+        self.truncate(len - del);
+    }
+
+    /// Splits the buffer into two at the given index, returning everything after it. The returned
+    /// buffer is short whenever the tail fits in `N` bytes. The storage of `self` is never changed.
+    ///
+    /// Panics if `at > len`.
+    pub fn split_off(&mut self, at: usize) -> Self
+    where
+        A: Clone,
+    {
+        let len = self.len();
+        assert!(
+            at <= len,
+            "`at` split index (is {at}) should be <= len (is {len})"
+        );
+        let mut other = Self::with_capacity_in(len - at, self.allocator().clone());
+        other.extend_from_slice(&self[at..]);
+        self.truncate(at);
+        other
+    }
+
+    /// Reserves room for at least `additional` more bytes, like [`SsoStringN::reserve`]
+    pub fn reserve(&mut self, additional: usize) {
+        self.inner.reserve(additional);
+    }
+
+    pub fn reserve_exact(&mut self, additional: usize) {
+        self.inner.reserve_exact(additional);
+    }
+
+    pub fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        self.inner.try_reserve(additional)
+    }
+
+    pub fn try_reserve_exact(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        self.inner.try_reserve_exact(additional)
+    }
+
+    /// Shrinks the capacity of this buffer to at least `min_capacity` and its length, which makes
+    /// it short if that fits. Does nothing if `min_capacity` is not smaller than the current
+    /// capacity, like `Vec::shrink_to`.
+    pub fn shrink_to(&mut self, min_capacity: usize) {
+        self.inner.shrink_to(min_capacity);
+    }
+
+    pub fn shrink_to_fit(&mut self) {
+        self.shrink_to(self.len());
+    }
+
+    /// Converts this buffer into a string without copying, if it is valid utf-8
+    pub fn into_string(self) -> Result<SsoStringN<N, A>, IntoStringError<N, A>> {
+        match std::str::from_utf8(self.as_slice()) {
+            Ok(_) => Ok(self.inner),
+            Err(error) => Err(IntoStringError { bytes: self, error }),
+        }
+    }
+
+    /// Converts this buffer into a string without copying or checking that it is valid utf-8
+    ///
+    /// # Safety
+    /// - the bytes must be valid utf-8
+    pub unsafe fn into_string_unchecked(self) -> SsoStringN<N, A> {
+        self.inner
+    }
+}
+
+impl<const N: usize, A: Allocator> SsoStringN<N, A> {
+    /// Converts this string into a byte buffer without copying
+    pub fn into_bytes(self) -> SsoBytesN<N, A> {
+        SsoBytesN::from(self)
+    }
+
+    /// Returns the contents of this string as a [`SsoBytesN`], which can be changed like a
+    /// `Vec<u8>`. This is where `std::string::String::as_mut_vec` lives on this string.
+    ///
+    /// # Safety
+    /// - the bytes must be valid utf-8 again before the borrow ends
+    pub unsafe fn as_mut_vec(&mut self) -> &mut SsoBytesN<N, A> {
+        // SAFETY: `SsoBytesN` is a transparent wrapper around `SsoStringN`, and the caller makes
+        // sure that the bytes are valid utf-8 by the time this string is used again
+        unsafe { &mut *(self as *mut Self).cast::<SsoBytesN<N, A>>() }
+    }
+}
+
+/// The error returned by [`SsoBytesN::into_string`], which gives the bytes back
+pub struct IntoStringError<const N: usize, A: Allocator = Global> {
+    bytes: SsoBytesN<N, A>,
+    error: Utf8Error,
+}
+
+impl<const N: usize, A: Allocator> IntoStringError<N, A> {
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub fn into_bytes(self) -> SsoBytesN<N, A> {
+        self.bytes
+    }
+
+    pub fn utf8_error(&self) -> Utf8Error {
+        self.error
+    }
+}
+
+impl<const N: usize, A: Allocator> fmt::Debug for IntoStringError<N, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IntoStringError")
+            .field("bytes", &self.bytes)
+            .field("error", &self.error)
+            .finish()
+    }
+}
+
+impl<const N: usize, A: Allocator> fmt::Display for IntoStringError<N, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.error, f)
+    }
+}
+
+impl<const N: usize, A: Allocator> Error for IntoStringError<N, A> {}
+
+impl<const N: usize, A: Allocator> From<SsoStringN<N, A>> for SsoBytesN<N, A> {
+    fn from(value: SsoStringN<N, A>) -> Self {
+        Self { inner: value }
+    }
+}
+
+impl<'a, const N: usize> From<&'a [u8]> for SsoBytesN<N> {
+    fn from(value: &'a [u8]) -> Self {
+        let mut bytes = Self::with_capacity(value.len());
+        bytes.extend_from_slice(value);
+        bytes
+    }
+}
+
+impl<'a, const N: usize, const M: usize> From<&'a [u8; M]> for SsoBytesN<N> {
+    fn from(value: &'a [u8; M]) -> Self {
+        Self::from(&value[..])
+    }
+}
+
+impl<'a, const N: usize> From<&'a str> for SsoBytesN<N> {
+    fn from(value: &'a str) -> Self {
+        Self::from(value.as_bytes())
+    }
+}
+
+// `Vec<u8>` always allocates with `Global`, so we can only adopt its buffer into a buffer that
+// uses `Global` as well
+impl<const N: usize> From<Vec<u8>> for SsoBytesN<N> {
+    /// A vector that does not fit inline hands its buffer over without copying, unless its
+    /// capacity is larger than [`LongString::MAX_CAPACITY`]
+    fn from(value: Vec<u8>) -> Self {
+        if value.len() <= N || value.capacity() > <LongString>::MAX_CAPACITY {
+            return Self::from(&value[..]);
+        }
+        let mut value = ManuallyDrop::new(value);
+        // SAFETY:
+        // - `value.len()` is greater than 0, so the buffer is neither dangling nor null
+        // - the capacity is at most `LongString::MAX_CAPACITY`
+        // - RawBuf uses `Global` internally, so handing over the buffer of a `Vec` is just fine
+        // - the bytes do not have to be utf-8 (field contract)
+        let long = unsafe {
+            let ptr = NonNull::new_unchecked(value.as_mut_ptr());
+            LongString::from_raw_parts(ptr, value.len(), value.capacity())
+        };
+        Self {
+            inner: SsoStringN::from_long(long),
+        }
+    }
+}
+
+impl<const N: usize> From<SsoBytesN<N>> for Vec<u8> {
+    /// A long buffer hands its buffer over without copying, a short or static one is copied into
+    /// a new buffer that is exactly as big as it is
+    fn from(value: SsoBytesN<N>) -> Self {
+        // the buffer is owned by the new vector, and `Global` needs no dropping
+        let value = ManuallyDrop::new(value);
+        match value.inner.tagged() {
+            TaggedSsoString64::Short(short) => short.as_bytes().to_vec(),
+            TaggedSsoString64::Static(s) => s.as_bytes().to_vec(),
+            // SAFETY:
+            // - the buffer was allocated by `Global` as a slice of `u8`, like a `Vec<u8>`
+            // - `long.capacity()` is the exact size of the buffer, and `long.len()` is at most that
+            // - `value` is never dropped, so the vector is the only owner of the buffer
+            TaggedSsoString64::Long(long) => unsafe {
+                Vec::from_raw_parts(long.buf().as_ptr(), long.len(), long.capacity())
+            },
+        }
+    }
+}
+
+impl<const N: usize, A: Allocator> Deref for SsoBytesN<N, A> {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        self.as_slice()
+    }
+}
+
+impl<const N: usize, A: Allocator> DerefMut for SsoBytesN<N, A> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.as_mut_slice()
+    }
+}
+
+impl<const N: usize, A: Allocator> AsRef<[u8]> for SsoBytesN<N, A> {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+impl<const N: usize, A: Allocator> AsMut<[u8]> for SsoBytesN<N, A> {
+    fn as_mut(&mut self) -> &mut [u8] {
+        self
+    }
+}
+
+impl<const N: usize, A: Allocator> Borrow<[u8]> for SsoBytesN<N, A> {
+    fn borrow(&self) -> &[u8] {
+        self
+    }
+}
+
+impl<const N: usize, A: Allocator> BorrowMut<[u8]> for SsoBytesN<N, A> {
+    fn borrow_mut(&mut self) -> &mut [u8] {
+        self
+    }
+}
+
+impl<const N: usize, A: Allocator + Clone> Clone for SsoBytesN<N, A> {
+    /// The clone is only as big as it has to be, so a long buffer that fits in `N` bytes becomes
+    /// short
+    fn clone(&self) -> Self {
+        let mut other = Self::with_capacity_in(self.len(), self.allocator().clone());
+        other.extend_from_slice(self);
+        other
+    }
+
+    /// Reuses the buffer of `self` if it is long enough, the allocator of `self` is kept
+    fn clone_from(&mut self, source: &Self) {
+        self.clear();
+        self.extend_from_slice(source);
+    }
+}
+
+impl<const N: usize, A: Allocator + Default> Default for SsoBytesN<N, A> {
+    fn default() -> Self {
+        Self::new_in(A::default())
+    }
+}
+
+impl<const N: usize, A: Allocator> fmt::Debug for SsoBytesN<N, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_slice(), f)
+    }
+}
+
+impl<const N: usize, const M: usize, A: Allocator, B: Allocator> PartialEq<SsoBytesN<M, B>>
+    for SsoBytesN<N, A>
+{
+    fn eq(&self, other: &SsoBytesN<M, B>) -> bool {
+        self.as_slice() == other.as_slice()
+    }
+}
+
+impl<const N: usize, A: Allocator> Eq for SsoBytesN<N, A> {}
+
+impl<const N: usize, const M: usize, A: Allocator, B: Allocator> PartialOrd<SsoBytesN<M, B>>
+    for SsoBytesN<N, A>
+{
+    fn partial_cmp(&self, other: &SsoBytesN<M, B>) -> Option<cmp::Ordering> {
+        self.as_slice().partial_cmp(other.as_slice())
+    }
+}
+
+impl<const N: usize, A: Allocator> Ord for SsoBytesN<N, A> {
+    fn cmp(&self, other: &Self) -> cmp::Ordering {
+        self.as_slice().cmp(other.as_slice())
+    }
+}
+
+macro_rules! impl_eq_bytes {
+    ([$($generics:tt)*] $Other:ty) => {
+        impl<$($generics)* const N: usize, A: Allocator> PartialEq<$Other> for SsoBytesN<N, A> {
+            fn eq(&self, other: &$Other) -> bool {
+                self.as_slice() == &other[..]
+            }
+        }
+
+        impl<$($generics)* const N: usize, A: Allocator> PartialEq<SsoBytesN<N, A>> for $Other {
+            fn eq(&self, other: &SsoBytesN<N, A>) -> bool {
+                &self[..] == other.as_slice()
+            }
+        }
+    };
+}
+
+impl_eq_bytes!([][u8]);
+impl_eq_bytes!(['a,] &'a [u8]);
+impl_eq_bytes!([] Vec<u8>);
+impl_eq_bytes!([const M: usize,] [u8; M]);
+impl_eq_bytes!(['a, const M: usize,] &'a [u8; M]);
+
+impl<const N: usize, A: Allocator> Hash for SsoBytesN<N, A> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // exactly the same as `[u8]`, so that lookups through `Borrow<[u8]>` work
+        self.as_slice().hash(state)
+    }
+}
+
+impl<const N: usize, A: Allocator> Extend<u8> for SsoBytesN<N, A> {
+    fn extend<I: IntoIterator<Item = u8>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        self.inner.reserve_to_fit(iter.size_hint().0);
+        iter.for_each(|byte| self.push(byte));
+    }
+}
+
+impl<'a, const N: usize, A: Allocator> Extend<&'a u8> for SsoBytesN<N, A> {
+    fn extend<I: IntoIterator<Item = &'a u8>>(&mut self, iter: I) {
+        self.extend(iter.into_iter().copied())
+    }
+}
+
+impl<const N: usize> FromIterator<u8> for SsoBytesN<N> {
+    fn from_iter<I: IntoIterator<Item = u8>>(iter: I) -> Self {
+        let mut bytes = Self::new();
+        bytes.extend(iter);
+        bytes
+    }
+}
+
+impl<const N: usize, A: Allocator> io::Write for SsoBytesN<N, A> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.extend_from_slice(buf);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
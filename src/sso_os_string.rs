@@ -0,0 +1,570 @@
+//! Small string optimised [`OsString`] and [`PathBuf`] for Unix, where both are just bytes. They
+//! are a thin layer over [`SsoBytes`], so short names are stored inline like a short
+//! [`String`](crate::String), and converting from or to the std types hands long buffers over
+//! without copying.
+
+use std::{
+    borrow::Borrow,
+    cmp,
+    collections::TryReserveError,
+    ffi::{OsStr, OsString},
+    fmt,
+    hash::{Hash, Hasher},
+    ops::Deref,
+    os::unix::ffi::{OsStrExt, OsStringExt},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    sso_bytes::SsoBytes,
+    sso_string::SsoString,
+    unified_alloc::{Allocator, Global},
+};
+
+/// An [`OsString`] that stores up to 23 bytes (or 11 on 32-bit targets) inline. Like on any Unix,
+/// the bytes may be anything.
+pub struct SsoOsString<A: Allocator = Global> {
+    inner: SsoBytes<A>,
+}
+
+impl SsoOsString {
+    /// Constructs an empty (short) string, this can be used to initialise a `static`
+    pub const fn new() -> Self {
+        Self {
+            inner: SsoBytes::new(),
+        }
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::with_capacity_in(capacity, Global)
+    }
+}
+
+impl<A: Allocator> SsoOsString<A> {
+    /// Constructs an empty (short) string that will use `alloc` if it ever has to allocate
+    pub fn new_in(alloc: A) -> Self {
+        Self {
+            inner: SsoBytes::new_in(alloc),
+        }
+    }
+
+    /// Constructs a string with at least `capacity` bytes of capacity, allocated with `alloc`.
+    /// Capacities that fit inline do not allocate.
+    pub fn with_capacity_in(capacity: usize, alloc: A) -> Self {
+        Self {
+            inner: SsoBytes::with_capacity_in(capacity, alloc),
+        }
+    }
+
+    /// Returns a reference to the allocator that backs this string
+    pub fn allocator(&self) -> &A {
+        self.inner.allocator()
+    }
+
+    /// Returns `true` if the string is stored inline (no heap allocations), and `false` otherwise
+    pub fn is_short(&self) -> bool {
+        self.inner.is_short()
+    }
+
+    /// Returns `true` if this string owns a heap buffer. Every string is exactly one of short,
+    /// long or static.
+    pub fn is_long(&self) -> bool {
+        self.inner.is_long()
+    }
+
+    pub fn as_os_str(&self) -> &OsStr {
+        OsStr::from_bytes(&self.inner)
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.inner.capacity()
+    }
+
+    /// Appends `s` to the end of this string
+    pub fn push<S: AsRef<OsStr>>(&mut self, s: S) {
+        self.inner.extend_from_slice(s.as_ref().as_bytes())
+    }
+
+    /// Shortens this string to `new_len` bytes. Does nothing if `new_len` is greater than the
+    /// current length.
+    pub fn truncate(&mut self, new_len: usize) {
+        self.inner.truncate(new_len)
+    }
+
+    pub fn clear(&mut self) {
+        self.inner.clear()
+    }
+
+    pub fn reserve(&mut self, additional: usize) {
+        self.inner.reserve(additional)
+    }
+
+    pub fn reserve_exact(&mut self, additional: usize) {
+        self.inner.reserve_exact(additional)
+    }
+
+    pub fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        self.inner.try_reserve(additional)
+    }
+
+    pub fn try_reserve_exact(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        self.inner.try_reserve_exact(additional)
+    }
+
+    /// Shrinks the capacity of this string as much as possible, which moves it inline if it fits
+    pub fn shrink_to_fit(&mut self) {
+        self.inner.shrink_to_fit()
+    }
+
+    /// Converts this string into a [`SsoString`] if it is valid utf-8, or gives it back otherwise.
+    /// Never copies.
+    pub fn into_string(self) -> Result<SsoString<A>, Self> {
+        self.inner
+            .into_string()
+            .map_err(|err| Self::from(err.into_bytes()))
+    }
+
+    /// Converts this string into its bytes without copying
+    pub fn into_bytes(self) -> SsoBytes<A> {
+        self.inner
+    }
+}
+
+impl<A: Allocator> From<SsoBytes<A>> for SsoOsString<A> {
+    fn from(value: SsoBytes<A>) -> Self {
+        Self { inner: value }
+    }
+}
+
+impl<A: Allocator> From<SsoString<A>> for SsoOsString<A> {
+    fn from(value: SsoString<A>) -> Self {
+        Self::from(value.into_bytes())
+    }
+}
+
+impl<'a> From<&'a OsStr> for SsoOsString {
+    fn from(value: &'a OsStr) -> Self {
+        Self::from(SsoBytes::from(value.as_bytes()))
+    }
+}
+
+impl<'a> From<&'a str> for SsoOsString {
+    fn from(value: &'a str) -> Self {
+        Self::from(OsStr::new(value))
+    }
+}
+
+impl From<OsString> for SsoOsString {
+    /// A string that does not fit inline hands its buffer over without copying
+    fn from(value: OsString) -> Self {
+        Self::from(SsoBytes::from(value.into_vec()))
+    }
+}
+
+impl From<SsoOsString> for OsString {
+    /// A long string hands its buffer over without copying, a short one is copied into a new
+    /// buffer that is exactly as big as it is
+    fn from(value: SsoOsString) -> Self {
+        OsString::from_vec(Vec::from(value.inner))
+    }
+}
+
+impl<A: Allocator> Deref for SsoOsString<A> {
+    type Target = OsStr;
+
+    fn deref(&self) -> &Self::Target {
+        self.as_os_str()
+    }
+}
+
+impl<A: Allocator> AsRef<OsStr> for SsoOsString<A> {
+    fn as_ref(&self) -> &OsStr {
+        self
+    }
+}
+
+impl<A: Allocator> AsRef<Path> for SsoOsString<A> {
+    fn as_ref(&self) -> &Path {
+        Path::new(self.as_os_str())
+    }
+}
+
+impl<A: Allocator> Borrow<OsStr> for SsoOsString<A> {
+    fn borrow(&self) -> &OsStr {
+        self
+    }
+}
+
+impl<A: Allocator + Clone> Clone for SsoOsString<A> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        self.inner.clone_from(&source.inner)
+    }
+}
+
+impl<A: Allocator + Default> Default for SsoOsString<A> {
+    fn default() -> Self {
+        Self::new_in(A::default())
+    }
+}
+
+impl<A: Allocator> fmt::Debug for SsoOsString<A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_os_str(), f)
+    }
+}
+
+impl<A: Allocator, B: Allocator> PartialEq<SsoOsString<B>> for SsoOsString<A> {
+    fn eq(&self, other: &SsoOsString<B>) -> bool {
+        self.as_os_str() == other.as_os_str()
+    }
+}
+
+impl<A: Allocator> Eq for SsoOsString<A> {}
+
+impl<A: Allocator, B: Allocator> PartialOrd<SsoOsString<B>> for SsoOsString<A> {
+    fn partial_cmp(&self, other: &SsoOsString<B>) -> Option<cmp::Ordering> {
+        self.as_os_str().partial_cmp(other.as_os_str())
+    }
+}
+
+impl<A: Allocator> Ord for SsoOsString<A> {
+    fn cmp(&self, other: &Self) -> cmp::Ordering {
+        self.as_os_str().cmp(other.as_os_str())
+    }
+}
+
+macro_rules! impl_eq_os_str {
+    ($Outer:ident, [$($generics:tt)*] $Other:ty, $as_ref:ident) => {
+        impl<$($generics)* A: Allocator> PartialEq<$Other> for $Outer<A> {
+            fn eq(&self, other: &$Other) -> bool {
+                self.$as_ref() == AsRef::<<Self as Deref>::Target>::as_ref(other)
+            }
+        }
+
+        impl<$($generics)* A: Allocator> PartialEq<$Outer<A>> for $Other {
+            fn eq(&self, other: &$Outer<A>) -> bool {
+                other == self
+            }
+        }
+    };
+}
+
+impl_eq_os_str!(SsoOsString, [] OsStr, as_os_str);
+impl_eq_os_str!(SsoOsString, ['a,] &'a OsStr, as_os_str);
+impl_eq_os_str!(SsoOsString, [] OsString, as_os_str);
+impl_eq_os_str!(SsoOsString, [] str, as_os_str);
+impl_eq_os_str!(SsoOsString, ['a,] &'a str, as_os_str);
+
+impl<A: Allocator> Hash for SsoOsString<A> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // exactly the same as `OsStr`, so that lookups through `Borrow<OsStr>` work
+        self.as_os_str().hash(state)
+    }
+}
+
+/// A [`PathBuf`] that stores up to 23 bytes (or 11 on 32-bit targets) inline
+pub struct SsoPathBuf<A: Allocator = Global> {
+    inner: SsoOsString<A>,
+}
+
+impl SsoPathBuf {
+    /// Constructs an empty (short) path, this can be used to initialise a `static`
+    pub const fn new() -> Self {
+        Self {
+            inner: SsoOsString::new(),
+        }
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::with_capacity_in(capacity, Global)
+    }
+}
+
+impl<A: Allocator> SsoPathBuf<A> {
+    /// Constructs an empty (short) path that will use `alloc` if it ever has to allocate
+    pub fn new_in(alloc: A) -> Self {
+        Self {
+            inner: SsoOsString::new_in(alloc),
+        }
+    }
+
+    /// Constructs a path with at least `capacity` bytes of capacity, allocated with `alloc`.
+    /// Capacities that fit inline do not allocate.
+    pub fn with_capacity_in(capacity: usize, alloc: A) -> Self {
+        Self {
+            inner: SsoOsString::with_capacity_in(capacity, alloc),
+        }
+    }
+
+    /// Returns a reference to the allocator that backs this path
+    pub fn allocator(&self) -> &A {
+        self.inner.allocator()
+    }
+
+    /// Returns `true` if the path is stored inline (no heap allocations), and `false` otherwise
+    pub fn is_short(&self) -> bool {
+        self.inner.is_short()
+    }
+
+    /// Returns `true` if this path owns a heap buffer. Every path is exactly one of short,
+    /// long or static.
+    pub fn is_long(&self) -> bool {
+        self.inner.is_long()
+    }
+
+    pub fn as_path(&self) -> &Path {
+        self.inner.as_ref()
+    }
+
+    pub fn as_mut_os_string(&mut self) -> &mut SsoOsString<A> {
+        &mut self.inner
+    }
+
+    pub fn into_os_string(self) -> SsoOsString<A> {
+        self.inner
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.inner.capacity()
+    }
+
+    /// Extends this path with `path`, exactly like [`PathBuf::push`]: an absolute `path` replaces
+    /// this one, and a relative one is joined with a `/` if needed
+    pub fn push<P: AsRef<Path>>(&mut self, path: P) {
+        let path = path.as_ref();
+        let need_sep = self.inner.as_bytes().last().is_some_and(|&b| b != b'/');
+        if path.is_absolute() {
+            self.inner.clear();
+        } else if need_sep {
+            self.inner.push("/");
+        }
+        self.inner.push(path);
+    }
+
+    /// Truncates this path to its parent, exactly like [`PathBuf::pop`]. Returns `false` and does
+    /// nothing if there is no parent.
+    pub fn pop(&mut self) -> bool {
+        match self.parent().map(|parent| parent.as_os_str().len()) {
+            Some(len) => {
+                self.inner.truncate(len);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Replaces the last component of this path with `file_name`, exactly like
+    /// [`PathBuf::set_file_name`]
+    pub fn set_file_name<S: AsRef<OsStr>>(&mut self, file_name: S) {
+        if self.file_name().is_some() {
+            let popped = self.pop();
+            debug_assert!(popped);
+        }
+        self.push(file_name.as_ref());
+    }
+
+    /// Replaces the extension of this path with `extension`, or removes it if `extension` is
+    /// empty, exactly like [`PathBuf::set_extension`]. Returns `false` and does nothing if there
+    /// is no file name.
+    ///
+    /// Panics if `extension` contains a `/`.
+    pub fn set_extension<S: AsRef<OsStr>>(&mut self, extension: S) -> bool {
+        let extension = extension.as_ref().as_bytes();
+        assert!(
+            !extension.contains(&b'/'),
+            "extension cannot contain path separators: {:?}",
+            OsStr::from_bytes(extension)
+        );
+        let Some(file_stem) = self.file_stem() else {
+            return false;
+        };
+        // the stem borrows from this path, so its end is an offset into it
+        let end = file_stem.as_bytes().as_ptr_range().end as usize
+            - self.inner.as_bytes().as_ptr() as usize;
+        self.inner.truncate(end);
+        if !extension.is_empty() {
+            self.inner.push(".");
+            self.inner.push(OsStr::from_bytes(extension));
+        }
+        true
+    }
+
+    pub fn clear(&mut self) {
+        self.inner.clear()
+    }
+
+    pub fn reserve(&mut self, additional: usize) {
+        self.inner.reserve(additional)
+    }
+
+    pub fn reserve_exact(&mut self, additional: usize) {
+        self.inner.reserve_exact(additional)
+    }
+
+    pub fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        self.inner.try_reserve(additional)
+    }
+
+    pub fn try_reserve_exact(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        self.inner.try_reserve_exact(additional)
+    }
+
+    /// Shrinks the capacity of this path as much as possible, which moves it inline if it fits
+    pub fn shrink_to_fit(&mut self) {
+        self.inner.shrink_to_fit()
+    }
+}
+
+impl<A: Allocator> From<SsoOsString<A>> for SsoPathBuf<A> {
+    fn from(value: SsoOsString<A>) -> Self {
+        Self { inner: value }
+    }
+}
+
+impl<A: Allocator> From<SsoString<A>> for SsoPathBuf<A> {
+    fn from(value: SsoString<A>) -> Self {
+        Self::from(SsoOsString::from(value))
+    }
+}
+
+impl<'a> From<&'a Path> for SsoPathBuf {
+    fn from(value: &'a Path) -> Self {
+        Self::from(SsoOsString::from(value.as_os_str()))
+    }
+}
+
+impl<'a> From<&'a OsStr> for SsoPathBuf {
+    fn from(value: &'a OsStr) -> Self {
+        Self::from(SsoOsString::from(value))
+    }
+}
+
+impl<'a> From<&'a str> for SsoPathBuf {
+    fn from(value: &'a str) -> Self {
+        Self::from(SsoOsString::from(value))
+    }
+}
+
+impl From<PathBuf> for SsoPathBuf {
+    /// A path that does not fit inline hands its buffer over without copying
+    fn from(value: PathBuf) -> Self {
+        Self::from(SsoOsString::from(value.into_os_string()))
+    }
+}
+
+impl From<OsString> for SsoPathBuf {
+    /// A string that does not fit inline hands its buffer over without copying
+    fn from(value: OsString) -> Self {
+        Self::from(SsoOsString::from(value))
+    }
+}
+
+impl From<SsoPathBuf> for PathBuf {
+    /// A long path hands its buffer over without copying, a short one is copied into a new buffer
+    /// that is exactly as big as it is
+    fn from(value: SsoPathBuf) -> Self {
+        PathBuf::from(OsString::from(value.inner))
+    }
+}
+
+impl<A: Allocator> Deref for SsoPathBuf<A> {
+    type Target = Path;
+
+    fn deref(&self) -> &Self::Target {
+        self.as_path()
+    }
+}
+
+impl<A: Allocator> AsRef<Path> for SsoPathBuf<A> {
+    fn as_ref(&self) -> &Path {
+        self
+    }
+}
+
+impl<A: Allocator> AsRef<OsStr> for SsoPathBuf<A> {
+    fn as_ref(&self) -> &OsStr {
+        self.as_os_str()
+    }
+}
+
+impl<A: Allocator> Borrow<Path> for SsoPathBuf<A> {
+    fn borrow(&self) -> &Path {
+        self
+    }
+}
+
+impl<A: Allocator + Clone> Clone for SsoPathBuf<A> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        self.inner.clone_from(&source.inner)
+    }
+}
+
+impl<A: Allocator + Default> Default for SsoPathBuf<A> {
+    fn default() -> Self {
+        Self::new_in(A::default())
+    }
+}
+
+impl<A: Allocator> fmt::Debug for SsoPathBuf<A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_path(), f)
+    }
+}
+
+impl<P: AsRef<Path>, A: Allocator> Extend<P> for SsoPathBuf<A> {
+    fn extend<I: IntoIterator<Item = P>>(&mut self, iter: I) {
+        iter.into_iter().for_each(|path| self.push(path))
+    }
+}
+
+impl<P: AsRef<Path>> FromIterator<P> for SsoPathBuf {
+    fn from_iter<I: IntoIterator<Item = P>>(iter: I) -> Self {
+        let mut path = Self::new();
+        path.extend(iter);
+        path
+    }
+}
+
+// paths compare by their components, like `Path`, so `a//b` and `a/b/` are equal
+impl<A: Allocator, B: Allocator> PartialEq<SsoPathBuf<B>> for SsoPathBuf<A> {
+    fn eq(&self, other: &SsoPathBuf<B>) -> bool {
+        self.as_path() == other.as_path()
+    }
+}
+
+impl<A: Allocator> Eq for SsoPathBuf<A> {}
+
+impl<A: Allocator, B: Allocator> PartialOrd<SsoPathBuf<B>> for SsoPathBuf<A> {
+    fn partial_cmp(&self, other: &SsoPathBuf<B>) -> Option<cmp::Ordering> {
+        self.as_path().partial_cmp(other.as_path())
+    }
+}
+
+impl<A: Allocator> Ord for SsoPathBuf<A> {
+    fn cmp(&self, other: &Self) -> cmp::Ordering {
+        self.as_path().cmp(other.as_path())
+    }
+}
+
+impl_eq_os_str!(SsoPathBuf, [] Path, as_path);
+impl_eq_os_str!(SsoPathBuf, ['a,] &'a Path, as_path);
+impl_eq_os_str!(SsoPathBuf, [] PathBuf, as_path);
+
+impl<A: Allocator> Hash for SsoPathBuf<A> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // exactly the same as `Path`, so that lookups through `Borrow<Path>` work
+        self.as_path().hash(state)
+    }
+}
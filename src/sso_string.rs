@@ -1,65 +1,202 @@
 use std::{
-    borrow::{Borrow, Cow},
+    borrow::{Borrow, BorrowMut, Cow},
     cmp,
     collections::TryReserveError,
+    convert::Infallible,
+    ffi::OsStr,
     fmt,
-    mem::{self, ManuallyDrop},
-    ops::RangeBounds,
-    ops::{self, Deref},
+    hash::{Hash, Hasher},
+    iter::FusedIterator,
+    marker::PhantomData,
+    mem::{self, ManuallyDrop, MaybeUninit},
+    ops::{self, Bound, Deref, DerefMut, RangeBounds},
+    path::Path,
     ptr::{self, NonNull},
-    slice,
-    string::{Drain, FromUtf16Error, FromUtf8Error},
+    slice::{self, SliceIndex},
+    str::{Chars, FromStr},
+    string::{FromUtf16Error, FromUtf8Error},
 };
 
 use crate::{
-    duck_impl, never_impl, todo_impl, unified_alloc,
-    unsafe_field::{UnsafeAssign, UnsafeField},
+    duck_impl,
+    unified_alloc::{self, Allocator, Global},
+    unsafe_field::{
+        SimultaneousUnsafeAssign, SimultaneousUnsafeAssignment, UnsafeAssign, UnsafeField,
+    },
 };
 
-#[derive(Clone, Copy)]
+/// Checks the invariants of `$string` that can be checked in O(1) after it has been changed, in
+/// debug builds or with the `strict-invariants` feature.
+///
+/// This deliberately runs [`SsoStringN::check_layout`] and not [`SsoStringN::check_invariants`],
+/// even with `strict-invariants`: the bytes are never checked for utf-8 here. That would make every
+/// change O(n), and [`SsoBytesN`](crate::sso_bytes::SsoBytesN) changes its bytes through these same
+/// functions while they are not utf-8 at all, so the check would reject valid buffers.
+macro_rules! debug_check_layout {
+    ($string:expr) => {
+        if cfg!(any(debug_assertions, feature = "strict-invariants")) {
+            $string.check_layout();
+        }
+    };
+}
+
+/// A pointer to a buffer of `T`s that was allocated by an allocator of type `A`. The buffer does
+/// not own the allocator, so every method that (de)allocates has to be given one.
 #[repr(C)]
-pub struct RawBuf<T> {
+pub struct RawBuf<T, A = Global> {
     pub(crate) data: NonNull<T>,
+    _alloc: PhantomData<A>,
+}
+
+impl<T, A> Clone for RawBuf<T, A> {
+    fn clone(&self) -> Self {
+        *self
+    }
 }
 
+impl<T, A> Copy for RawBuf<T, A> {}
+
 #[derive(Debug)]
 pub struct InvalidArgumentError;
 
+/// Converts any `RangeBounds<usize>` into a `Range<usize>` that is guaranteed to satisfy
+/// `start <= end <= len`. Panics with the same messages as slice indexing otherwise.
+fn normalize_range<R: RangeBounds<usize>>(range: R, len: usize) -> ops::Range<usize> {
+    let start = match range.start_bound() {
+        Bound::Included(&start) => start,
+        Bound::Excluded(&start) => start
+            .checked_add(1)
+            .unwrap_or_else(|| panic!("attempted to index slice from after maximum usize")),
+        Bound::Unbounded => 0,
+    };
+    let end = match range.end_bound() {
+        Bound::Included(&end) => end
+            .checked_add(1)
+            .unwrap_or_else(|| panic!("attempted to index slice up to maximum usize")),
+        Bound::Excluded(&end) => end,
+        Bound::Unbounded => len,
+    };
+    if start > end {
+        panic!("slice index starts at {start} but ends at {end}");
+    }
+    if end > len {
+        panic!("range end index {end} out of range for slice of length {len}");
+    }
+    start..end
+}
+
+/// Replaces `buf[range]` with `s`, moving `buf[range.end..len]` so that it directly follows `s`.
+/// Returns the new length of the initialised region.
+///
+/// # Safety
+/// - `range.start <= range.end <= len`
+/// - `buf[0..len]` must be valid for reads and writes
+/// - `buf[0..len - range.len() + s.len()]` must be valid for writes
+/// - `s` must not overlap with `buf[0..len - range.len() + s.len()]`
+unsafe fn replace_range_raw(buf: *mut u8, len: usize, range: ops::Range<usize>, s: &str) -> usize {
+    let tail_len = len - range.end;
+    // SAFETY:
+    // - both regions are within buf[0..new_len] or buf[0..len] (caller contract)
+    // - `ptr::copy` allows the regions to overlap
+    ptr::copy(buf.add(range.end), buf.add(range.start + s.len()), tail_len);
+    // SAFETY:
+    // - src is valid for reads of s.len() by slice definition
+    // - dst is within buf[0..new_len] (caller contract)
+    // - the regions do not overlap (caller contract)
+    ptr::copy_nonoverlapping(s.as_ptr(), buf.add(range.start), s.len());
+    range.start + s.len() + tail_len
+}
+
 impl<T> RawBuf<T> {
+    pub fn new(capacity: usize) -> (Self, usize) {
+        Self::new_in(capacity, &Global)
+    }
+
+    /// The same as [`RawBuf::new`], but returns an error instead of panicking if `capacity` is
+    /// impossibly big or the allocator fails.
+    pub fn try_new(capacity: usize) -> Result<(Self, usize), TryReserveError> {
+        Self::try_new_in(capacity, &Global)
+    }
+
+    /// Deallocates the buffer. Returns [`InvalidArgumentError`] if `len` is impossibly big.
+    ///
+    /// # Safety
+    /// - `len` must be the exact length of the allocated object, using the value returned
+    ///   with `RawBuf::new() -> (_, len)` will guarantee safety.
+    /// - this must be the first time that you call this function (aka self.data cannot be dangling)
+    pub unsafe fn dealloc(self, len: usize) -> Result<Self, InvalidArgumentError> {
+        // SAFETY: the buffer was allocated with `Global`, caller contract is passed on
+        unsafe { self.dealloc_in(len, &Global) }
+    }
+}
+
+impl<T, A> RawBuf<T, A> {
     pub const fn dangling() -> Self {
+        Self::from_non_null(NonNull::dangling())
+    }
+
+    /// Wraps a pointer to a buffer that was allocated by `A`. A `RawBuf` never frees itself, and
+    /// every method that uses the buffer is unsafe, so this is safe.
+    pub const fn from_non_null(data: NonNull<T>) -> Self {
         Self {
-            data: NonNull::dangling(),
+            data,
+            _alloc: PhantomData,
         }
     }
 
-    pub fn new(capacity: usize) -> (Self, usize) {
+    /// Allocates a buffer for at least `capacity` elements with `alloc`, returning the buffer
+    /// and its exact capacity. Panics if the allocation is impossible or fails.
+    pub fn new_in(capacity: usize, alloc: &A) -> (Self, usize)
+    where
+        A: Allocator,
+    {
+        Self::try_new_in(capacity, alloc).unwrap_or_else(|err| panic!("allocation error: {err}"))
+    }
+
+    /// The same as [`RawBuf::new_in`], but returns an error instead of panicking if `capacity` is
+    /// impossibly big or the allocator fails.
+    pub fn try_new_in(capacity: usize, alloc: &A) -> Result<(Self, usize), TryReserveError>
+    where
+        A: Allocator,
+    {
         if capacity == 0 {
-            return (Self::dangling(), 0);
+            return Ok((Self::dangling(), 0));
         }
 
-        let data = unified_alloc::alloc_slice::<T>(capacity);
+        let data = unified_alloc::try_alloc_slice_in::<T, A>(capacity, alloc)?;
 
-        (Self { data: data.cast() }, data.len())
+        Ok((
+            Self {
+                data: data.cast(),
+                _alloc: PhantomData,
+            },
+            data.len(),
+        ))
     }
 
-    /// Deallocates the buffer. Returns [`InvalidArgumentError`] if `len` is impossibly big.
+    /// Deallocates the buffer with `alloc`. Returns [`InvalidArgumentError`] if `len` is
+    /// impossibly big.
     ///
     /// # Safety
     /// - `len` must be the exact length of the allocated object, using the value returned
-    ///   with `RawBuf::new() -> (_, len)` will guarantee safety.
+    ///   with `RawBuf::new_in() -> (_, len)` will guarantee safety.
+    /// - the buffer must have been allocated by `alloc` (or a copy of it)
     /// - this must be the first time that you call this function (aka self.data cannot be dangling)
-    pub unsafe fn dealloc(mut self, len: usize) -> Result<Self, InvalidArgumentError> {
+    pub unsafe fn dealloc_in(mut self, len: usize, alloc: &A) -> Result<Self, InvalidArgumentError>
+    where
+        A: Allocator,
+    {
         // SAFETY: cast temporarily for method, pointer is non-null still
         let nonnull_slice = unsafe {
             NonNull::new_unchecked(ptr::slice_from_raw_parts_mut(self.data.as_ptr(), len))
         };
         // SAFETY:
-        // - nonnull_slice points to an allocation created by RawBuf<T>
+        // - nonnull_slice points to an allocation created by RawBuf<T> with `alloc`
         // - the allocation should not be deallocated (caller contract)
         // - the len of the allocation should be the same as what was returned by new (caller
         //   contract)
         unsafe {
-            unified_alloc::dealloc_slice(nonnull_slice);
+            unified_alloc::dealloc_slice_in(nonnull_slice, alloc);
         }
         // we need to self.data explicitly dangle, so that general slice functions are
         // perceived as safe by Miri. If we allocate, and deallocate, Miri has a tag for the
@@ -78,50 +215,138 @@ impl<T> RawBuf<T> {
     }
 }
 
+/// An inline string of at most `N` bytes. The length is stored next to a tag bit in a single
+/// byte, so `N` can be at most 126. The size is always rounded up to a multiple of the pointer
+/// size, so the best values for `N` are one less than a multiple of it.
 #[derive(Clone, Copy)]
 #[repr(C)]
-#[repr(align(8))]
-pub struct ShortString64 {
+#[cfg_attr(target_pointer_width = "64", repr(align(8)))]
+#[cfg_attr(target_pointer_width = "32", repr(align(4)))]
+pub struct ShortStringN<const N: usize> {
     /// # Safety
-    /// - `1` is always a valid value
-    /// - the last bit must always be `1`
-    /// when shifted by >> 1:
-    /// - `len` must be less than or equal to `ShortString64::MAX_CAPACITY`
+    /// - `Self::FLAG` is always a valid value
+    /// - the `Self::FLAG` bit must always be set
+    ///
+    /// when masked with `!Self::FLAG` and shifted by `>> Self::LEN_SHIFT`:
+    /// - `len` must be less than or equal to `ShortStringN::MAX_CAPACITY`
     len_and_flag: UnsafeField<u8, 0>,
     /// # Safety
     /// - Must always be valid utf8
-    buf: UnsafeField<[u8; Self::MAX_CAPACITY], 1>,
+    buf: UnsafeField<[u8; N], 1>,
 }
 
-impl ShortString64 {
-    pub const MAX_CAPACITY: usize = 23;
+/// The short string that fits in the same 24 bytes as a [`LongString`] on 64-bit targets
+#[cfg(target_pointer_width = "64")]
+pub type ShortString64 = ShortStringN<23>;
+
+/// The short string that fits in the same 12 bytes as a [`LongString`] on 32-bit targets
+#[cfg(target_pointer_width = "32")]
+pub type ShortString32 = ShortStringN<11>;
+
+/// The short string that is exactly as big as a [`LongString`] on the current target
+#[cfg(target_pointer_width = "64")]
+pub type ShortString = ShortString64;
+
+/// The short string that is exactly as big as a [`LongString`] on the current target
+#[cfg(target_pointer_width = "32")]
+pub type ShortString = ShortString32;
+
+const _: () = assert!(mem::size_of::<ShortString>() == mem::size_of::<LongString>());
+
+impl<const N: usize> ShortStringN<N> {
+    pub const MAX_CAPACITY: usize = N;
+
+    /// The tag bit of `len_and_flag`, which overlaps with the first byte of the length of a
+    /// [`LongString`]. That is its least significant byte on little-endian targets, and its most
+    /// significant byte on big-endian targets.
+    #[cfg(target_endian = "little")]
+    pub(crate) const FLAG: u8 = 1;
+    #[cfg(target_endian = "big")]
+    pub(crate) const FLAG: u8 = 1 << 7;
 
-    /// Constructs and empty ShortString64
-    pub fn new() -> Self {
+    /// How far the length is shifted to make room for `Self::FLAG`
+    #[cfg(target_endian = "little")]
+    const LEN_SHIFT: u32 = 1;
+    #[cfg(target_endian = "big")]
+    const LEN_SHIFT: u32 = 0;
+
+    /// the length has to fit in the 7 bits of `len_and_flag` that are not `Self::FLAG`, and
+    /// `len_and_flag` can never be `0xFF`, which is the niche of a [`SsoStringN`]
+    const VALID_CAPACITY: () = assert!(N <= 126, "the inline capacity can be at most 126 bytes");
+
+    /// Constructs and empty ShortStringN
+    pub const fn new() -> Self {
+        let () = Self::VALID_CAPACITY;
         Self {
-            // SAFETY: 1 is always a valid value
-            len_and_flag: unsafe { UnsafeField::new(1) }, // 0, 1
-            buf: unsafe { UnsafeField::new([0; 23]) },
+            // SAFETY: Self::FLAG is always a valid value
+            len_and_flag: unsafe { UnsafeField::new(Self::FLAG) }, // len 0, flag set
+            buf: unsafe { UnsafeField::new([0; N]) },
+        }
+    }
+
+    /// Copies `s` into a new short string, or returns `None` if it is longer than `N` bytes
+    pub const fn try_from_str(s: &str) -> Option<Self> {
+        let () = Self::VALID_CAPACITY;
+        if s.len() > N {
+            return None;
+        }
+        let mut buf = [0; N];
+        let bytes = s.as_bytes();
+        let mut i = 0;
+        while i < bytes.len() {
+            buf[i] = bytes[i];
+            i += 1;
+        }
+        // SAFETY:
+        // - s.len() <= N <= 126, so shifting it leaves Self::FLAG alone, and it is a valid length
+        // - buf[0..len] is a copy of s, which is valid utf-8
+        unsafe {
+            Some(Self {
+                len_and_flag: UnsafeField::new(Self::FLAG | ((s.len() as u8) << Self::LEN_SHIFT)),
+                buf: UnsafeField::new(buf),
+            })
         }
     }
 
     /// in a union with a long string, returns `true` if this has been upgraded
     pub const fn is_short(&self) -> bool {
-        (*self.len_and_flag.get() & 1) == 1
+        (*self.len_and_flag.get() & Self::FLAG) != 0
     }
 
     /// Although not unsafe, sa the string is zeroed, you shold uphold that `len` is all
     /// user-initialised. This depends on the function that you are implementing with this.
     ///
     /// SAFETY:
-    /// - `len` must be less than or equal to `ShortString64::MAX_CAPACITY`
+    /// - `len` must be less than or equal to `ShortStringN::MAX_CAPACITY`
     pub unsafe fn set_len(&mut self, len: usize) {
-        let mask = *self.len_and_flag.get() & 1;
+        let mask = *self.len_and_flag.get() & Self::FLAG;
         // SAFETY:
-        // - len is masked `mask` which sets the last bit to 1 no matter what and does not affect
-        //   the first 7 bits at all
-        // - len >> 1 is len, the safety contract is passed to the caller
-        self.len_and_flag.set(mask | ((len as u8) << 1));
+        // - len is masked `mask` which sets the flag bit no matter what and does not affect the
+        //   other 7 bits at all, since len <= 126
+        // - len >> Self::LEN_SHIFT is len, the safety contract is passed to the caller
+        self.len_and_flag
+            .set(mask | ((len as u8) << Self::LEN_SHIFT));
+        debug_check_layout!(self);
+    }
+
+    /// Panics if the tag bit is not set, or if the length is larger than the capacity
+    pub(crate) fn check_layout(&self) {
+        assert!(self.is_short(), "the tag bit of a short string is not set");
+        assert!(
+            self.len() <= Self::MAX_CAPACITY,
+            "len {} is larger than the capacity {}",
+            self.len(),
+            Self::MAX_CAPACITY
+        );
+    }
+
+    /// Panics if any invariant of this string does not hold: the tag bit has to be set, the
+    /// length can be at most the capacity, and the bytes have to be valid utf-8
+    pub fn check_invariants(&self) {
+        self.check_layout();
+        if let Err(err) = std::str::from_utf8(self.as_bytes()) {
+            panic!("the bytes of a short string are not utf-8: {err}");
+        }
     }
 
     /// Returns the length of this short string, `len` upholds fewer invariants on a short string,
@@ -129,7 +354,7 @@ impl ShortString64 {
     ///
     /// - `self.len() <= self.capacity()` Note that `self.capacity()` is a constant
     pub const fn len(&self) -> usize {
-        ((*self.len_and_flag.get() & (u8::MAX << 1)) >> 1) as usize
+        ((*self.len_and_flag.get() & !Self::FLAG) >> Self::LEN_SHIFT) as usize
     }
 
     /// Returns the capacity of this short string, this is a constant, which is equal to
@@ -151,7 +376,7 @@ impl ShortString64 {
     pub fn next_ptr(&mut self) -> NonNull<u8> {
         // SAFETY:
         // - no issues with overflow or invalid value as self.len() < Self::MAX_CAPACITY, which is
-        //   23.
+        //   at most 126.
         // - ... which is also the size of the buffer, so we're either one past buf, or within
         //   the buffer
         unsafe {
@@ -186,7 +411,7 @@ impl ShortString64 {
         // SAFETY: bounds check done such that all code that for all code that reaches this point,
         // s.len() <= self.remaining_capacity()
         unsafe {
-            self.push_str_unchecked(&s);
+            self.push_str_unchecked(s);
         }
     }
 
@@ -196,26 +421,164 @@ impl ShortString64 {
         self.push_str(utf8);
     }
 
+    /// Insert a string `s` at byte position `idx`. If `s` does not fit, do nothing.
+    ///
+    /// Panics if `idx` does not lie on a char boundary, or is out of bounds.
+    pub fn insert_str(&mut self, idx: usize, s: &str) {
+        assert!(self.as_str().is_char_boundary(idx));
+        if s.len() > self.remaining_capacity() {
+            return;
+        }
+        // SAFETY: idx..idx is in bounds and on a char boundary (checked above), and we just did
+        // an exact bounds check on the remaining capacity
+        unsafe { self.replace_range_unchecked(idx..idx, s) }
+    }
+
+    /// Insert a `char` at byte position `idx`. If `ch` does not fit, do nothing.
+    ///
+    /// Panics if `idx` does not lie on a char boundary, or is out of bounds.
+    pub fn insert(&mut self, idx: usize, ch: char) {
+        let mut buf = [0; 4];
+        let utf8 = ch.encode_utf8(&mut buf);
+        self.insert_str(idx, utf8);
+    }
+
+    /// Removes the `char` at byte position `idx` and returns it.
+    ///
+    /// Panics if `idx` does not lie on a char boundary, or is larger than or equal to the length.
+    pub fn remove(&mut self, idx: usize) -> char {
+        let Some(ch) = self.as_str()[idx..].chars().next() else {
+            panic!("cannot remove a char from the end of a string");
+        };
+        // SAFETY: `ch` starts at idx, so the range covers exactly one whole utf8 sequence
+        unsafe { self.remove_range_unchecked(idx..idx + ch.len_utf8()) }
+        ch
+    }
+
+    /// Shortens this string to `new_len` bytes. Does nothing if `new_len` is greater than the
+    /// current length.
+    ///
+    /// Panics if `new_len` does not lie on a char boundary.
+    pub fn truncate(&mut self, new_len: usize) {
+        if new_len <= self.len() {
+            assert!(self.as_str().is_char_boundary(new_len));
+            // SAFETY: new_len is smaller than the current length and on a char boundary
+            unsafe { self.set_len(new_len) }
+        }
+    }
+
+    /// Splits this string in two at byte position `at`, returning everything after it. A short
+    /// string's tail always fits in another short string.
+    ///
+    /// Panics if `at` does not lie on a char boundary, or is out of bounds.
+    pub fn split_off(&mut self, at: usize) -> Self {
+        assert!(self.as_str().is_char_boundary(at));
+        let mut other = Self::new();
+        // SAFETY: the tail is at most as long as this string, so it fits into an empty one
+        unsafe { other.push_str_unchecked(&self.as_str()[at..]) }
+        // SAFETY: at is smaller than the current length and on a char boundary
+        unsafe { self.set_len(at) }
+        other
+    }
+
     /// Converts this to a [`LongString`]. Where the capacity is equal to or greater than
     /// `Self::MAX_CAPACITY + additional_capacity`.
-    pub fn into_long(&self, additional_capacity: usize) -> LongString {
-        let mut long = LongString::with_capacity(Self::MAX_CAPACITY + additional_capacity);
+    pub fn into_long(self, additional_capacity: usize) -> LongString {
+        self.into_long_in(additional_capacity, Global)
+    }
+
+    /// The same as [`ShortStringN::into_long`], but returns an error instead of panicking if the
+    /// allocation fails.
+    pub fn try_into_long(self, additional_capacity: usize) -> Result<LongString, TryReserveError> {
+        self.try_into_long_in(additional_capacity, Global)
+    }
+
+    /// The same as [`ShortStringN::into_long`], but allocates with `alloc`
+    pub fn into_long_in<A: Allocator>(self, additional_capacity: usize, alloc: A) -> LongString<A> {
+        self.try_into_long_in(additional_capacity, alloc)
+            .unwrap_or_else(|err| panic!("allocation error: {err}"))
+    }
+
+    /// The same as [`ShortStringN::into_long_in`], but returns an error instead of panicking if
+    /// the allocation fails.
+    pub fn try_into_long_in<A: Allocator>(
+        self,
+        additional_capacity: usize,
+        alloc: A,
+    ) -> Result<LongString<A>, TryReserveError> {
+        let capacity = Self::MAX_CAPACITY
+            .checked_add(additional_capacity)
+            .ok_or_else(unified_alloc::capacity_overflow)?;
+        let mut long = LongString::try_with_capacity_in(capacity, alloc)?;
         // SAFETY: long has at least Self::MAX_CAPACITY space, so it can fit any string this
         // short string contains
         unsafe {
             long.push_str_unchecked(self.as_str());
         }
-        long
+        Ok(long)
+    }
+
+    /// Replaces `self[range]` with `s`.
+    ///
+    /// # Safety
+    /// - `range.start <= range.end <= self.len()`
+    /// - `range.start` and `range.end` must lie on char boundaries
+    /// - `self.len() - range.len() + s.len()` must be less than or equal to
+    ///   `ShortStringN::MAX_CAPACITY`
+    pub unsafe fn replace_range_unchecked(&mut self, range: ops::Range<usize>, s: &str) {
+        let len = self.len();
+        let buf = self.buf.get_mut().cast::<u8>().as_ptr();
+        // SAFETY:
+        // - range is within 0..len (caller contract)
+        // - the new length fits in the buffer (caller contract), so buf[0..new_len] is valid for
+        //   writes
+        // - `s` cannot overlap, since we hold `&mut self`
+        let new_len = replace_range_raw(buf, len, range, s);
+        // SAFETY:
+        // - new_len <= MAX_CAPACITY (caller contract)
+        // - we have only moved whole utf8 sequences around, since range lies on char boundaries
+        //   and `s` is a valid str
+        self.set_len(new_len);
+    }
+
+    /// Appends `self[range]` to the end of this string.
+    ///
+    /// # Safety
+    /// - `range.start <= range.end <= self.len()`
+    /// - `range.start` and `range.end` must lie on char boundaries
+    /// - `range.len()` must be less than or equal to `self.remaining_capacity()`
+    pub unsafe fn extend_from_within_unchecked(&mut self, range: ops::Range<usize>) {
+        let len = self.len();
+        let buf = self.buf.get_mut().cast::<u8>().as_ptr();
+        // SAFETY:
+        // - src is within buf[0..len] (caller contract)
+        // - dst is buf[len..len + range.len()], which fits in the buffer (caller contract)
+        // - the regions cannot overlap, as range.end <= len
+        ptr::copy_nonoverlapping(buf.add(range.start), buf.add(len), range.len());
+        // SAFETY:
+        // - len + range.len() <= MAX_CAPACITY (caller contract)
+        // - we just copied a slice of whole utf8 sequences to buf[len..]
+        self.set_len(len + range.len());
+    }
+
+    /// Removes `self[range]` without checking any bounds.
+    ///
+    /// # Safety
+    /// - `range.start <= range.end <= self.len()`
+    /// - `range.start` and `range.end` must lie on char boundaries
+    pub unsafe fn remove_range_unchecked(&mut self, range: ops::Range<usize>) {
+        // SAFETY: caller contract, and removing bytes can never exceed MAX_CAPACITY
+        self.replace_range_unchecked(range, "");
     }
 
     /// Returns a slice of bytes that is always valid utf-8
-    pub fn as_bytes(&self) -> &[u8] {
-        // SAFETY: always safe to convert to &[u8]
-        unsafe { &*self.get_sized_buf().as_ptr() }
+    pub const fn as_bytes(&self) -> &[u8] {
+        // SAFETY: buf[0..len] is always initialised, and len <= N
+        unsafe { slice::from_raw_parts(self.buf.get().as_ptr(), self.len()) }
     }
 
     /// interpret this string as a `&str`
-    pub fn as_str(&self) -> &str {
+    pub const fn as_str(&self) -> &str {
         // SAFETY: always valid utf-8, by definition
         unsafe { std::str::from_utf8_unchecked(self.as_bytes()) }
     }
@@ -268,7 +631,7 @@ impl ShortString64 {
             // - both are properly aligned because we're working with bytes
             let raw = ptr::slice_from_raw_parts_mut(ptr.add(1), self.len());
             // SAFETY: ptr.add(1) cannot be null, as it is also a valid &[u8]
-            NonNull::new_unchecked(raw as *mut [u8])
+            NonNull::new_unchecked(raw)
         }
     }
 
@@ -281,81 +644,240 @@ impl ShortString64 {
     }
 }
 
-impl fmt::Display for ShortString64 {
+impl<const N: usize> fmt::Display for ShortStringN<N> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self.as_str())
     }
 }
 
-impl fmt::Debug for ShortString64 {
+impl<const N: usize> fmt::Debug for ShortStringN<N> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{:?}", self.as_str())
     }
 }
 
 // SAFETY: all structs contain different integers
+/// The allocator is the first field, so that a [`SsoStringN`] can store it in the same place for
+/// short strings, where it is followed by a [`ShortStringN`] instead.
 #[repr(C)]
-pub struct LongString {
+pub struct LongString<A: Allocator = Global> {
+    /// # Safety
+    /// `buf` is always allocated by `alloc`, so it can only be modified together with `buf`
+    alloc: UnsafeField<A, 3>,
     /// # Safety
     /// - `0` is always a valid value
-    /// - the last bit is always 0
-    /// when shifted by >> 1:
+    /// - the bits that overlap with the tag bit of a [`ShortStringN`] and with
+    ///   [`StaticString::FLAG`] are always 0
+    ///
+    /// when shifted by `>> Self::LEN_SHIFT`:
     /// - `len <= capacity`
     /// - `buf[0..len]` is always a valid SharedReadWrite slice of valid u8, if the string is not
-    ///    borrowed, otherwise the permissions become that of the borrow
+    ///   borrowed, otherwise the permissions become that of the borrow
     len: UnsafeField<usize, 0>,
     /// # Safety
-    /// buf and capacity are linked, so we can only modify either if we update the entire struct
-    /// simultaneously. As a result, we cannot implement Drop. The size of the allocated object
-    /// starting at buf.data is always exactly capacity bytes long.
-    buf: UnsafeField<RawBuf<u8>, 1>,
+    /// buf and capacity are linked, so we can only modify both at once, with a
+    /// [`SimultaneousUnsafeAssignment`]. The size of the allocated object starting at buf.data is
+    /// always exactly capacity bytes long.
+    buf: UnsafeField<RawBuf<u8, A>, 1>,
     /// # Safety
-    /// buf and capacity are linked, so we can only modify either if we update the entire struct
-    /// simultaneously. As a result, we cannot implement Drop. The size of the allocated object
-    /// starting at buf.data is always exactly capacity bytes long.
+    /// buf and capacity are linked, so we can only modify both at once, with a
+    /// [`SimultaneousUnsafeAssignment`]. The size of the allocated object starting at buf.data is
+    /// always exactly capacity bytes long.
     capacity: UnsafeField<usize, 2>,
 }
 
 impl LongString {
     /// Construct a new `LongString` with at least `capacity` as the `capacity`. Note that this
-    /// will panic in the case of an impossible allocation (e.g. `capacity > isize::MAX`)
+    /// will panic in the case of an impossible allocation (e.g. `capacity > Self::MAX_CAPACITY`)
     pub fn with_capacity(capacity: usize) -> Self {
-        let (buf, capacity) = RawBuf::new(capacity);
+        Self::with_capacity_in(capacity, Global)
+    }
+
+    /// Construct a new `LongString` with at least `capacity` as the `capacity`, returning an error
+    /// instead of panicking if the allocation is impossible or fails.
+    pub fn try_with_capacity(capacity: usize) -> Result<Self, TryReserveError> {
+        Self::try_with_capacity_in(capacity, Global)
+    }
+
+    /// Construct a new `LongString` from a `length`, `buf` and `capacity`
+    ///
+    /// # Safety
+    /// - invariants of `length`
+    ///     - `0` is always a valid value
+    ///     - `len <= capacity`
+    ///     - `buf[0..len]` is always a valid SharedReadWrite slice of valid u8, if the string is not
+    ///       borrowed, otherwise the permissions become that of the borrow
+    /// - invariants of `buf` and `capacity`
+    ///     - `capacity <= LongString::MAX_CAPACITY`
+    ///     - The size of the allocated object starting at buf is *exactly* `capacity` bytes long
+    ///     - `buf` must be allocated with std::allocator::Global
+    pub unsafe fn from_raw_parts(buf: NonNull<u8>, length: usize, capacity: usize) -> Self {
+        // SAFETY: passed to caller, `Global` is the allocator that std uses
+        Self::from_raw_parts_in(buf, length, capacity, Global)
+    }
+
+    pub fn from_str(s: &str) -> Self {
+        Self::from_str_in(s, Global)
+    }
+}
+
+impl<A: Allocator> LongString<A> {
+    /// How far `len` is shifted so that the tag bit of a [`ShortStringN`] and
+    /// [`StaticString::FLAG`] are always `0`. On big-endian targets they overlap with the two most
+    /// significant bits instead, which are always `0` since `len <= Self::MAX_CAPACITY`.
+    #[cfg(target_endian = "little")]
+    pub(crate) const LEN_SHIFT: u32 = 2;
+    #[cfg(target_endian = "big")]
+    pub(crate) const LEN_SHIFT: u32 = 0;
+
+    /// The largest capacity of a long string. Two bits of the length are taken by the tags, so
+    /// this is a quarter of the address space, rather than the `isize::MAX` of a `Vec`.
+    pub const MAX_CAPACITY: usize = usize::MAX >> 2;
+
+    /// Allocates a buffer for at least `capacity` bytes with `alloc`, returning the buffer and a
+    /// capacity that can be stored in a long string. Returns a capacity overflow error if
+    /// `capacity` is larger than [`Self::MAX_CAPACITY`].
+    pub(crate) fn try_new_buf(
+        capacity: usize,
+        alloc: &A,
+    ) -> Result<(RawBuf<u8, A>, usize), TryReserveError> {
+        if capacity > Self::MAX_CAPACITY {
+            return Err(unified_alloc::capacity_overflow());
+        }
+        let (buf, allocated) = RawBuf::try_new_in(capacity, alloc)?;
+        // any size between the one we asked for and the one we got fits the allocation, so the
+        // buffer can be freed with the smaller one
+        Ok((buf, cmp::min(allocated, Self::MAX_CAPACITY)))
+    }
+
+    /// Construct a new `LongString` with at least `capacity` as the `capacity`, allocated with
+    /// `alloc`. Note that this will panic in the case of an impossible allocation.
+    pub fn with_capacity_in(capacity: usize, alloc: A) -> Self {
+        Self::try_with_capacity_in(capacity, alloc)
+            .unwrap_or_else(|err| panic!("allocation error: {err}"))
+    }
+
+    /// Construct a new `LongString` with at least `capacity` as the `capacity`, allocated with
+    /// `alloc`, returning an error instead of panicking if the allocation is impossible or fails.
+    pub fn try_with_capacity_in(capacity: usize, alloc: A) -> Result<Self, TryReserveError> {
+        let (buf, capacity) = Self::try_new_buf(capacity, &alloc)?;
 
         unsafe {
-            Self {
+            Ok(Self {
                 // SAFETY: a value of `0` is always valid
                 len: UnsafeField::new(0),
-                // SAFETY: by definition of RawBuf::new, capacity and buf match, so both these
-                // constructions are safe
+                // SAFETY: by definition of LongString::try_new_buf, capacity and buf match, and buf
+                // was allocated by alloc, so all these constructions are safe
                 capacity: UnsafeField::new(capacity),
                 buf: UnsafeField::new(buf),
+                alloc: UnsafeField::new(alloc),
+            })
+        }
+    }
+
+    /// Construct a new `LongString` from a `length`, `buf`, `capacity` and the allocator that
+    /// allocated `buf`
+    ///
+    /// # Safety
+    /// - the same as [`LongString::from_raw_parts`], except that `buf` must be allocated with
+    ///   `alloc` (or a copy of it) instead of `Global`
+    pub unsafe fn from_raw_parts_in(
+        buf: NonNull<u8>,
+        length: usize,
+        capacity: usize,
+        alloc: A,
+    ) -> Self {
+        let long = Self {
+            // SAFETY: invariants of `.len()` are passed to caller, so we must ensure the tag bits
+            // are `0`, which we do by shifting left by LEN_SHIFT, since length <= MAX_CAPACITY
+            len: UnsafeField::new(length << Self::LEN_SHIFT),
+            // SAFETY: passed to caller
+            buf: UnsafeField::new(RawBuf {
+                data: buf,
+                _alloc: PhantomData,
+            }),
+            // SAFETY: passed to caller
+            capacity: UnsafeField::new(capacity),
+            // SAFETY: passed to caller
+            alloc: UnsafeField::new(alloc),
+        };
+        debug_check_layout!(long);
+        long
+    }
+
+    pub fn from_str_in(s: &str, alloc: A) -> Self {
+        let mut long = Self::with_capacity_in(s.len(), alloc);
+        // SAFETY: we allocate long with_capacity(s.len()). It is empty, therefore it must have
+        // remaining_capacity == capacity == s.len()
+        unsafe {
+            long.push_str_unchecked(s);
+        }
+        long
+    }
+
+    /// Returns a reference to the allocator that backs this string
+    pub const fn allocator(&self) -> &A {
+        self.alloc.get()
+    }
+
+    /// Moves the contents of this string to a new buffer of at least `capacity` bytes, and frees
+    /// the old one. If the allocation fails, `self` is left untouched and an error is returned.
+    ///
+    /// Panics if `capacity` is smaller than `self.len()`.
+    fn try_move_to_new_buf(&mut self, capacity: usize) -> Result<(), TryReserveError> {
+        assert!(capacity >= self.len());
+        let (buf, capacity) = Self::try_new_buf(capacity, self.allocator())?;
+        // SAFETY:
+        // - src is buf[0..len], which is initialised
+        // - dst is a new allocation of at least len bytes, so it is valid for writes and cannot
+        //   overlap
+        unsafe { ptr::copy_nonoverlapping(self.buf().as_ptr(), buf.as_ptr(), self.len()) }
+        // SAFETY:
+        // - buf was allocated by our allocator with exactly `capacity` bytes
+        // - len <= capacity, and buf[0..len] was just initialised with the old contents
+        let (old_buf, old_capacity) = unsafe {
+            SimultaneousUnsafeAssignment
+                .with(&mut self.buf, buf)
+                .with(&mut self.capacity, capacity)
+                .replace_all()
+        };
+        debug_check_layout!(self);
+        if old_capacity != 0 {
+            // SAFETY: the old capacity is the exact size of the old buffer, which was allocated by
+            // alloc, and `self` no longer points to it
+            unsafe {
+                old_buf
+                    .dealloc_in(old_capacity, self.allocator())
+                    .expect("should be the exact capacity");
             }
         }
+        Ok(())
     }
 
     /// interpret this as a `&str`
-    pub fn as_str(&self) -> &str {
+    pub const fn as_str(&self) -> &str {
         // SAFETY: `LongString` always contains valid utf-8, buf[0..len] is always initialised
         unsafe { std::str::from_utf8_unchecked(self.as_bytes()) }
     }
 
     pub fn as_mut_str(&mut self) -> &mut str {
-        // SAFETY: conversion to `&'self mut [u8]` is valid, since we have not modified the buffer,
-        // since acquiring the pointer (we immediately derefrenced)
-        let buf = unsafe { &mut *self.get_sized_buf().as_ptr() };
+        let buf = ptr::slice_from_raw_parts_mut(self.buf().data.as_ptr(), self.len());
+        // SAFETY: conversion to `&'self mut [u8]` is valid, since buf[0..len] is always initialised
+        // and we have not modified the buffer since acquiring the pointer (we immediately
+        // derefrenced)
+        let buf = unsafe { &mut *buf };
         // SAFETY: always valid utf-8, by definition
         unsafe { std::str::from_utf8_unchecked_mut(buf) }
     }
 
     /// alias for `self.as_str().as_bytes()`
-    pub fn as_bytes(&self) -> &[u8] {
+    pub const fn as_bytes(&self) -> &[u8] {
         // SAFETY:
         // - valid for reads of u8, since we are within 0..len, which is by definition,
         //   initialised and allocated
         // - we cannot mutate the slice, since the returned slice lives as long as the borrow
         //   to self
-        // - allocations are no larger than isize::MAX, so len can never be greater than that
+        // - capacities are no larger than MAX_CAPACITY, so len can never be greater than that
         unsafe { slice::from_raw_parts(self.buf().data.as_ptr(), self.len()) }
     }
 
@@ -401,8 +923,7 @@ impl LongString {
         // SAFETY:
         // - the maxmimum index is capacity, which is within the specified boundary of the allocated
         //   object (RawBuf.data), or one byte past the end
-        // - we cannot allocate a buffer of more than isize::MAX, thus capacity must be less than
-        //   `isize::MAX`
+        // - capacity is at most MAX_CAPACITY, which is less than `isize::MAX`
         // - allocations are fully within the address space, so we cannot wrap around
         let ptr = self.buf().data.as_ptr().add(index);
         // SAFETY:
@@ -433,9 +954,9 @@ impl LongString {
     /// you needn't check
     ///
     /// - `self.len() < self.capacity()`
-    /// - `self.len() < isize::MAX` (derived invariant from `self.capacity() < isize::MAX`)
+    /// - `self.len() <= Self::MAX_CAPACITY` (derived invariant from `self.capacity()`)
     pub const fn len(&self) -> usize {
-        *self.len.get() >> 1
+        *self.len.get() >> Self::LEN_SHIFT
     }
 
     /// Returns the capacity of this string, that is, how many bytes it can fit before a realloc.
@@ -444,14 +965,14 @@ impl LongString {
     ///
     /// `self.capacity()` upholds the following invariants:
     ///
-    /// - `self.capacity() < isize::MAX`
+    /// - `self.capacity() <= Self::MAX_CAPACITY`
     /// - `self.capacity()` is the exact size of the allocated buffer
     pub const fn capacity(&self) -> usize {
         *self.capacity.get()
     }
 
     /// Gets the underyling buffer being used for this string
-    pub const fn buf(&self) -> &RawBuf<u8> {
+    pub const fn buf(&self) -> &RawBuf<u8, A> {
         self.buf.get()
     }
 
@@ -462,24 +983,63 @@ impl LongString {
     }
 
     /// clones this string, with at least `additional_capacity` extra space
-    pub fn clone_with_additional_capacity(&self, additional_capacity: usize) -> Self {
-        let mut new = Self::with_capacity(self.capacity() + additional_capacity);
+    pub fn clone_with_additional_capacity(&self, additional_capacity: usize) -> Self
+    where
+        A: Clone,
+    {
+        self.try_clone_with_additional_capacity(additional_capacity)
+            .unwrap_or_else(|err| panic!("allocation error: {err}"))
+    }
+
+    /// The same as [`LongString::clone_with_additional_capacity`], but returns an error instead of
+    /// panicking if the allocation is impossible or fails.
+    pub fn try_clone_with_additional_capacity(
+        &self,
+        additional_capacity: usize,
+    ) -> Result<Self, TryReserveError>
+    where
+        A: Clone,
+    {
+        let capacity = self
+            .capacity()
+            .checked_add(additional_capacity)
+            .ok_or_else(unified_alloc::capacity_overflow)?;
+        let mut new = Self::try_with_capacity_in(capacity, self.allocator().clone())?;
         // SAFETY: new has at least self.capacity() space, so it can allocate anything that
         // self holds
         unsafe {
             new.push_str_unchecked(self.as_str());
         }
-        new
+        Ok(new)
     }
 
     /// realloc to fit at least `remaining_capacity` more bytes
     pub fn realloc(&mut self, remaining_capacity: usize) {
-        let new = self.clone_with_additional_capacity(cmp::max(
-            remaining_capacity - self.remaining_capacity(),
+        self.try_realloc(remaining_capacity)
+            .unwrap_or_else(|err| panic!("allocation error: {err}"))
+    }
+
+    /// realloc to fit at least `remaining_capacity` more bytes. If the allocation fails, `self` is
+    /// left untouched and an error is returned.
+    pub fn try_realloc(&mut self, remaining_capacity: usize) -> Result<(), TryReserveError> {
+        let additional = cmp::max(
+            remaining_capacity.saturating_sub(self.remaining_capacity()),
             self.capacity() * 2,
-        ));
-        self.free();
-        *self = new;
+        );
+        let capacity = self
+            .capacity()
+            .checked_add(additional)
+            .ok_or_else(unified_alloc::capacity_overflow)?;
+        self.try_move_to_new_buf(capacity)
+    }
+
+    /// Moves this string into a new buffer of at least `capacity` bytes, which may be smaller than
+    /// the current one.
+    ///
+    /// Panics if `capacity` is smaller than `self.len()`, or if the allocation fails.
+    pub fn realloc_exact(&mut self, capacity: usize) {
+        self.try_move_to_new_buf(capacity)
+            .unwrap_or_else(|err| panic!("allocation error: {err}"))
     }
 
     /// # Safety
@@ -517,118 +1077,504 @@ impl LongString {
         self.push_str(utf8);
     }
 
-    /// `len` is truncated to a 63-bit number.
+    /// Insert a string `s` at byte position `idx`, allocating if needed.
+    ///
+    /// Panics if `idx` does not lie on a char boundary, or is out of bounds.
+    pub fn insert_str(&mut self, idx: usize, s: &str) {
+        assert!(self.as_str().is_char_boundary(idx));
+        self.replace_range(idx..idx, s);
+    }
+
+    /// Insert a `char` at byte position `idx`, allocating if needed.
+    ///
+    /// Panics if `idx` does not lie on a char boundary, or is out of bounds.
+    pub fn insert(&mut self, idx: usize, ch: char) {
+        let mut buf = [0; 4];
+        let utf8 = ch.encode_utf8(&mut buf);
+        self.insert_str(idx, utf8);
+    }
+
+    /// Removes the `char` at byte position `idx` and returns it.
+    ///
+    /// Panics if `idx` does not lie on a char boundary, or is larger than or equal to the length.
+    pub fn remove(&mut self, idx: usize) -> char {
+        let Some(ch) = self.as_str()[idx..].chars().next() else {
+            panic!("cannot remove a char from the end of a string");
+        };
+        // SAFETY: `ch` starts at idx, so the range covers exactly one whole utf8 sequence
+        unsafe { self.remove_range_unchecked(idx..idx + ch.len_utf8()) }
+        ch
+    }
+
+    /// Shortens this string to `new_len` bytes. Does nothing if `new_len` is greater than the
+    /// current length. The capacity is left untouched.
+    ///
+    /// Panics if `new_len` does not lie on a char boundary.
+    pub fn truncate(&mut self, new_len: usize) {
+        if new_len <= self.len() {
+            assert!(self.as_str().is_char_boundary(new_len));
+            // SAFETY: new_len is smaller than the current length and on a char boundary
+            unsafe { self.set_len(new_len) }
+        }
+    }
+
+    /// Splits this string in two at byte position `at`, returning everything after it in a newly
+    /// allocated `LongString`. The capacity of `self` is left untouched.
+    ///
+    /// Panics if `at` does not lie on a char boundary, or is out of bounds.
+    pub fn split_off(&mut self, at: usize) -> LongString<A>
+    where
+        A: Clone,
+    {
+        assert!(self.as_str().is_char_boundary(at));
+        let other = LongString::from_str_in(&self.as_str()[at..], self.allocator().clone());
+        // SAFETY: at is smaller than the current length and on a char boundary
+        unsafe { self.set_len(at) }
+        other
+    }
+
+    /// Makes sure that at least `additional` more bytes can be pushed without a realloc
+    pub fn reserve(&mut self, additional: usize) {
+        if self.remaining_capacity() < additional {
+            self.realloc(additional);
+        }
+    }
+
+    /// The same as [`LongString::reserve`], but returns an error instead of panicking if the
+    /// allocation fails. `self` is left untouched on failure.
+    pub fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        if self.remaining_capacity() < additional {
+            self.try_realloc(additional)?;
+        }
+        Ok(())
+    }
+
+    /// Reallocates this string so that it has at least `additional` more bytes of remaining
+    /// capacity, without the amortised growth of [`LongString::try_reserve`]. `self` is left
+    /// untouched on failure.
+    pub fn try_reserve_exact(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        let capacity = self
+            .capacity()
+            .checked_add(additional)
+            .ok_or_else(unified_alloc::capacity_overflow)?;
+        self.try_move_to_new_buf(capacity)
+    }
+
+    /// Replaces `self[range]` with `s`, allocating if needed. Panics if either end of the range
+    /// does not lie on a char boundary, or is out of bounds.
+    pub fn replace_range<R>(&mut self, range: R, s: &str)
+    where
+        R: RangeBounds<usize>,
+    {
+        let range = normalize_range(range, self.len());
+        assert!(self.as_str().is_char_boundary(range.start));
+        assert!(self.as_str().is_char_boundary(range.end));
+
+        let new_len = self.len() - range.len() + s.len();
+        self.reserve(new_len.saturating_sub(self.len()));
+        let buf = self.buf().data.as_ptr();
+        // SAFETY:
+        // - range is within 0..len, checked by `normalize_range`
+        // - new_len <= capacity, since we reserved enough space for it
+        // - `s` cannot overlap, since we hold `&mut self`
+        let new_len = unsafe { replace_range_raw(buf, self.len(), range, s) };
+        // SAFETY:
+        // - new_len <= capacity
+        // - we have only moved whole utf8 sequences around, since range lies on char boundaries
+        //   and `s` is a valid str
+        unsafe { self.set_len(new_len) }
+    }
+
+    /// Appends `self[src]` to the end of this string, allocating if needed. Panics if either end
+    /// of the range does not lie on a char boundary, or is out of bounds.
+    pub fn extend_from_within<R>(&mut self, src: R)
+    where
+        R: RangeBounds<usize>,
+    {
+        let src = normalize_range(src, self.len());
+        assert!(self.as_str().is_char_boundary(src.start));
+        assert!(self.as_str().is_char_boundary(src.end));
+
+        self.reserve(src.len());
+        let len = self.len();
+        let buf = self.buf().data.as_ptr();
+        // SAFETY:
+        // - src is within buf[0..len], checked by `normalize_range`
+        // - dst is buf[len..len + src.len()], which is allocated, since we just reserved it
+        // - the regions cannot overlap, as src.end <= len
+        unsafe { ptr::copy_nonoverlapping(buf.add(src.start), buf.add(len), src.len()) };
+        // SAFETY:
+        // - len + src.len() <= capacity, since we just reserved it
+        // - we just copied a slice of whole utf8 sequences to buf[len..]
+        unsafe { self.set_len(len + src.len()) }
+    }
+
+    /// Removes `self[range]` without checking any bounds. The allocation is kept as it is.
+    ///
+    /// # Safety
+    /// - `range.start <= range.end <= self.len()`
+    /// - `range.start` and `range.end` must lie on char boundaries
+    pub unsafe fn remove_range_unchecked(&mut self, range: ops::Range<usize>) {
+        let buf = self.buf().data.as_ptr();
+        // SAFETY: range is within 0..len (caller contract) and we only shrink the string
+        let new_len = replace_range_raw(buf, self.len(), range, "");
+        // SAFETY: new_len <= len <= capacity, and range lies on char boundaries
+        self.set_len(new_len);
+    }
+
+    /// `len` is truncated to a 62-bit number on 64-bit targets (30-bit on 32-bit targets), which
+    /// is at most [`LongString::MAX_CAPACITY`].
     ///
     /// # Safety
     /// - everything from `buf[0..len]` must be initialised.
     /// - you must uphold `len <= capacity`
     unsafe fn set_len(&mut self, len: usize) {
         // SAFETY: safety contract passed to caller
-        self.len.set(len << 1);
-    }
-
-    /// free the buffer of this string, setting the `len` and `capacity` to `0`
-    pub fn free(&mut self) {
+        self.len.set(len << Self::LEN_SHIFT);
+        debug_check_layout!(self);
+    }
+
+    /// Panics if a tag bit is set, if the capacity is impossibly big, or if the length is larger
+    /// than the capacity. The size of the allocation itself cannot be asked of an allocator, so
+    /// only that there is one is checked.
+    pub(crate) fn check_layout(&self) {
+        let tag = self.len.get().to_ne_bytes()[0];
+        assert_eq!(
+            tag & ShortString::FLAG,
+            0,
+            "the tag bit of a long string is set"
+        );
+        assert_eq!(
+            tag & StaticString::FLAG,
+            0,
+            "the static bit of a long string is set"
+        );
         let capacity = self.capacity();
-        *self = unsafe {
-            Self {
-                // SAFETY: 0 always satisfies len's invaraints
-                len: UnsafeField::new(0),
-                // SAFETY: the buffer is dangling and the capacity is 0, which is a valid
-                // state for LongString, these two fields have a linked invariant
-                capacity: UnsafeField::new(0),
-                buf: UnsafeField::new(
-                    self.buf
-                        .own()
-                        // SAFETY: capacity is the exact size of the buffer
-                        .dealloc(capacity)
-                        .expect("should be the exact capacity"),
-                ),
-            }
-        };
+        assert!(
+            capacity <= Self::MAX_CAPACITY,
+            "capacity {capacity} is larger than {}",
+            Self::MAX_CAPACITY
+        );
+        assert!(
+            self.len() <= capacity,
+            "len {} is larger than the capacity {capacity}",
+            self.len()
+        );
+        if capacity != 0 {
+            assert_ne!(
+                self.buf().data,
+                NonNull::dangling(),
+                "a buffer with a capacity of {capacity} is dangling"
+            );
+        }
     }
 
-    /// Construct a new `LongString` from a `length`, `buf` and `capacity`
-    ///
-    /// # Safety
-    /// - invariants of `length`
-    ///     - `0` is always a valid value
-    ///     - `len <= capacity`
-    ///     - `buf[0..len]` is always a valid SharedReadWrite slice of valid u8, if the string is not
-    ///        borrowed, otherwise the permissions become that of the borrow
-    /// - invariants of `buf` and `capacity`
-    ///     - The size of the allocated object starting at buf is *exactly* `capacity` bytes long
-    ///     - `buf` must be allocated with std::allocator::Global
-    pub unsafe fn from_raw_parts(buf: NonNull<u8>, length: usize, capacity: usize) -> Self {
-        Self {
-            // SAFETY: invariants of `.len()` are passed to caller, so we must ensure the final bit
-            // is `0`, which we do by shifting left 1.
-            len: UnsafeField::new(length << 1),
-            // SAFETY: passed to caller
-            buf: UnsafeField::new(RawBuf { data: buf }),
-            // SAFETY: passed to caller
-            capacity: UnsafeField::new(capacity),
+    /// Panics if any invariant of this string does not hold: the tag bit has to be clear, the
+    /// length can be at most the capacity, a non-zero capacity needs an allocation, and the bytes
+    /// have to be valid utf-8
+    pub fn check_invariants(&self) {
+        self.check_layout();
+        if let Err(err) = std::str::from_utf8(self.as_bytes()) {
+            panic!("the bytes of a long string are not utf-8: {err}");
         }
     }
 
-    pub fn from_str(s: &str) -> Self {
-        let mut long = Self::with_capacity(s.len());
-        // SAFETY: we allocate long with_capacity(s.len()). It is empty, therefore it must have
-        // remaining_capacity == capacity == s.len()
+    /// free the buffer of this string, setting the `len` and `capacity` to `0`. Does nothing if
+    /// the string has no allocation (e.g. it has already been freed)
+    pub fn free(&mut self) {
+        if self.capacity() == 0 {
+            return;
+        }
+        // SAFETY:
+        // - 0 always satisfies len's invaraints
+        // - a dangling buffer with a capacity of 0 is a valid state for LongString, these two
+        //   fields have a linked invariant
+        let ((_, buf), capacity) = unsafe {
+            SimultaneousUnsafeAssignment
+                .with(&mut self.len, 0)
+                .with(&mut self.buf, RawBuf::dangling())
+                .with(&mut self.capacity, 0)
+                .replace_all()
+        };
+        debug_check_layout!(self);
+        // SAFETY: capacity is the exact size of the old buffer, which was allocated by our
+        // allocator, and `self` no longer points to it
         unsafe {
-            long.push_str_unchecked(s);
+            buf.dealloc_in(capacity, self.allocator())
+                .expect("should be the exact capacity");
         }
-        long
     }
 }
 
-impl fmt::Display for LongString {
+impl<A: Allocator> fmt::Display for LongString<A> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self.as_str())
     }
 }
 
-impl fmt::Debug for LongString {
+impl<A: Allocator> fmt::Debug for LongString<A> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{:?}", self.as_str())
     }
 }
 
-impl Clone for LongString {
+impl<A: Allocator + Clone> Clone for LongString<A> {
     fn clone(&self) -> Self {
         self.clone_with_additional_capacity(0)
     }
 }
 
-pub enum TaggedSsoString64Mut<'a> {
-    Short(&'a mut ShortString64),
-    Long(&'a mut LongString),
+pub enum TaggedSsoString64Mut<
+    'a,
+    const N: usize = { ShortString::MAX_CAPACITY },
+    A: Allocator = Global,
+> {
+    Short(&'a mut ShortStringN<N>),
+    Long(&'a mut LongString<A>),
 }
 
-pub enum TaggedSsoString64<'a> {
-    Short(&'a ShortString64),
-    Long(&'a LongString),
+pub enum TaggedSsoString64<
+    'a,
+    const N: usize = { ShortString::MAX_CAPACITY },
+    A: Allocator = Global,
+> {
+    Short(&'a ShortStringN<N>),
+    Long(&'a LongString<A>),
+    Static(&'a StaticString),
 }
 
-#[cfg(all(target_endian = "little", target_pointer_width = "64"))]
+/// A borrowed `&'static str`, the third variant of a [`SsoStringN`] next to [`ShortStringN`] and
+/// [`LongString`] (see [`SsoStringN::from_static`]). It is laid out like the start of a
+/// [`LongString`] without its allocator, with [`StaticString::FLAG`] set in its length. It owns
+/// nothing, so it is never written to or freed, and it has no capacity.
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub struct StaticString {
+    /// # Safety
+    /// - the bit that overlaps with the tag bit of a [`ShortStringN`] is always 0
+    /// - the bit that overlaps with `Self::FLAG` is always 1
+    ///
+    /// when masked with `!Self::LEN_FLAG` and shifted by `>> LongString::LEN_SHIFT`:
+    /// - `ptr[0..len]` is a `&'static str`
+    len_and_flag: UnsafeField<usize, 0>,
+    /// # Safety
+    /// - points to the start of a `&'static str` of `len` bytes
+    ptr: UnsafeField<NonNull<u8>, 1>,
+}
+
+impl StaticString {
+    /// The bit of the first byte of a [`SsoStringN`] that marks a static string, when the tag bit
+    /// of a [`ShortStringN`] is clear. It is the bit right next to the tag bit, which is why
+    /// [`LongString::LEN_SHIFT`] is 2 instead of 1.
+    #[cfg(target_endian = "little")]
+    pub(crate) const FLAG: u8 = 1 << 1;
+    #[cfg(target_endian = "big")]
+    pub(crate) const FLAG: u8 = 1 << 6;
+
+    /// `Self::FLAG` where it is in `len_and_flag`, which is its first byte
+    const LEN_FLAG: usize = {
+        let mut bytes = [0; mem::size_of::<usize>()];
+        bytes[0] = Self::FLAG;
+        usize::from_ne_bytes(bytes)
+    };
+
+    /// Borrows `s`, or returns `None` if it is longer than [`LongString::MAX_CAPACITY`], since its
+    /// length would not fit next to the tag bits
+    pub const fn new(s: &'static str) -> Option<Self> {
+        if s.len() > <LongString>::MAX_CAPACITY {
+            return None;
+        }
+        // SAFETY:
+        // - s.len() <= MAX_CAPACITY, so shifting it clears both tag bits, and the flag is set
+        // - the pointer and length are those of a `&'static str`
+        unsafe {
+            Some(Self {
+                len_and_flag: UnsafeField::new(
+                    (s.len() << <LongString>::LEN_SHIFT) | Self::LEN_FLAG,
+                ),
+                ptr: UnsafeField::new(NonNull::new_unchecked(s.as_ptr() as *mut u8)),
+            })
+        }
+    }
+
+    /// returns the length of this string in bytes
+    pub const fn len(&self) -> usize {
+        (*self.len_and_flag.get() & !Self::LEN_FLAG) >> <LongString>::LEN_SHIFT
+    }
+
+    /// A static string has no room to spare, so this is its length
+    pub const fn capacity(&self) -> usize {
+        self.len()
+    }
+
+    /// alias for `self.as_str().as_bytes()`
+    pub const fn as_bytes(&self) -> &'static [u8] {
+        // SAFETY: ptr[0..len] is a `&'static str`
+        unsafe { slice::from_raw_parts(self.ptr.get().as_ptr(), self.len()) }
+    }
+
+    /// Returns the `&'static str` that this borrows
+    pub const fn as_str(&self) -> &'static str {
+        // SAFETY: ptr[0..len] is a `&'static str`
+        unsafe { std::str::from_utf8_unchecked(self.as_bytes()) }
+    }
+
+    /// Shortens this string to `new_len` bytes, by borrowing less. Does nothing if `new_len` is
+    /// greater than the current length.
+    ///
+    /// Panics if `new_len` does not lie on a char boundary.
+    pub fn truncate(&mut self, new_len: usize) {
+        if new_len <= self.len() {
+            *self = Self::new(&self.as_str()[..new_len]).expect("the string got shorter");
+        }
+    }
+
+    /// Panics if the tag bit is set, if the static bit is clear, or if the length is impossibly
+    /// big
+    pub(crate) fn check_layout(&self) {
+        let tag = self.len_and_flag.get().to_ne_bytes()[0];
+        assert_eq!(
+            tag & ShortString::FLAG,
+            0,
+            "the tag bit of a static string is set"
+        );
+        assert_ne!(
+            tag & Self::FLAG,
+            0,
+            "the static bit of a static string is clear"
+        );
+        assert!(
+            self.len() <= <LongString>::MAX_CAPACITY,
+            "len {} is larger than {}",
+            self.len(),
+            <LongString>::MAX_CAPACITY
+        );
+    }
+
+    /// Panics if any invariant of this string does not hold: the tag bits have to be right, and
+    /// the bytes have to be valid utf-8
+    pub fn check_invariants(&self) {
+        self.check_layout();
+        if let Err(err) = std::str::from_utf8(self.as_bytes()) {
+            panic!("the bytes of a static string are not utf-8: {err}");
+        }
+    }
+}
+
+// SAFETY: a static string is a `&'static str`, which is both
+unsafe impl Send for StaticString {}
+// SAFETY: a static string is a `&'static str`, which is both
+unsafe impl Sync for StaticString {}
+
+impl fmt::Display for StaticString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl fmt::Debug for StaticString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.as_str())
+    }
+}
+
+/// The first byte of a [`ShortStringN`], or of the length of a [`LongString`] or [`StaticString`].
+/// A short string has the tag bit set, and a length of at most 126, and the others have it clear,
+/// so this is never `0xFF` for any of them. The missing variant is a niche, so `Option<SsoString>`
+/// is as big as a `SsoString`.
+#[derive(Clone, Copy)]
+#[repr(u8)]
+#[allow(dead_code)]
+#[rustfmt::skip]
+pub(crate) enum Tag {
+    V0 = 0, V1, V2, V3, V4, V5, V6, V7, V8, V9, V10, V11, V12, V13, V14, V15, V16, V17, V18, V19,
+    V20, V21, V22, V23, V24, V25, V26, V27, V28, V29, V30, V31, V32, V33, V34, V35, V36, V37, V38,
+    V39, V40, V41, V42, V43, V44, V45, V46, V47, V48, V49, V50, V51, V52, V53, V54, V55, V56, V57,
+    V58, V59, V60, V61, V62, V63, V64, V65, V66, V67, V68, V69, V70, V71, V72, V73, V74, V75, V76,
+    V77, V78, V79, V80, V81, V82, V83, V84, V85, V86, V87, V88, V89, V90, V91, V92, V93, V94, V95,
+    V96, V97, V98, V99, V100, V101, V102, V103, V104, V105, V106, V107, V108, V109, V110, V111,
+    V112, V113, V114, V115, V116, V117, V118, V119, V120, V121, V122, V123, V124, V125, V126, V127,
+    V128, V129, V130, V131, V132, V133, V134, V135, V136, V137, V138, V139, V140, V141, V142, V143,
+    V144, V145, V146, V147, V148, V149, V150, V151, V152, V153, V154, V155, V156, V157, V158, V159,
+    V160, V161, V162, V163, V164, V165, V166, V167, V168, V169, V170, V171, V172, V173, V174, V175,
+    V176, V177, V178, V179, V180, V181, V182, V183, V184, V185, V186, V187, V188, V189, V190, V191,
+    V192, V193, V194, V195, V196, V197, V198, V199, V200, V201, V202, V203, V204, V205, V206, V207,
+    V208, V209, V210, V211, V212, V213, V214, V215, V216, V217, V218, V219, V220, V221, V222, V223,
+    V224, V225, V226, V227, V228, V229, V230, V231, V232, V233, V234, V235, V236, V237, V238, V239,
+    V240, V241, V242, V243, V244, V245, V246, V247, V248, V249, V250, V251, V252, V253, V254,
+}
+
+/// The bytes of a [`ShortStringN`], a [`StaticString`], or the [`LongString`] without its
+/// allocator. This is not a
+/// union, since the compiler never looks for niches in unions, instead only the bytes after the
+/// tag are.
+#[derive(Clone, Copy)]
+#[repr(C)]
+#[cfg_attr(target_pointer_width = "64", repr(align(8)))]
+#[cfg_attr(target_pointer_width = "32", repr(align(4)))]
+pub(crate) struct RawSsoStringN<const N: usize> {
+    pub(crate) tag: Tag,
+    rest: RawRest<N>,
+}
+
+/// Everything after the tag of a [`RawSsoStringN`]. Copying a union copies all of its bytes as
+/// they are, so this keeps the provenance of the pointer in a long string.
+#[derive(Clone, Copy)]
 #[repr(C)]
-pub union SsoString {
-    pub(crate) short: ManuallyDrop<ShortString64>,
-    pub(crate) long: ManuallyDrop<LongString>,
+union RawRest<const N: usize> {
+    short: [MaybeUninit<u8>; N],
+    long: [MaybeUninit<u8>; mem::size_of::<LongString>() - 1],
 }
 
-impl Drop for SsoString {
+/// A string that stores up to `N` bytes inline, and moves to the heap when it grows any larger.
+/// The size of the string is the size of a [`LongString`] or of a [`ShortStringN<N>`], whichever
+/// is bigger, so inline capacities above [`ShortString::MAX_CAPACITY`] grow the string in steps of
+/// the pointer size.
+///
+/// The allocator comes first, followed by the bytes of either variant. Since [`LongString`] starts
+/// with its allocator as well, a long string can be used in place. A short string is the
+/// [`ShortStringN`] that starts at `raw`. Zero-sized allocators like [`Global`] take up no space
+/// at all, stateful ones grow the string by their own size.
+///
+/// A string that borrows a `&'static str` (see [`SsoStringN::from_static`]) is a third variant,
+/// a [`StaticString`] that starts at `raw`. It has the tag bit clear like a long string, and
+/// [`StaticString::FLAG`] set, which is always clear for a long string. It is read like the other
+/// two, and becomes owned in [`SsoStringN::tagged_mut`].
+#[repr(C)]
+pub struct SsoStringN<const N: usize, A: Allocator = Global> {
+    /// # Safety
+    /// - for long strings, this is the allocator of the [`LongString`] that starts at `self`
+    alloc: A,
+    /// # Safety
+    /// - the tag bit and [`StaticString::FLAG`] of `raw.tag` say which variant is active
+    /// - for short strings, a valid [`ShortStringN<N>`] starts at `raw`
+    /// - for long strings, a valid [`LongString<A>`] starts at `self`
+    /// - for static strings, a valid [`StaticString`] starts at `raw`
+    raw: RawSsoStringN<N>,
+}
+
+const _: () = assert!(mem::size_of::<StaticString>() <= mem::size_of::<LongString>());
+
+/// The [`SsoStringN`] that is exactly as big as a `std::string::String`: 23 bytes inline on 64-bit
+/// targets and 11 bytes inline on 32-bit targets
+pub type SsoString<A = Global> = SsoStringN<{ ShortString::MAX_CAPACITY }, A>;
+
+const _: () = assert!(mem::size_of::<SsoString>() == mem::size_of::<LongString>());
+const _: () = assert!(mem::size_of::<Option<SsoString>>() == mem::size_of::<SsoString>());
+
+impl<const N: usize, A: Allocator> Drop for SsoStringN<N, A> {
     fn drop(&mut self) {
-        match self.tagged_mut() {
-            TaggedSsoString64Mut::Long(long) => {
+        // the allocator is dropped right after this, like any other field. Static strings own
+        // nothing, and `tagged_mut` would copy them first, so only long strings are freed.
+        if self.is_long() {
+            if let TaggedSsoString64Mut::Long(long) = self.tagged_mut() {
                 long.free();
             }
-            _ => {}
         }
     }
 }
 
-impl<'a> From<&'a str> for SsoString {
+impl<'a, const N: usize> From<&'a str> for SsoStringN<N> {
     fn from(value: &'a str) -> Self {
         let mut s = Self::new();
         s.push_str(value);
@@ -636,45 +1582,82 @@ impl<'a> From<&'a str> for SsoString {
     }
 }
 
-// we only allow this feature on stable, because I have no idea how the API is going to change for
-// swap-in allocators and it makes me shiver in my boots to think about the implications of this
-#[cfg(not(feature = "nightly"))]
-impl From<String> for SsoString {
+impl<'a, const N: usize> From<&'a mut str> for SsoStringN<N> {
+    fn from(value: &'a mut str) -> Self {
+        Self::from(&*value)
+    }
+}
+
+impl<'a, const N: usize> From<&'a String> for SsoStringN<N> {
+    fn from(value: &'a String) -> Self {
+        Self::from(value.as_str())
+    }
+}
+
+impl<const N: usize> From<char> for SsoStringN<N> {
+    fn from(value: char) -> Self {
+        let mut s = Self::new();
+        s.push(value);
+        s
+    }
+}
+
+impl<const N: usize> From<Box<str>> for SsoStringN<N> {
+    fn from(value: Box<str>) -> Self {
+        // reuses the allocation if the string is long
+        Self::from(value.into_string())
+    }
+}
+
+impl<'a, const N: usize> From<Cow<'a, str>> for SsoStringN<N> {
+    fn from(value: Cow<'a, str>) -> Self {
+        match value {
+            Cow::Borrowed(s) => Self::from(s),
+            Cow::Owned(s) => Self::from(s),
+        }
+    }
+}
+
+// `std::string::String` always allocates with `Global`, so we can only adopt its buffer into a
+// string that uses `Global` as well
+impl<const N: usize> From<String> for SsoStringN<N> {
     fn from(value: String) -> Self {
         // perform a memcpy if the `String` is short enough, this is more likely to result in a
         // cache miss than a stack ptr swap, but is probably fine for most use-cases of this
-        // function, where we immediately turn a small std::string::String into an SsoString
-        if value.len() < ShortString64::MAX_CAPACITY {
-            let mut short = ManuallyDrop::new(ShortString64::new());
-            short.push_str(&value);
-            return SsoString { short };
+        // function, where we immediately turn a small std::string::String into an SsoString. A
+        // buffer that is too big for a long string has to be copied as well.
+        if value.len() <= N || value.capacity() > <LongString>::MAX_CAPACITY {
+            let mut s = Self::new();
+            s.push_str(&value);
+            return s;
         }
 
-        // otherwise, we need to swap stack values
-        let mut value = mem::ManuallyDrop::new(value);
+        // otherwise, we need to swap stack values. The pointer has to come from the `Vec`, a
+        // pointer from the `str` may only access the initialised part of the buffer, which is not
+        // enough to free it with.
+        let mut value = mem::ManuallyDrop::new(value.into_bytes());
         let long = unsafe {
             // SAFETY:
             // - since `value.len()` is always greater than `0`, we can be sure that it is not
             //   dangling or null.
             let ptr = NonNull::new_unchecked(value.as_mut_ptr());
             // SAFETY:
-            // - all length and capacity invariants are upheld by `std::string::String`
+            // - all length and capacity invariants are upheld by `std::string::String`, and the
+            //   capacity is at most `LongString::MAX_CAPACITY`
             // - RawBuf uses `Global` internally, so handing over `String`'s allocation is just
             //   fine
-            // - ptr has SharedReadWrite provenance.. I think
+            // - ptr is valid for reads and writes of the whole buffer
             LongString::from_raw_parts(ptr, value.len(), value.capacity())
         };
 
-        SsoString {
-            long: ManuallyDrop::new(long),
-        }
+        Self::from_long(long)
     }
 }
 
 /// A wrapper around `str`, so that we can implement `ToOwned` where `ToOwned::Owned` is
 /// `sso::String`
 #[repr(transparent)]
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct SsoStr(str);
 
 impl Deref for SsoStr {
@@ -708,139 +1691,704 @@ impl ToOwned for SsoStr {
 
     fn to_owned(&self) -> Self::Owned {
         let s: &str = self;
-        let owned = SsoString::from(s);
-        owned
+        SsoString::from(s)
     }
 }
 
-impl SsoString {
-    pub fn new() -> Self {
-        Self {
-            short: ManuallyDrop::new(ShortString64::new()),
+impl<const N: usize> SsoStringN<N> {
+    /// Constructs an empty (short) string, this can be used to initialise a `static`
+    pub const fn new() -> Self {
+        Self::from_short_const(ShortStringN::new())
+    }
+
+    /// Copies `s` into a short string, or returns `None` if it is longer than `N` bytes. Unlike
+    /// `From<&str>`, this never allocates, so it can be used to initialise a `static`. See
+    /// [`short_string!`](crate::short_string) to fail to compile instead.
+    pub const fn from_short_str(s: &str) -> Option<Self> {
+        match ShortStringN::try_from_str(s) {
+            Some(short) => Some(Self::from_short_const(short)),
+            None => None,
         }
     }
 
-    /// Returns `true` if this string is a short string (no heap allocations), and `false` otherwise
-    pub fn is_short(&self) -> bool {
-        // SAFETY: transmuting anything to a byte array is always valid, which is essentially what
-        // we're doing when we do this.
-        unsafe { self.short.is_short() }
+    /// The same as [`SsoStringN::from_short`], but usable in a const fn
+    const fn from_short_const(short: ShortStringN<N>) -> Self {
+        let mut this = MaybeUninit::<Self>::uninit();
+        // SAFETY:
+        // - `Global` is zero-sized, so it does not need to be initialised
+        // - raw fits a short string, see `from_short`
+        // - the tag of a short string is always valid, and everything after it is allowed to be
+        //   uninitialised
+        unsafe {
+            ptr::addr_of_mut!((*this.as_mut_ptr()).raw)
+                .cast::<ShortStringN<N>>()
+                .write(short);
+            this.assume_init()
+        }
     }
 
-    /// Returns `!self.is_short()`
-    pub fn is_long(&self) -> bool {
-        !self.is_short()
+    /// Borrows a `&'static str` without copying it, however long it is. The string becomes owned
+    /// the first time it is changed, which only allocates if it doesn't fit in `N` bytes.
+    ///
+    /// Panics if `s` is longer than [`LongString::MAX_CAPACITY`].
+    pub const fn from_static(s: &'static str) -> Self {
+        let Some(static_str) = StaticString::new(s) else {
+            panic!("a static string can be at most LongString::MAX_CAPACITY bytes");
+        };
+        let mut this = MaybeUninit::<Self>::uninit();
+        // SAFETY:
+        // - `Global` is zero-sized, so it does not need to be initialised
+        // - raw is at least as big as a static string, and aligned for a pointer
+        // - the first byte of the length of a static string is always a valid tag, and everything
+        //   after the pointer is allowed to be uninitialised
+        unsafe {
+            ptr::addr_of_mut!((*this.as_mut_ptr()).raw)
+                .cast::<StaticString>()
+                .write(static_str);
+            this.assume_init()
+        }
     }
 
-    /// Returns the underlying union as an enum, allowing you to access the underlying short or
-    /// long variant for the string
-    pub fn tagged(&self) -> TaggedSsoString64 {
-        if self.is_short() {
-            TaggedSsoString64::Short(unsafe { &self.short })
-        } else {
-            TaggedSsoString64::Long(unsafe { &self.long })
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::with_capacity_in(capacity, Global)
+    }
+
+    /// Creates a new `SsoString::Long` from a length, capacity and pointer. This method only
+    /// exists to match `std::string::String`'s method of the same signature and name. It will
+    /// always create a long string, which is probably what you want if you are using this method.
+    ///
+    /// # Safety (from [`std::string::String`])
+    ///
+    /// This is highly unsafe, due to the numer of invariants that aren't checked:
+    ///
+    /// - The memory at buf needs to have been previously allocated by the same allocator the
+    ///   standard library uses, with a required alignment of exactly 1.
+    /// - `length` needs to be less than or equal to capacity.
+    /// - `capacity` needs to be the correct value, and at most [`LongString::MAX_CAPACITY`].
+    /// - The first length bytes at buf need to be valid UTF-8.
+    pub unsafe fn from_raw_parts(buf: *mut u8, length: usize, capacity: usize) -> Self {
+        // SAFETY: safety contract passed to caller (buf must be nonnull)
+        let ptr = NonNull::new_unchecked(buf);
+        // SAFETY: safety contract passed to caller
+        Self::from_long(LongString::from_raw_parts(ptr, length, capacity))
+    }
+
+    /// Decodes a UTF-16 encoded slice into a new string, returning an error if it contains any
+    /// invalid data. The string is short if the decoded bytes fit in it.
+    pub fn from_utf16(v: &[u16]) -> Result<Self, FromUtf16Error> {
+        Self::try_decode_utf16(v.iter().copied(), v.len()).ok_or_else(utf16_error)
+    }
+
+    /// Decodes a UTF-16 encoded slice into a new string, replacing invalid data with
+    /// [`char::REPLACEMENT_CHARACTER`]
+    pub fn from_utf16_lossy(v: &[u16]) -> Self {
+        Self::decode_utf16_lossy(v.iter().copied(), v.len())
+    }
+
+    /// The same as [`SsoStringN::from_utf16`], but decodes little-endian bytes. A trailing odd byte
+    /// is an error.
+    pub fn from_utf16le(v: &[u8]) -> Result<Self, FromUtf16Error> {
+        let units = v.chunks_exact(2);
+        if !units.remainder().is_empty() {
+            return Err(utf16_error());
         }
+        let units = units.map(|unit| u16::from_le_bytes([unit[0], unit[1]]));
+        Self::try_decode_utf16(units, v.len() / 2).ok_or_else(utf16_error)
     }
 
-    /// Same as [`SsoString::tagged`], but returns allows mutation of the underlying values instead
-    pub fn tagged_mut(&mut self) -> TaggedSsoString64Mut {
-        if self.is_short() {
-            TaggedSsoString64Mut::Short(unsafe { &mut self.short })
-        } else {
-            TaggedSsoString64Mut::Long(unsafe { &mut self.long })
+    /// The same as [`SsoStringN::from_utf16`], but decodes big-endian bytes. A trailing odd byte is
+    /// an error.
+    pub fn from_utf16be(v: &[u8]) -> Result<Self, FromUtf16Error> {
+        let units = v.chunks_exact(2);
+        if !units.remainder().is_empty() {
+            return Err(utf16_error());
+        }
+        let units = units.map(|unit| u16::from_be_bytes([unit[0], unit[1]]));
+        Self::try_decode_utf16(units, v.len() / 2).ok_or_else(utf16_error)
+    }
+
+    /// The same as [`SsoStringN::from_utf16_lossy`], but decodes little-endian bytes. A trailing
+    /// odd byte is replaced as well.
+    pub fn from_utf16le_lossy(v: &[u8]) -> Self {
+        let units = v.chunks_exact(2);
+        let odd = !units.remainder().is_empty();
+        let units = units.map(|unit| u16::from_le_bytes([unit[0], unit[1]]));
+        let mut s = Self::decode_utf16_lossy(units, v.len() / 2);
+        if odd {
+            s.push(char::REPLACEMENT_CHARACTER);
         }
+        s
     }
 
-    duck_impl! {
-        /// Returns a slice of bytes of this string's contents
-        pub fn as_bytes(&self) -> &[u8];
+    /// The same as [`SsoStringN::from_utf16_lossy`], but decodes big-endian bytes. A trailing odd
+    /// byte is replaced as well.
+    pub fn from_utf16be_lossy(v: &[u8]) -> Self {
+        let units = v.chunks_exact(2);
+        let odd = !units.remainder().is_empty();
+        let units = units.map(|unit| u16::from_be_bytes([unit[0], unit[1]]));
+        let mut s = Self::decode_utf16_lossy(units, v.len() / 2);
+        if odd {
+            s.push(char::REPLACEMENT_CHARACTER);
+        }
+        s
     }
 
-    duck_impl! {
-        pub fn as_mut_str(&mut self) -> &mut str;
+    /// Decodes `units` into a new string, or returns `None` at the first unpaired surrogate.
+    /// `len` is the number of units, which is also the least number of bytes they decode to, so
+    /// the string starts out long only if they can never fit in a short one.
+    fn try_decode_utf16(units: impl Iterator<Item = u16>, len: usize) -> Option<Self> {
+        let mut s = Self::with_capacity(len);
+        for ch in char::decode_utf16(units) {
+            s.push(ch.ok()?);
+        }
+        Some(s)
     }
 
-    never_impl! {
-        /// A method with the same name exists on `std::string::String`, but cannot exist on this
-        /// version, as it does not use a vector internally (ever). I don't think this method is
-        /// good anyway, since it doesn't scope the unsafe behaviour appropriately.
-        ///
-        /// If you desperately want to use this kind of method, you can simply create `&str`s
-        /// with unsafe unchecked methods. Probably not needed though, but I won't judge `:o)`
-        pub unsafe fn as_mut_vec(&mut self) -> &mut Vec<u8>;
+    /// The same as [`SsoStringN::try_decode_utf16`], but replaces unpaired surrogates
+    fn decode_utf16_lossy(units: impl Iterator<Item = u16>, len: usize) -> Self {
+        let mut s = Self::with_capacity(len);
+        for ch in char::decode_utf16(units) {
+            s.push(ch.unwrap_or(char::REPLACEMENT_CHARACTER));
+        }
+        s
     }
 
-    duck_impl! {
-        pub fn as_str(&self) -> &str;
+    /// Converts a vector of bytes to a string. Short strings are copied inline and the buffer is
+    /// freed, long strings keep the buffer of the vector.
+    pub fn from_utf8(v: Vec<u8>) -> Result<Self, FromUtf8Error> {
+        std::string::String::from_utf8(v).map(|s| s.into())
     }
 
-    duck_impl! {
-        pub fn capacity(&self) -> usize;
+    /// Converts a vector of bytes to a string without checking that it is valid UTF-8, the same as
+    /// [`SsoStringN::from_utf8`] otherwise.
+    ///
+    /// # Safety
+    /// - the bytes passed in must be valid UTF-8
+    pub unsafe fn from_utf8_unchecked(v: Vec<u8>) -> Self {
+        // SAFETY: v is valid utf-8 (caller contract)
+        Self::from(unsafe { std::string::String::from_utf8_unchecked(v) })
     }
 
-    duck_impl! {
-        pub fn clear(&mut self as duck) {
-            // SAFETY: 0 is always a valid value for len on both variants
-            unsafe { duck.set_len(0) }
+    /// Converts this string into a `Box<str>`, dropping any excess capacity. A long string hands
+    /// its buffer over to the box, which is shrunk in place if the allocator allows it.
+    pub fn into_boxed_str(self) -> Box<str> {
+        String::from(self).into_boxed_str()
+    }
+
+    /// Leaks this string, returning a mutable reference to its contents. A short string is moved
+    /// to the heap first, since its bytes live inside of `self`. The excess capacity of a long
+    /// string is leaked along with it, call [`SsoStringN::into_boxed_str`] and [`Box::leak`] to
+    /// avoid that.
+    pub fn leak<'a>(self) -> &'a mut str {
+        String::from(self).leak()
+    }
+}
+
+impl SsoString {
+    /// Converts a slice of bytes to a string, replacing invalid sequences with
+    /// [`char::REPLACEMENT_CHARACTER`]. Valid input is borrowed, invalid input is decoded into a
+    /// string that is short if it fits.
+    pub fn from_utf8_lossy(v: &[u8]) -> Cow<'_, SsoStr> {
+        if let Ok(s) = std::str::from_utf8(v) {
+            return Cow::Borrowed(SsoStr::from_str(s));
+        }
+
+        // every invalid sequence is at least one byte, and is replaced by three
+        let mut s = Self::with_capacity(v.len());
+        for chunk in v.utf8_chunks() {
+            s.push_str(chunk.valid());
+            if !chunk.invalid().is_empty() {
+                s.push(char::REPLACEMENT_CHARACTER);
+            }
         }
+        Cow::Owned(s)
     }
+}
 
-    todo_impl! {
-        pub fn drain<R>(&mut self, _range: R) -> Drain<'_>
-        where
-            R: RangeBounds<usize>,
+/// Sets the length of a string that is being compacted in place to `idx - del_bytes` when dropped,
+/// so that a panic halfway through leaves only the characters that were already kept
+struct SetLenOnDrop<'a, const N: usize, A: Allocator> {
+    /// # Safety
+    /// - bytes[0..idx - del_bytes] must be valid utf-8 whenever this is dropped
+    s: &'a mut SsoStringN<N, A>,
+    idx: usize,
+    del_bytes: usize,
+}
+
+impl<const N: usize, A: Allocator> Drop for SetLenOnDrop<'_, N, A> {
+    fn drop(&mut self) {
+        // SAFETY: the kept part of the string is valid utf-8 (field contract), and it is never
+        // longer than the string was
+        unsafe { self.s.set_len(self.idx - self.del_bytes) }
     }
+}
+
+/// A [`FromUtf16Error`]. These can only be constructed by the standard library, so we get one by
+/// decoding a lone surrogate.
+fn utf16_error() -> FromUtf16Error {
+    String::from_utf16(&[0xD800]).expect_err("a lone surrogate is never valid UTF-16")
+}
 
-    todo_impl! {
-        pub fn extend_from_within<R>(&mut self, _src: R)
-        where
-            R: RangeBounds<usize>,
+impl<const N: usize> From<SsoStringN<N>> for String {
+    /// A long string hands its buffer over without copying, a short or static string is copied
+    /// into a new buffer that is exactly as big as it is.
+    fn from(value: SsoStringN<N>) -> Self {
+        // the buffer of a long string is owned by the new string, and `Global` needs no dropping
+        let value = ManuallyDrop::new(value);
+        match value.tagged() {
+            TaggedSsoString64::Short(short) => String::from(short.as_str()),
+            TaggedSsoString64::Static(s) => String::from(s.as_str()),
+            TaggedSsoString64::Long(long) => {
+                // SAFETY:
+                // - the buffer was allocated by `Global`, which is the allocator that `String`
+                //   uses, as a slice of `u8`, so with an alignment of exactly 1
+                // - `long.capacity()` is the exact size of the buffer, and `long.len()` is at most
+                //   that
+                // - the first `long.len()` bytes of a long string are valid utf-8
+                // - `value` is never dropped, so the new string is the only owner of the buffer
+                unsafe { String::from_raw_parts(long.buf().as_ptr(), long.len(), long.capacity()) }
+            }
+        }
     }
+}
 
-    /// Creates a new `SsoString::Long` from a length, capacity and pointer. This method only
-    /// exists to match `std::string::String`'s method of the same signature and name. It will
-    /// always create a long string, which is probably what you want if you are using this method.
-    ///
-    /// # Safety (from [`std::string::String`])
+impl<const N: usize, A: Allocator> SsoStringN<N, A> {
+    /// Constructs an empty (short) string that will use `alloc` if it ever has to allocate
+    pub fn new_in(alloc: A) -> Self {
+        Self::from_short(ShortStringN::new(), alloc)
+    }
+
+    /// The same as [`SsoStringN::from_static`], but uses `alloc` once the string becomes owned
+    pub fn from_static_in(s: &'static str, alloc: A) -> Self {
+        let static_str = StaticString::new(s)
+            .expect("a static string can be at most LongString::MAX_CAPACITY bytes");
+        Self::from_static_string(static_str, alloc)
+    }
+
+    /// Constructs a static string from its parts
+    fn from_static_string(static_str: StaticString, alloc: A) -> Self {
+        let mut this = MaybeUninit::<Self>::uninit();
+        let this_ptr = this.as_mut_ptr();
+        // SAFETY:
+        // - both pointers are derived from the fields of `this`, so they are aligned and in bounds
+        // - raw is at least as big as a static string, and aligned for a pointer
+        // - the first byte of the length of a static string is always a valid tag, and everything
+        //   after the pointer is allowed to be uninitialised
+        unsafe {
+            ptr::addr_of_mut!((*this_ptr).alloc).write(alloc);
+            ptr::addr_of_mut!((*this_ptr).raw)
+                .cast::<StaticString>()
+                .write(static_str);
+        }
+        // SAFETY: both fields have just been initialised
+        let this = unsafe { this.assume_init() };
+        debug_check_layout!(this);
+        this
+    }
+
+    /// Returns `true` if this string borrows a `&'static str`, see [`SsoStringN::from_static`]
+    pub const fn is_static(&self) -> bool {
+        // the tag is the first byte of every variant
+        !self.is_short() && (self.raw.tag as u8 & StaticString::FLAG) != 0
+    }
+
+    /// Panics if the invariants of the variant that this is do not hold, see
+    /// [`SsoStringN::check_invariants`]
+    pub(crate) fn check_layout(&self) {
+        match self.tagged() {
+            TaggedSsoString64::Short(short) => short.check_layout(),
+            TaggedSsoString64::Long(long) => long.check_layout(),
+            TaggedSsoString64::Static(s) => s.check_layout(),
+        }
+    }
+
+    /// Panics if any invariant of this string does not hold. A short string must have its tag bit
+    /// set and fit inline, a long string must have its tag bits clear and fit in its buffer, a
+    /// static string must have only [`StaticString::FLAG`] set, and the bytes of all of them must
+    /// be valid utf-8.
     ///
-    /// This is highly unsafe, due to the numer of invariants that aren't checked:
+    /// The O(1) part of these checks runs after every change in debug builds, or in release
+    /// builds with the `strict-invariants` feature. The utf-8 check never runs automatically, not
+    /// even with `strict-invariants`, only when this is called.
+    pub fn check_invariants(&self) {
+        match self.tagged() {
+            TaggedSsoString64::Short(short) => short.check_invariants(),
+            TaggedSsoString64::Long(long) => long.check_invariants(),
+            TaggedSsoString64::Static(s) => s.check_invariants(),
+        }
+    }
+
+    /// Copies a static string into storage of its own, so that it can be changed. The result is
+    /// short if it fits. Every other string is left untouched.
+    fn make_owned(&mut self) {
+        let TaggedSsoString64::Static(s) = self.tagged() else {
+            return;
+        };
+        let s = s.as_str();
+        if s.len() <= N {
+            let mut short = ShortStringN::new();
+            // SAFETY: the short string is empty, and s fits in it
+            unsafe { short.push_str_unchecked(s) };
+            // SAFETY: a static string owns nothing
+            unsafe { self.write_short(short) };
+            return;
+        }
+
+        let (buf, capacity) = LongString::try_new_buf(s.len(), &self.alloc)
+            .unwrap_or_else(|err| panic!("allocation error: {err}"));
+        // SAFETY:
+        // - dst is a new allocation of at least s.len() bytes, so it fits and cannot overlap
+        // - the allocator is moved out of self, which is immediately overwritten without being
+        //   dropped, nothing in between can panic
+        // - buf was allocated by alloc with exactly capacity bytes, and buf[0..len] was just
+        //   initialised with the valid utf-8 of s
+        unsafe {
+            ptr::copy_nonoverlapping(s.as_ptr(), buf.as_ptr(), s.len());
+            let alloc = ptr::read(&self.alloc);
+            let long = LongString::from_raw_parts_in(buf.data, s.len(), capacity, alloc);
+            ptr::write(self, SsoStringN::from_long(long));
+        }
+    }
+
+    /// Overwrites the contents of this string with `short`, the allocator stays where it is
     ///
-    /// - The memory at buf needs to have been previously allocated by the same allocator the
-    ///   standard library uses, with a required alignment of exactly 1.
-    /// - `length` needs to be less than or equal to capacity.
-    /// - `capacity` needs to be the correct value.
-    /// - The first length bytes at buf need to be valid UTF-8.
-    pub unsafe fn from_raw_parts(buf: *mut u8, length: usize, capacity: usize) -> Self {
-        // SAFETY: safety contract passed to caller (buf must be nonnull)
-        let ptr = NonNull::new_unchecked(buf);
-        // SAFETY: safety contract passed to caller
-        SsoString {
-            long: ManuallyDrop::new(LongString::from_raw_parts(ptr, length, capacity)),
+    /// # Safety
+    /// - this string must not own a buffer, since it would be leaked
+    unsafe fn write_short(&mut self, short: ShortStringN<N>) {
+        let raw = (&mut self.raw as *mut RawSsoStringN<N>).cast::<ShortStringN<N>>();
+        // SAFETY: a short string fits in raw
+        unsafe { ptr::write(raw, short) }
+        debug_check_layout!(self);
+    }
+
+    /// Constructs a short string from its parts
+    fn from_short(short: ShortStringN<N>, alloc: A) -> Self {
+        let mut this = MaybeUninit::<Self>::uninit();
+        let this_ptr = this.as_mut_ptr();
+        // SAFETY:
+        // - both pointers are derived from the fields of `this`, so they are aligned and in bounds
+        // - raw is at least N + 1 bytes, rounded up to the alignment of a `ShortStringN`, so the
+        //   short string fits
+        // - the tag of a short string is always valid, and everything after it is allowed to be
+        //   uninitialised
+        unsafe {
+            ptr::addr_of_mut!((*this_ptr).alloc).write(alloc);
+            ptr::addr_of_mut!((*this_ptr).raw)
+                .cast::<ShortStringN<N>>()
+                .write(short);
+        }
+        // SAFETY: both fields have just been initialised
+        let this = unsafe { this.assume_init() };
+        debug_check_layout!(this);
+        this
+    }
+
+    /// Constructs a long string in place, the allocator of `long` becomes the allocator of the
+    /// string
+    pub(crate) fn from_long(long: LongString<A>) -> Self {
+        let mut this = MaybeUninit::<Self>::uninit();
+        // SAFETY:
+        // - both types are repr(C) and start with an `A`, followed by a field with the alignment
+        //   of a pointer, so the allocator ends up in `alloc` and the rest in `raw`
+        // - raw is at least as big as a long string without its allocator
+        // - the first byte of the length of a long string is always a valid tag, and everything
+        //   after it is allowed to be uninitialised
+        let this = unsafe {
+            this.as_mut_ptr().cast::<LongString<A>>().write(long);
+            this.assume_init()
+        };
+        debug_check_layout!(this);
+        this
+    }
+
+    /// Constructs a string with at least `capacity` bytes of capacity, allocated with `alloc`.
+    /// Capacities up to `N` do not allocate.
+    pub fn with_capacity_in(capacity: usize, alloc: A) -> Self {
+        if capacity <= N {
+            Self::new_in(alloc)
+        } else {
+            Self::from_long(LongString::with_capacity_in(capacity, alloc))
+        }
+    }
+
+    /// Returns a reference to the allocator that backs this string
+    pub fn allocator(&self) -> &A {
+        &self.alloc
+    }
+
+    /// Moves this string into a string with an inline capacity of `M` bytes. A long string keeps
+    /// its buffer, so this only allocates if a short string does not fit in `M` bytes.
+    pub fn into_inline_capacity<const M: usize>(self) -> SsoStringN<M, A> {
+        let this = ManuallyDrop::new(self);
+        match this.tagged() {
+            TaggedSsoString64::Short(short) => {
+                // SAFETY: `this` is never dropped, so the allocator is only moved out once
+                let alloc = unsafe { ptr::read(&this.alloc) };
+                let mut other = SsoStringN::new_in(alloc);
+                other.push_str(short.as_str());
+                other
+            }
+            // SAFETY: `this` is never dropped, so the buffer and allocator are only moved out once
+            TaggedSsoString64::Long(long) => SsoStringN::from_long(unsafe { ptr::read(long) }),
+            TaggedSsoString64::Static(s) => {
+                // SAFETY: `this` is never dropped, so the allocator is only moved out once
+                let alloc = unsafe { ptr::read(&this.alloc) };
+                SsoStringN::from_static_string(*s, alloc)
+            }
+        }
+    }
+
+    /// Returns `true` if this string is a short string (no heap allocations), and `false` otherwise
+    pub const fn is_short(&self) -> bool {
+        // the tag is the first byte of every variant
+        (self.raw.tag as u8 & ShortStringN::<N>::FLAG) != 0
+    }
+
+    /// Returns `true` if this string owns a heap buffer. Every string is exactly one of short,
+    /// long or static.
+    pub const fn is_long(&self) -> bool {
+        !self.is_short() && !self.is_static()
+    }
+
+    /// Returns the underlying string as an enum, allowing you to access the underlying short,
+    /// long or static variant for the string
+    pub const fn tagged(&self) -> TaggedSsoString64<'_, N, A> {
+        if self.is_short() {
+            let short = (&self.raw as *const RawSsoStringN<N>).cast::<ShortStringN<N>>();
+            // SAFETY: a valid short string starts at raw
+            TaggedSsoString64::Short(unsafe { &*short })
+        } else if self.is_static() {
+            let s = (&self.raw as *const RawSsoStringN<N>).cast::<StaticString>();
+            // SAFETY: a valid static string starts at raw
+            TaggedSsoString64::Static(unsafe { &*s })
+        } else {
+            let long = (self as *const Self).cast::<LongString<A>>();
+            // SAFETY: a valid long string starts at self
+            TaggedSsoString64::Long(unsafe { &*long })
+        }
+    }
+
+    /// Same as [`SsoStringN::tagged`], but allows mutation of the underlying values instead. A
+    /// static string becomes owned first, so it is never returned.
+    pub fn tagged_mut(&mut self) -> TaggedSsoString64Mut<'_, N, A> {
+        self.make_owned();
+        if self.is_short() {
+            let short = (&mut self.raw as *mut RawSsoStringN<N>).cast::<ShortStringN<N>>();
+            // SAFETY: a valid short string starts at raw, and every value it can be changed to
+            // through `&mut` keeps the tag bit set
+            TaggedSsoString64Mut::Short(unsafe { &mut *short })
+        } else {
+            let long = (self as *mut Self).cast::<LongString<A>>();
+            // SAFETY: a valid long string starts at self, and every length it can be changed to
+            // through `&mut` keeps the tag bit clear
+            TaggedSsoString64Mut::Long(unsafe { &mut *long })
+        }
+    }
+
+    /// Upgrades a short string to a long string with at least `additional` bytes of remaining
+    /// capacity, moving the allocator over to the long string. Long strings are left untouched. If
+    /// the allocation fails, `self` is left untouched and an error is returned.
+    fn try_upgrade(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        let TaggedSsoString64Mut::Short(&mut short) = self.tagged_mut() else {
+            return Ok(());
+        };
+        let capacity = N
+            .checked_add(additional)
+            .ok_or_else(unified_alloc::capacity_overflow)?;
+        let alloc = &self.alloc;
+        let (buf, capacity) = LongString::try_new_buf(capacity, alloc)?;
+        // SAFETY:
+        // - src is the initialised part of the short string
+        // - dst is a new allocation of at least MAX_CAPACITY bytes, so it fits and cannot overlap
+        unsafe { ptr::copy_nonoverlapping(short.as_bytes().as_ptr(), buf.as_ptr(), short.len()) }
+        // SAFETY:
+        // - the allocator is moved out of self, which is immediately overwritten without being
+        //   dropped, nothing in between can panic
+        // - buf was allocated by alloc with exactly capacity bytes, and buf[0..len] was just
+        //   initialised with the valid utf-8 of the short string
+        unsafe {
+            let alloc = ptr::read(alloc);
+            let long = LongString::from_raw_parts_in(buf.data, short.len(), capacity, alloc);
+            ptr::write(self, SsoStringN::from_long(long));
+        }
+        Ok(())
+    }
+
+    /// The same as [`SsoStringN::try_upgrade`], but panics if the allocation fails
+    fn upgrade(&mut self, additional: usize) {
+        self.try_upgrade(additional)
+            .unwrap_or_else(|err| panic!("allocation error: {err}"))
+    }
+
+    /// Upgrades a short string to a long string if `additional` more bytes would not fit in it.
+    /// Long strings are left untouched.
+    fn upgrade_to_fit(&mut self, additional: usize) {
+        if let TaggedSsoString64Mut::Short(short) = self.tagged_mut() {
+            if additional > short.remaining_capacity() {
+                self.upgrade(additional);
+            }
+        }
+    }
+
+    /// Makes room for at least `additional` more bytes. Unlike [`Self::reserve`], a short string is
+    /// only upgraded if they do not fit in it, and a long string is only moved if they do not fit in
+    /// its buffer.
+    pub(crate) fn reserve_to_fit(&mut self, additional: usize) {
+        match self.tagged_mut() {
+            TaggedSsoString64Mut::Short(short) => {
+                if additional > short.remaining_capacity() {
+                    self.upgrade(additional);
+                }
+            }
+            TaggedSsoString64Mut::Long(long) => {
+                if additional > long.remaining_capacity() {
+                    long.realloc(additional);
+                }
+            }
+        }
+    }
+
+    /// Replaces the contents of this string with `short`, freeing the buffer of a long string. The
+    /// allocator stays where it is.
+    pub(crate) fn set_short(&mut self, short: ShortStringN<N>) {
+        if self.is_long() {
+            if let TaggedSsoString64Mut::Long(long) = self.tagged_mut() {
+                long.free();
+            }
+        }
+        // SAFETY: the buffer of a long string has just been freed, and other strings own nothing
+        unsafe { self.write_short(short) }
+    }
+
+    /// Returns a slice of bytes of this string's contents
+    pub const fn as_bytes(&self) -> &[u8] {
+        match self.tagged() {
+            TaggedSsoString64::Short(short) => short.as_bytes(),
+            TaggedSsoString64::Long(long) => long.as_bytes(),
+            TaggedSsoString64::Static(s) => s.as_bytes(),
         }
     }
 
-    todo_impl!(pub fn from_utf16(_v: &[u16]) -> Result<SsoString, FromUtf16Error>);
+    duck_impl! {
+        pub fn as_mut_str(&mut self) -> &mut str;
+    }
+
+    pub const fn as_str(&self) -> &str {
+        match self.tagged() {
+            TaggedSsoString64::Short(short) => short.as_str(),
+            TaggedSsoString64::Long(long) => long.as_str(),
+            TaggedSsoString64::Static(s) => s.as_str(),
+        }
+    }
 
-    todo_impl!(pub fn from_utf16_lossy(_v: &[u16]) -> SsoString);
+    duck_impl! {
+        /// Returns the number of bytes this string can hold without reallocating. A static string
+        /// has no room to spare, so this is its length.
+        pub fn capacity(&self) -> usize;
+    }
 
-    pub fn from_utf8(v: Vec<u8>) -> Result<SsoString, FromUtf8Error> {
-        std::string::String::from_utf8(v).map(|s| s.into())
+    /// Truncates this string to a length of 0, keeping its storage. A static string becomes an
+    /// empty short string instead of being copied.
+    pub fn clear(&mut self) {
+        if self.is_static() {
+            // SAFETY: a static string owns nothing
+            unsafe { self.write_short(ShortStringN::new()) };
+            return;
+        }
+        match self.tagged_mut() {
+            // SAFETY: 0 is always a valid value for len on both variants
+            TaggedSsoString64Mut::Short(short) => unsafe { short.set_len(0) },
+            TaggedSsoString64Mut::Long(long) => unsafe { long.set_len(0) },
+        }
     }
 
-    todo_impl!(pub fn from_utf8_lossy(_v: &[u8]) -> Cow<'_, SsoStr>);
+    /// Removes the specified range from the string in bulk, returning all removed characters as
+    /// an iterator. The gap is closed when the [`Drain`] is dropped, even if it was not fully
+    /// consumed. The storage of the string is never changed, so a long string stays long.
+    ///
+    /// Panics if the start or end of the range do not lie on a char boundary, or if they are out of
+    /// bounds.
+    pub fn drain<R>(&mut self, range: R) -> Drain<'_, N, A>
+    where
+        R: RangeBounds<usize>,
+    {
+        let ops::Range { start, end } = normalize_range(range, self.len());
+        assert!(self.is_char_boundary(start));
+        assert!(self.is_char_boundary(end));
 
-    todo_impl!(pub unsafe fn from_utf8_unchecked(_v: &[u8]) -> SsoString);
+        let string = NonNull::from(&mut *self);
+        // SAFETY:
+        // - start..end is in bounds and on char boundaries (checked above)
+        // - the `Drain` mutably borrows `self` for as long as `iter` lives, so the bytes cannot be
+        //   changed by anyone else. We only write to them through `string` on drop, after which
+        //   `iter` is never used again
+        let iter = unsafe { (*string.as_ptr()).get_unchecked(start..end) }.chars();
+        Drain {
+            string,
+            start,
+            end,
+            iter,
+            _marker: PhantomData,
+        }
+    }
 
-    todo_impl!(pub fn insert(&mut self, _idx: usize, _c: char));
+    /// Copies `self[src]` to the end of the string, upgrading to a long string if the result does
+    /// not fit in a short one.
+    ///
+    /// Panics if the start or end of the range do not lie on a char boundary, or if they are out of
+    /// bounds.
+    pub fn extend_from_within<R>(&mut self, src: R)
+    where
+        R: RangeBounds<usize>,
+    {
+        let src = normalize_range(src, self.len());
+        assert!(self.is_char_boundary(src.start));
+        assert!(self.is_char_boundary(src.end));
 
-    todo_impl!(pub fn insert_str(&mut self, _idx: usize, _s: &str));
+        match self.tagged_mut() {
+            TaggedSsoString64Mut::Short(short) => {
+                if src.len() <= short.remaining_capacity() {
+                    // SAFETY: src is in bounds and on char boundaries (checked above) and we just
+                    // did an exact bounds check on the remaining capacity
+                    unsafe { short.extend_from_within_unchecked(src) }
+                } else {
+                    self.upgrade(src.len());
+                    self.extend_from_within(src);
+                }
+            }
+            TaggedSsoString64Mut::Long(long) => long.extend_from_within(src),
+        }
+    }
 
-    todo_impl!(pub fn into_boxed_str(self) -> Box<str>);
+    /// Inserts a character into this string at byte position `idx`, upgrading to a long string if
+    /// it no longer fits in a short one.
+    ///
+    /// Panics if `idx` does not lie on a char boundary, or is out of bounds.
+    pub fn insert(&mut self, idx: usize, ch: char) {
+        let mut buf = [0; 4];
+        let utf8 = ch.encode_utf8(&mut buf);
+        self.insert_str(idx, utf8);
+    }
 
-    todo_impl!(pub fn leak<'a>(self) -> &'a mut str);
+    /// Inserts a string slice into this string at byte position `idx`, upgrading to a long string
+    /// if it no longer fits in a short one.
+    ///
+    /// Panics if `idx` does not lie on a char boundary, or is out of bounds.
+    pub fn insert_str(&mut self, idx: usize, string: &str) {
+        assert!(self.is_char_boundary(idx));
+        self.upgrade_to_fit(string.len());
+        match self.tagged_mut() {
+            TaggedSsoString64Mut::Short(short) => short.insert_str(idx, string),
+            TaggedSsoString64Mut::Long(long) => long.insert_str(idx, string),
+        }
+    }
 
-    duck_impl! {
-        pub fn len(&self) -> usize;
+    pub const fn len(&self) -> usize {
+        match self.tagged() {
+            TaggedSsoString64::Short(short) => short.len(),
+            TaggedSsoString64::Long(long) => long.len(),
+            TaggedSsoString64::Static(s) => s.len(),
+        }
     }
 
     pub fn push(&mut self, ch: char) {
@@ -859,9 +2407,8 @@ impl SsoString {
                         short.push_str_unchecked(s);
                     }
                 } else {
-                    let mut long = ManuallyDrop::new(short.into_long(s.len()));
-                    long.push_str(s);
-                    *self = SsoString { long };
+                    self.upgrade(s.len());
+                    self.push_str(s);
                 }
             }
             TaggedSsoString64Mut::Long(long) => {
@@ -870,20 +2417,62 @@ impl SsoString {
         }
     }
 
-    todo_impl!(pub fn remove(&mut self, _idx: usize) -> char);
+    duck_impl! {
+        /// Removes a `char` from this string at byte position `idx` and returns it. The storage of
+        /// the string is never changed.
+        ///
+        /// Panics if `idx` does not lie on a char boundary, or is larger than or equal to the
+        /// length.
+        pub fn remove(&mut self, idx: usize) -> char;
+    }
 
-    todo_impl!(
-        pub fn replace_range<R>(&mut self, _range: R, _replace_with: &str)
-        where
-            R: RangeBounds<usize>,
-    );
+    /// Removes the specified range in the string, and replaces it with the given string. The
+    /// string moves between short and long storage if the new length crosses `N`.
+    ///
+    /// Panics if the start or end of the range do not lie on a char boundary, or if they are out of
+    /// bounds.
+    pub fn replace_range<R>(&mut self, range: R, replace_with: &str)
+    where
+        R: RangeBounds<usize>,
+    {
+        let range = normalize_range(range, self.len());
+        assert!(self.is_char_boundary(range.start));
+        assert!(self.is_char_boundary(range.end));
 
-    pub fn reserve(&mut self, additional: usize) {
+        let new_len = self.len() - range.len() + replace_with.len();
         match self.tagged_mut() {
             TaggedSsoString64Mut::Short(short) => {
-                let long = ManuallyDrop::new(short.into_long(additional));
-                *self = SsoString { long };
+                if new_len <= N {
+                    // SAFETY: range is in bounds and on char boundaries (checked above) and the
+                    // new length fits in a short string
+                    unsafe { short.replace_range_unchecked(range, replace_with) }
+                } else {
+                    self.upgrade(replace_with.len());
+                    self.replace_range(range, replace_with);
+                }
+            }
+            TaggedSsoString64Mut::Long(long) => {
+                if new_len <= N {
+                    let old = long.as_str();
+                    let mut short = ShortStringN::new();
+                    // SAFETY: the three parts add up to new_len, which fits in a short string, and
+                    // each of them is a str, since range lies on char boundaries
+                    unsafe {
+                        short.push_str_unchecked(&old[..range.start]);
+                        short.push_str_unchecked(replace_with);
+                        short.push_str_unchecked(&old[range.end..]);
+                    }
+                    self.set_short(short);
+                } else {
+                    long.replace_range(range, replace_with);
+                }
             }
+        }
+    }
+
+    pub fn reserve(&mut self, additional: usize) {
+        match self.tagged_mut() {
+            TaggedSsoString64Mut::Short(_) => self.upgrade(additional),
             TaggedSsoString64Mut::Long(long) => {
                 long.realloc(additional);
             }
@@ -896,7 +2485,7 @@ impl SsoString {
 
     duck_impl! {
         pub fn pop(&mut self as duck) -> Option<char> {
-            let ch = duck.as_str().chars().rev().next()?;
+            let ch = duck.as_str().chars().next_back()?;
             // SAFETY: will always still be valid utf8, as we are 'removing' a correctly sized utf8
             // byte sequence from the end of this string. For added assurance that this is safe,
             // this is basically exactly the same code as the std library impementation.
@@ -911,99 +2500,145 @@ impl SsoString {
     /// extra, just because of the implementation of `Global`.
     pub fn reserve_exact(&mut self, additional: usize) {
         match self.tagged_mut() {
-            TaggedSsoString64Mut::Short(short) => {
-                let long = ManuallyDrop::new(short.into_long(additional));
-                *self = SsoString { long };
-            }
-            TaggedSsoString64Mut::Long(old) => {
-                let long = ManuallyDrop::new(old.clone_with_additional_capacity(additional));
-                old.free();
-                *self = SsoString { long };
-            }
+            TaggedSsoString64Mut::Short(_) => self.upgrade(additional),
+            TaggedSsoString64Mut::Long(long) => long
+                .try_reserve_exact(additional)
+                .unwrap_or_else(|err| panic!("allocation error: {err}")),
         }
     }
 
-    /// Retains only the characters specified by the predicate.
-    ///
-    /// # TODO
-    ///
-    /// This is a bad implementation of a simple function, it creates an entirely new string, it
-    /// doesn't require `&mut`. The better version would be easier to implement with `as_mut_str`.
-    ///
-    /// I don't think I'm going to bother with this any time soon.
+    /// Retains only the characters specified by the predicate. The string is compacted in place,
+    /// so its storage never changes. If the predicate panics, only the characters that were
+    /// already kept are left, like `std::string::String::retain`.
     pub fn retain<F>(&mut self, mut f: F)
     where
         F: FnMut(char) -> bool,
     {
-        macro_rules! duck_body {
-            ($duck:ident, $f:ident) => {
-                for ch in self.chars() {
-                    if $f(ch) {
-                        $duck.push(ch)
-                    }
-                }
-            };
-        }
+        self.retain_mut(|ch| f(*ch))
+    }
 
-        let mut result = SsoString::with_capacity(self.capacity());
-        match result.tagged_mut() {
-            TaggedSsoString64Mut::Long(long) => {
-                duck_body!(long, f)
+    /// Retains only the characters specified by the predicate, which may also change them. A
+    /// changed character that is longer than the space freed up so far moves the rest of the
+    /// string back, which may grow it, or upgrade a short string.
+    ///
+    /// Unlike [`retain`](Self::retain) and [`retain_ascii`](Self::retain_ascii), this is only
+    /// done in place as long as no character grows by more than the space freed up before it. If
+    /// one does, the string may reallocate, so pointers into its buffer must not be held across
+    /// a call.
+    pub fn retain_mut<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&mut char) -> bool,
+    {
+        let mut len = self.len();
+        let mut guard = SetLenOnDrop {
+            s: self,
+            idx: 0,
+            del_bytes: 0,
+        };
+        while guard.idx < len {
+            // SAFETY: bytes[idx..len] have not been touched yet, so they are valid utf-8 that
+            // starts on a char boundary
+            let old = unsafe {
+                let rest = &guard.s.as_mut_bytes()[guard.idx..len];
+                std::str::from_utf8_unchecked(rest).chars().next()
             }
-            TaggedSsoString64Mut::Short(short) => {
-                duck_body!(short, f)
+            .expect("idx < len, so there is a char left");
+            let mut ch = old;
+            if !f(&mut ch) {
+                guard.del_bytes += old.len_utf8();
+                guard.idx += old.len_utf8();
+                continue;
+            }
+
+            let write = guard.idx - guard.del_bytes;
+            if ch.len_utf8() <= guard.del_bytes + old.len_utf8() {
+                if guard.del_bytes > 0 || ch != old {
+                    // SAFETY: the char is written over bytes that have already been processed
+                    let bytes = unsafe { guard.s.as_mut_bytes() };
+                    ch.encode_utf8(&mut bytes[write..]);
+                }
+                guard.del_bytes = guard.del_bytes + old.len_utf8() - ch.len_utf8();
+                guard.idx += old.len_utf8();
+            } else {
+                // close the gap, so that the string is valid again, and make room for the char
+                let tail = guard.idx + old.len_utf8();
+                // SAFETY: the tail is moved right after the kept chars, which are followed by a
+                // char boundary
+                unsafe { guard.s.as_mut_bytes() }.copy_within(tail..len, write);
+                len -= tail - write;
+                guard.idx = write;
+                guard.del_bytes = 0;
+                // SAFETY: bytes[0..len] are the kept chars followed by the untouched ones
+                unsafe { guard.s.set_len(len) };
+                guard.s.insert(write, ch);
+                len += ch.len_utf8();
+                guard.idx += ch.len_utf8();
             }
         }
-        *self = result;
     }
 
-    pub fn with_capacity(capacity: usize) -> Self {
-        if capacity <= ShortString64::MAX_CAPACITY {
-            Self {
-                short: ManuallyDrop::new(ShortString64::new()),
-            }
-        } else {
-            Self {
-                long: ManuallyDrop::new(LongString::with_capacity(capacity)),
+    /// Retains only the ASCII bytes specified by the predicate, non-ASCII characters are always
+    /// kept. This works on bytes without decoding any chars, and can never split a char, since no
+    /// byte of a non-ASCII char is ASCII.
+    pub fn retain_ascii<F>(&mut self, mut f: F)
+    where
+        F: FnMut(u8) -> bool,
+    {
+        let len = self.len();
+        let mut guard = SetLenOnDrop {
+            s: self,
+            idx: 0,
+            del_bytes: 0,
+        };
+        while guard.idx < len {
+            // SAFETY: only whole chars are removed, and the rest is moved to the front, so the
+            // bytes are valid utf-8 again once the guard sets the length
+            let bytes = unsafe { guard.s.as_mut_bytes() };
+            let byte = bytes[guard.idx];
+            if byte.is_ascii() && !f(byte) {
+                guard.del_bytes += 1;
+            } else if guard.del_bytes > 0 {
+                bytes[guard.idx - guard.del_bytes] = byte;
             }
+            guard.idx += 1;
         }
     }
 
+    /// Returns the initialised bytes of this string, which may be changed to anything
+    ///
+    /// # Safety
+    /// - the bytes must be valid utf-8 again before this string is used as a `str`
+    unsafe fn as_mut_bytes(&mut self) -> &mut [u8] {
+        // SAFETY: caller contract
+        unsafe { self.as_mut_str().as_bytes_mut() }
+    }
+
+    /// Shrinks the capacity of this string to at least `min_capacity` and its length, which makes
+    /// it short if that fits. Does nothing if `min_capacity` is not smaller than the current
+    /// capacity, like `std::string::String::shrink_to`. A static string has no capacity to spare,
+    /// so it is left alone.
     pub fn shrink_to(&mut self, min_capacity: usize) {
+        if self.is_static() || min_capacity >= self.capacity() {
+            return;
+        }
         match self.tagged_mut() {
             TaggedSsoString64Mut::Long(old) => {
                 let min_capacity = cmp::max(min_capacity, old.len());
-                if min_capacity <= ShortString64::MAX_CAPACITY {
-                    let mut short = ShortString64::new();
+                if min_capacity <= N {
+                    let mut short = ShortStringN::new();
                     // SAFETY:
-                    // 1. short is empty, therefore remaining_capacity == MAX_CAPACITY
-                    // 2. old.len() <= min_capacity is true (cmp::max)
-                    // 3. min_capacity <= MAX_CAPACITY is true (if statement)
-                    // - therefore old.len() <= short.remaining_capacity() because of the following
-                    //   derivation:
-                    // -> simplifies... old.len() <= MAX_CAPACITY (1)
-                    // -> given... old.len() <= min_capacity <= MAX_CAPACITY (2, 3)
-                    // -> old.len() <= MAX_CAPACITY == true
+                    // - old.len() <= min_capacity <= N, so the bytes fit in the empty short string
+                    // - the bytes are copied without being read as a `str`, so this is also valid
+                    //   for the bytes of an `SsoBytesN`, which do not have to be utf-8
                     unsafe {
-                        short.push_str_unchecked(old.as_str());
+                        let src = old.buf().as_ptr();
+                        ptr::copy_nonoverlapping(src, short.next_ptr().as_ptr(), old.len());
+                        short.set_len(old.len());
                     }
-                    old.free();
-                    *self = SsoString {
-                        short: ManuallyDrop::new(short),
-                    };
+                    self.set_short(short);
                 } else {
-                    let mut long = LongString::with_capacity(min_capacity);
-                    // SAFETY:
-                    // - min_capacity >= old.len(), therefore old.len() <= min_capacity
-                    // - we have allocated at least min_capacity for long, which is empty
-                    // - therefore old.as_str() can be pushed
-                    unsafe {
-                        long.push_str_unchecked(old.as_str());
-                    }
-                    old.free();
-                    *self = SsoString {
-                        long: ManuallyDrop::new(long),
-                    };
+                    // min_capacity >= old.len(), so the contents always fit in the new buffer
+                    old.realloc_exact(min_capacity);
                 }
             }
             TaggedSsoString64Mut::Short(..) => {
@@ -1021,36 +2656,170 @@ impl SsoString {
         self.shrink_to(self.len());
     }
 
-    todo_impl!(pub fn split_off(&mut self, _at: usize) -> String);
+    /// Splits the string into two at the given byte index, returning everything after it. The
+    /// returned string is short whenever the tail fits in `N` bytes.
+    /// The storage of `self` is never changed.
+    ///
+    /// Panics if `at` does not lie on a char boundary, or is out of bounds.
+    pub fn split_off(&mut self, at: usize) -> Self
+    where
+        A: Clone,
+    {
+        let alloc = self.allocator().clone();
+        match self.tagged_mut() {
+            TaggedSsoString64Mut::Short(short) => Self::from_short(short.split_off(at), alloc),
+            TaggedSsoString64Mut::Long(long) => {
+                if long.len().saturating_sub(at) <= N {
+                    assert!(long.as_str().is_char_boundary(at));
+                    let mut other = Self::new_in(alloc);
+                    other.push_str(&long.as_str()[at..]);
+                    long.truncate(at);
+                    other
+                } else {
+                    Self::from_long(long.split_off(at))
+                }
+            }
+        }
+    }
 
-    todo_impl!(pub fn truncate(&mut self, _new_len: usize));
+    /// Shortens this string to `new_len` bytes. Does nothing if `new_len` is greater than the
+    /// current length. A long string is never demoted to a short one, use
+    /// [`SsoStringN::shrink_to_fit`] afterwards if that is what you want. A static string stays
+    /// static, it just borrows less.
+    ///
+    /// Panics if `new_len` does not lie on a char boundary.
+    pub fn truncate(&mut self, new_len: usize) {
+        if self.is_static() {
+            let s = (&mut self.raw as *mut RawSsoStringN<N>).cast::<StaticString>();
+            // SAFETY: a valid static string starts at raw, and truncating it keeps its tag
+            unsafe { (*s).truncate(new_len) }
+            return;
+        }
+        match self.tagged_mut() {
+            TaggedSsoString64Mut::Short(short) => short.truncate(new_len),
+            TaggedSsoString64Mut::Long(long) => long.truncate(new_len),
+        }
+    }
 
-    todo_impl!(pub fn try_reserve(&mut self, _additional: usize) -> Result<(), TryReserveError>);
+    /// The same as [`SsoStringN::reserve`], but returns an error instead of panicking if the
+    /// allocation fails. The string is left untouched on failure.
+    pub fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        match self.tagged_mut() {
+            TaggedSsoString64Mut::Short(_) => self.try_upgrade(additional),
+            TaggedSsoString64Mut::Long(long) => long.try_reserve(additional),
+        }
+    }
 
-    todo_impl! {
-        pub fn try_reserve_exact(&mut self, _additional: usize) -> Result<(), TryReserveError>;
+    /// The same as [`SsoStringN::reserve_exact`], but returns an error instead of panicking if the
+    /// allocation fails. The string is left untouched on failure.
+    pub fn try_reserve_exact(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        match self.tagged_mut() {
+            TaggedSsoString64Mut::Short(_) => self.try_upgrade(additional),
+            TaggedSsoString64Mut::Long(long) => long.try_reserve_exact(additional),
+        }
     }
 }
 
-impl PartialEq<SsoString> for SsoString {
-    fn eq(&self, other: &SsoString) -> bool {
+impl<const N: usize, const M: usize, A: Allocator, B: Allocator> PartialEq<SsoStringN<M, B>>
+    for SsoStringN<N, A>
+{
+    fn eq(&self, other: &SsoStringN<M, B>) -> bool {
         self.as_str() == other.as_str()
     }
 }
 
-impl PartialEq<str> for SsoString {
-    fn eq(&self, other: &str) -> bool {
-        self.as_str() == other
+impl<const N: usize, A: Allocator> Eq for SsoStringN<N, A> {}
+
+impl<const N: usize, const M: usize, A: Allocator, B: Allocator> PartialOrd<SsoStringN<M, B>>
+    for SsoStringN<N, A>
+{
+    fn partial_cmp(&self, other: &SsoStringN<M, B>) -> Option<cmp::Ordering> {
+        self.as_str().partial_cmp(other.as_str())
+    }
+}
+
+impl<const N: usize, A: Allocator> Ord for SsoStringN<N, A> {
+    fn cmp(&self, other: &Self) -> cmp::Ordering {
+        self.as_str().cmp(other.as_str())
+    }
+}
+
+/// implements `PartialEq` and `PartialOrd` in both directions between a [`SsoStringN`] and a type
+/// that can be sliced into a `str`
+macro_rules! impl_cmp_str {
+    ([$($lt:lifetime)?] $Other:ty) => {
+        impl<$($lt,)? const N: usize, A: Allocator> PartialEq<$Other> for SsoStringN<N, A> {
+            fn eq(&self, other: &$Other) -> bool {
+                self.as_str() == &other[..]
+            }
+        }
+
+        impl<$($lt,)? const N: usize, A: Allocator> PartialEq<SsoStringN<N, A>> for $Other {
+            fn eq(&self, other: &SsoStringN<N, A>) -> bool {
+                &self[..] == other.as_str()
+            }
+        }
+
+        impl<$($lt,)? const N: usize, A: Allocator> PartialOrd<$Other> for SsoStringN<N, A> {
+            fn partial_cmp(&self, other: &$Other) -> Option<cmp::Ordering> {
+                self.as_str().partial_cmp(&other[..])
+            }
+        }
+
+        impl<$($lt,)? const N: usize, A: Allocator> PartialOrd<SsoStringN<N, A>> for $Other {
+            fn partial_cmp(&self, other: &SsoStringN<N, A>) -> Option<cmp::Ordering> {
+                self[..].partial_cmp(other.as_str())
+            }
+        }
+    };
+}
+
+impl_cmp_str!([] str);
+impl_cmp_str!(['a] &'a str);
+impl_cmp_str!([] String);
+impl_cmp_str!(['a] Cow<'a, str>);
+
+impl<const N: usize, A: Allocator> Hash for SsoStringN<N, A> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // exactly the same as `str`, so that lookups through `Borrow<str>` work
+        self.as_str().hash(state)
+    }
+}
+
+impl<const N: usize, A: Allocator + Clone> Clone for SsoStringN<N, A> {
+    /// The clone is only as big as it has to be, so a long string that fits in `N` bytes becomes
+    /// short. A static string stays static.
+    fn clone(&self) -> Self {
+        if let TaggedSsoString64::Static(s) = self.tagged() {
+            return Self::from_static_string(*s, self.allocator().clone());
+        }
+        let mut other = Self::with_capacity_in(self.len(), self.allocator().clone());
+        other.push_str(self);
+        other
+    }
+
+    /// Reuses the buffer of `self` if it is long enough, the allocator of `self` is kept.
+    fn clone_from(&mut self, source: &Self) {
+        self.clear();
+        self.push_str(source);
+    }
+}
+
+impl<const N: usize, A: Allocator + Default> Default for SsoStringN<N, A> {
+    fn default() -> Self {
+        Self::new_in(A::default())
     }
 }
 
-impl PartialEq<SsoString> for str {
-    fn eq(&self, other: &SsoString) -> bool {
-        self == other.as_str()
+impl<const N: usize> FromStr for SsoStringN<N> {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self::from(s))
     }
 }
 
-impl Deref for SsoString {
+impl<const N: usize, A: Allocator> Deref for SsoStringN<N, A> {
     type Target = str;
 
     fn deref(&self) -> &Self::Target {
@@ -1058,31 +2827,155 @@ impl Deref for SsoString {
     }
 }
 
-impl fmt::Display for SsoString {
+impl<const N: usize, A: Allocator> DerefMut for SsoStringN<N, A> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.as_mut_str()
+    }
+}
+
+impl<const N: usize, A: Allocator, I: SliceIndex<str>> ops::Index<I> for SsoStringN<N, A> {
+    type Output = I::Output;
+
+    fn index(&self, index: I) -> &Self::Output {
+        &self.as_str()[index]
+    }
+}
+
+impl<const N: usize, A: Allocator, I: SliceIndex<str>> ops::IndexMut<I> for SsoStringN<N, A> {
+    fn index_mut(&mut self, index: I) -> &mut Self::Output {
+        &mut self.as_mut_str()[index]
+    }
+}
+
+impl<const N: usize, A: Allocator> AsRef<str> for SsoStringN<N, A> {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl<const N: usize, A: Allocator> AsMut<str> for SsoStringN<N, A> {
+    fn as_mut(&mut self) -> &mut str {
+        self.as_mut_str()
+    }
+}
+
+impl<const N: usize, A: Allocator> AsRef<[u8]> for SsoStringN<N, A> {
+    fn as_ref(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+impl<const N: usize, A: Allocator> AsRef<OsStr> for SsoStringN<N, A> {
+    fn as_ref(&self) -> &OsStr {
+        self.as_str().as_ref()
+    }
+}
+
+impl<const N: usize, A: Allocator> AsRef<Path> for SsoStringN<N, A> {
+    fn as_ref(&self) -> &Path {
+        self.as_str().as_ref()
+    }
+}
+
+impl<const N: usize, A: Allocator> fmt::Display for SsoStringN<N, A> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self.tagged() {
             TaggedSsoString64::Short(short) => write!(f, "{}", short),
             TaggedSsoString64::Long(long) => write!(f, "{}", long),
+            TaggedSsoString64::Static(s) => write!(f, "{}", s),
         }
     }
 }
 
-impl fmt::Debug for SsoString {
+impl<const N: usize, A: Allocator> fmt::Debug for SsoStringN<N, A> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self.tagged() {
             TaggedSsoString64::Short(short) => write!(f, "{:?}", short),
             TaggedSsoString64::Long(long) => write!(f, "{:?}", long),
+            TaggedSsoString64::Static(s) => write!(f, "{:?}", s),
         }
     }
 }
 
-impl ops::AddAssign<&str> for SsoString {
+impl<const N: usize, A: Allocator> fmt::Write for SsoStringN<N, A> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.push_str(s);
+        Ok(())
+    }
+
+    fn write_char(&mut self, c: char) -> fmt::Result {
+        self.push(c);
+        Ok(())
+    }
+}
+
+impl<const N: usize, A: Allocator> Extend<char> for SsoStringN<N, A> {
+    fn extend<I: IntoIterator<Item = char>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        // every char is at least one byte, so this decides between short and long storage once,
+        // instead of upgrading halfway through
+        self.reserve_to_fit(iter.size_hint().0);
+        iter.for_each(|ch| self.push(ch));
+    }
+}
+
+impl<'a, const N: usize, A: Allocator> Extend<&'a char> for SsoStringN<N, A> {
+    fn extend<I: IntoIterator<Item = &'a char>>(&mut self, iter: I) {
+        self.extend(iter.into_iter().copied())
+    }
+}
+
+impl<const N: usize> FromIterator<char> for SsoStringN<N> {
+    fn from_iter<I: IntoIterator<Item = char>>(iter: I) -> Self {
+        let mut s = Self::new();
+        s.extend(iter);
+        s
+    }
+}
+
+impl<'a, const N: usize> FromIterator<&'a char> for SsoStringN<N> {
+    fn from_iter<I: IntoIterator<Item = &'a char>>(iter: I) -> Self {
+        let mut s = Self::new();
+        s.extend(iter);
+        s
+    }
+}
+
+/// implements `Extend` and `FromIterator` for a [`SsoStringN`] with items that deref to `str`.
+/// `generics` are any extra generic parameters of the item type, with a trailing comma.
+macro_rules! impl_extend_str {
+    ([$($generics:tt)*] $Item:ty) => {
+        impl<$($generics)* const N: usize, A: Allocator> Extend<$Item> for SsoStringN<N, A> {
+            fn extend<I: IntoIterator<Item = $Item>>(&mut self, iter: I) {
+                // the size hint counts items, which may all be empty, so it says nothing about
+                // whether we stay short. `push_str` upgrades as soon as we don't.
+                iter.into_iter().for_each(|s| self.push_str(&s));
+            }
+        }
+
+        impl<$($generics)* const N: usize> FromIterator<$Item> for SsoStringN<N> {
+            fn from_iter<I: IntoIterator<Item = $Item>>(iter: I) -> Self {
+                let mut s = Self::new();
+                s.extend(iter);
+                s
+            }
+        }
+    };
+}
+
+impl_extend_str!(['a,] &'a str);
+impl_extend_str!([] String);
+impl_extend_str!([] Box<str>);
+impl_extend_str!(['a,] Cow<'a, str>);
+impl_extend_str!([const M: usize, B: Allocator,] SsoStringN<M, B>);
+
+impl<const N: usize, A: Allocator> ops::AddAssign<&str> for SsoStringN<N, A> {
     fn add_assign(&mut self, rhs: &str) {
         self.push_str(rhs);
     }
 }
 
-impl ops::Add<&str> for SsoString {
+impl<const N: usize, A: Allocator> ops::Add<&str> for SsoStringN<N, A> {
     type Output = Self;
 
     fn add(mut self, rhs: &str) -> Self::Output {
@@ -1091,16 +2984,129 @@ impl ops::Add<&str> for SsoString {
     }
 }
 
-impl Borrow<SsoStr> for SsoString {
+impl<const N: usize, A: Allocator> Borrow<SsoStr> for SsoStringN<N, A> {
     fn borrow(&self) -> &SsoStr {
         // SAFETY: transmute from &T to #[repr(transparent)] &Wrapper(T)
         unsafe { mem::transmute(self.as_str()) }
     }
 }
 
+impl<const N: usize, A: Allocator> Borrow<str> for SsoStringN<N, A> {
+    fn borrow(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl<const N: usize, A: Allocator> BorrowMut<str> for SsoStringN<N, A> {
+    fn borrow_mut(&mut self) -> &mut str {
+        self.as_mut_str()
+    }
+}
+
+/// A draining iterator for [`SsoString`], created with [`SsoStringN::drain`]. Closes the gap in the
+/// string when dropped.
+pub struct Drain<'a, const N: usize = { ShortString::MAX_CAPACITY }, A: Allocator = Global> {
+    /// # Safety
+    /// - must point to the `SsoString` that is mutably borrowed for `'a`
+    string: NonNull<SsoStringN<N, A>>,
+    /// start of the drained range, always on a char boundary
+    start: usize,
+    /// end of the drained range, always on a char boundary
+    end: usize,
+    /// the remaining characters of `string[start..end]`
+    iter: Chars<'a>,
+    _marker: PhantomData<&'a mut SsoStringN<N, A>>,
+}
+
+impl<const N: usize, A: Allocator> Drain<'_, N, A> {
+    /// Returns the remaining (sub)string of this iterator as a slice.
+    pub fn as_str(&self) -> &str {
+        self.iter.as_str()
+    }
+}
+
+impl<const N: usize, A: Allocator> fmt::Debug for Drain<'_, N, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Drain").field(&self.as_str()).finish()
+    }
+}
+
+impl<const N: usize, A: Allocator> AsRef<str> for Drain<'_, N, A> {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl<const N: usize, A: Allocator> Drop for Drain<'_, N, A> {
+    fn drop(&mut self) {
+        // SAFETY: `string` is mutably borrowed for the lifetime of this `Drain` and `iter` is
+        // never used after this point
+        let string = unsafe { self.string.as_mut() };
+        let range = self.start..self.end;
+        match string.tagged_mut() {
+            // SAFETY: range was checked to be in bounds and on char boundaries when creating this
+            // `Drain`, and nobody else could have changed `string` since
+            TaggedSsoString64Mut::Short(short) => unsafe { short.remove_range_unchecked(range) },
+            TaggedSsoString64Mut::Long(long) => unsafe { long.remove_range_unchecked(range) },
+        }
+    }
+}
+
+impl<const N: usize, A: Allocator> Iterator for Drain<'_, N, A> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        self.iter.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+
+    fn last(mut self) -> Option<char> {
+        self.next_back()
+    }
+}
+
+impl<const N: usize, A: Allocator> DoubleEndedIterator for Drain<'_, N, A> {
+    fn next_back(&mut self) -> Option<char> {
+        self.iter.next_back()
+    }
+}
+
+impl<const N: usize, A: Allocator> FusedIterator for Drain<'_, N, A> {}
+
+/// Creates a short [`String`](crate::String) from a string that is known at compile time, and
+/// fails to compile if it does not fit inline (23 bytes, or 11 on 32-bit targets). This can be
+/// used to initialise a `static`.
+///
+/// ```
+/// static NAME: olis_string::String = olis_string::short_string!("Gregory");
+/// assert!(NAME.is_short());
+/// ```
+///
+/// ```compile_fail
+/// let s = olis_string::short_string!("a string that is too long to be short");
+/// ```
+#[macro_export]
+macro_rules! short_string {
+    ($s:expr) => {
+        const {
+            // `expect` instead of a match, a string cannot be dropped in a const context
+            <$crate::String>::from_short_str($s).expect("the string does not fit in a short string")
+        }
+    };
+}
+
+/// Creates a [`String`](crate::String) using interpolation of runtime expressions, like
+/// `std::format!`. The output is written straight into the string, which only moves to the heap
+/// once it outgrows the inline buffer.
 #[macro_export]
 macro_rules! format {
-    ($($arg:tt)*) => {
-        SsoString::from(format!($($arg)*))
-    }
+    ($($arg:tt)*) => {{
+        let mut s = <$crate::String>::new();
+        ::std::fmt::Write::write_fmt(&mut s, ::std::format_args!($($arg)*))
+            .expect("a formatting trait implementation returned an error");
+        s
+    }}
 }
@@ -0,0 +1,801 @@
+//! A small vector that stores up to `N` elements inline, and spills to a `RawBuf` when it grows
+//! any larger. Unlike [`SsoStringN`](crate::StringN), there is no tag bit to borrow from an
+//! arbitrary `T`, so the capacity is stored next to the elements instead.
+
+use std::{
+    borrow::{Borrow, BorrowMut},
+    cmp,
+    collections::TryReserveError,
+    fmt,
+    hash::{Hash, Hasher},
+    iter::FusedIterator,
+    mem::{self, ManuallyDrop, MaybeUninit},
+    ops::{Deref, DerefMut},
+    ptr::{self, NonNull},
+    slice,
+};
+
+use crate::{
+    sso_string::RawBuf,
+    unified_alloc::{self, Allocator, Global},
+    unsafe_field::{UnsafeAssign, UnsafeField},
+};
+
+/// The heap variant of a [`SsoVec`]
+struct HeapVec<T, A> {
+    buf: RawBuf<T, A>,
+    len: usize,
+}
+
+/// The elements of a [`SsoVec`], the capacity of the vector says which variant is active
+union SsoVecData<T, const N: usize, A> {
+    inline: ManuallyDrop<[MaybeUninit<T>; N]>,
+    heap: ManuallyDrop<HeapVec<T, A>>,
+}
+
+/// A vector that stores up to `N` elements inline, and moves them to the heap when it grows any
+/// larger. A vector that shrinks back to `N` elements or fewer only moves them back inline in
+/// [`SsoVec::shrink_to_fit`] and friends, like [`SsoStringN`](crate::StringN).
+///
+/// Zero-sized elements are never allocated, so a vector of them is inline until it holds more than
+/// `N` of them, and has a capacity of `usize::MAX` after that.
+pub struct SsoVec<T, const N: usize, A: Allocator = Global> {
+    /// # Safety
+    /// - if `capacity <= N`, the elements are inline, and `capacity` is their count
+    /// - otherwise, the elements are on the heap, and `capacity` is the exact number of elements
+    ///   that fit in `data.heap.buf`. For zero-sized elements, it is `usize::MAX` and the buffer is
+    ///   dangling.
+    capacity: UnsafeField<usize, 0>,
+    /// # Safety
+    /// - inline: `data.inline[0..capacity]` is initialised
+    /// - heap: `data.heap.buf` was allocated by `alloc`, `data.heap.len <= capacity`, and
+    ///   `data.heap.buf[0..len]` is initialised
+    data: UnsafeField<SsoVecData<T, N, A>, 1>,
+    alloc: A,
+}
+
+// SAFETY: the vector owns its elements, and the buffer is only shared through `&self`
+unsafe impl<T: Send, const N: usize, A: Allocator + Send> Send for SsoVec<T, N, A> {}
+// SAFETY: the vector owns its elements, and the buffer is only shared through `&self`
+unsafe impl<T: Sync, const N: usize, A: Allocator + Sync> Sync for SsoVec<T, N, A> {}
+
+impl<T, const N: usize> SsoVec<T, N> {
+    /// Constructs an empty (inline) vector
+    pub const fn new() -> Self {
+        Self::new_in(Global)
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::with_capacity_in(capacity, Global)
+    }
+}
+
+impl<T, const N: usize, A: Allocator> SsoVec<T, N, A> {
+    /// Constructs an empty (inline) vector that will use `alloc` if it ever has to allocate
+    pub const fn new_in(alloc: A) -> Self {
+        Self {
+            // SAFETY: no elements are inline
+            capacity: unsafe { UnsafeField::new(0) },
+            // SAFETY: an empty inline array has nothing to initialise
+            data: unsafe {
+                UnsafeField::new(SsoVecData {
+                    inline: ManuallyDrop::new([const { MaybeUninit::uninit() }; N]),
+                })
+            },
+            alloc,
+        }
+    }
+
+    /// Constructs a vector with room for at least `capacity` elements, allocated with `alloc`.
+    /// Capacities up to `N` do not allocate.
+    pub fn with_capacity_in(capacity: usize, alloc: A) -> Self {
+        let mut vec = Self::new_in(alloc);
+        vec.reserve_exact(capacity);
+        vec
+    }
+
+    /// Returns a reference to the allocator that backs this vector
+    pub fn allocator(&self) -> &A {
+        &self.alloc
+    }
+
+    /// Returns `true` if the elements are stored inline (no heap allocations), and `false`
+    /// otherwise
+    pub fn is_short(&self) -> bool {
+        *self.capacity.get() <= N
+    }
+
+    /// Returns `!self.is_short()`
+    pub fn is_long(&self) -> bool {
+        !self.is_short()
+    }
+
+    pub fn len(&self) -> usize {
+        if self.is_short() {
+            *self.capacity.get()
+        } else {
+            // SAFETY: the heap variant is active
+            unsafe { self.data.get().heap.len }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of elements this vector can hold without reallocating
+    pub fn capacity(&self) -> usize {
+        if self.is_short() {
+            N
+        } else {
+            *self.capacity.get()
+        }
+    }
+
+    pub fn as_ptr(&self) -> *const T {
+        if self.is_short() {
+            // SAFETY: the inline variant is active
+            unsafe { self.data.get().inline.as_ptr().cast() }
+        } else {
+            // SAFETY: the heap variant is active
+            unsafe { self.data.get().heap.buf.as_ptr() }
+        }
+    }
+
+    /// Returns a pointer to the start of the buffer, which is valid for writes of
+    /// `self.capacity()` elements until the vector is moved or reallocated
+    pub fn as_mut_ptr(&mut self) -> *mut T {
+        if self.is_short() {
+            let data = self.data.get_mut().as_ptr();
+            // SAFETY: the inline variant is active, and the pointer is derived from `&mut self`
+            unsafe { ptr::addr_of_mut!((*data).inline).cast() }
+        } else {
+            // SAFETY: the heap variant is active
+            unsafe { self.data.get().heap.buf.as_ptr() }
+        }
+    }
+
+    pub fn as_slice(&self) -> &[T] {
+        // SAFETY: the first `len` elements are initialised (field contract)
+        unsafe { slice::from_raw_parts(self.as_ptr(), self.len()) }
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        let len = self.len();
+        // SAFETY: the first `len` elements are initialised (field contract)
+        unsafe { slice::from_raw_parts_mut(self.as_mut_ptr(), len) }
+    }
+
+    /// Sets the length of this vector, like `Vec::set_len`
+    ///
+    /// # Safety
+    /// - `len` must be less than or equal to `self.capacity()`
+    /// - the first `len` elements must be initialised
+    pub unsafe fn set_len(&mut self, len: usize) {
+        if self.is_short() {
+            // SAFETY: len <= N, and the elements are initialised (caller contract)
+            unsafe { self.capacity.set(len) }
+        } else {
+            // SAFETY: the heap variant is active, len <= capacity, and the elements are
+            // initialised (caller contract)
+            unsafe { (*(*self.data.get_mut().as_ptr()).heap).len = len }
+        }
+    }
+
+    /// Moves the elements to a buffer with room for exactly `capacity` elements, or inline if that
+    /// is at most `N`. The vector is left untouched if the allocation fails.
+    fn try_move_to(&mut self, capacity: usize) -> Result<(), TryReserveError> {
+        let len = self.len();
+        assert!(capacity >= len);
+        if mem::size_of::<T>() == 0 {
+            if capacity > N && self.is_short() {
+                let heap = HeapVec {
+                    buf: RawBuf::dangling(),
+                    len,
+                };
+                // SAFETY: zero-sized elements are never allocated, and always initialised
+                unsafe { self.write_heap(heap, usize::MAX) };
+            }
+            return Ok(());
+        }
+
+        let old = self
+            .is_long()
+            .then(|| (self.as_mut_ptr(), *self.capacity.get()));
+        if capacity <= N {
+            let Some((src, old_capacity)) = old else {
+                return Ok(());
+            };
+            // SAFETY:
+            // - the elements are moved inline, which cannot overlap with the heap buffer, and
+            //   len <= capacity <= N
+            // - the heap buffer was allocated by `alloc` with `old_capacity` elements, and its
+            //   elements have been moved out
+            unsafe {
+                let dst = ptr::addr_of_mut!((*self.data.get_mut().as_ptr()).inline);
+                ptr::copy_nonoverlapping(src, dst.cast::<T>(), len);
+                self.capacity.set(len);
+                RawBuf::<T, A>::from_non_null(NonNull::new_unchecked(src))
+                    .dealloc_in(old_capacity, &self.alloc)
+                    .expect("should be the exact capacity");
+            }
+            return Ok(());
+        }
+
+        let (buf, capacity) = RawBuf::try_new_in(capacity, &self.alloc)?;
+        // SAFETY:
+        // - buf is a new allocation of at least len elements, so it fits and cannot overlap
+        // - the old heap buffer was allocated by `alloc` with `old_capacity` elements, and its
+        //   elements have been moved out
+        // - buf was allocated by `alloc` with exactly `capacity > N` elements, and its first
+        //   `len` elements were just moved in
+        unsafe {
+            ptr::copy_nonoverlapping(self.as_ptr(), buf.as_ptr(), len);
+            if let Some((src, old_capacity)) = old {
+                RawBuf::<T, A>::from_non_null(NonNull::new_unchecked(src))
+                    .dealloc_in(old_capacity, &self.alloc)
+                    .expect("should be the exact capacity");
+            }
+            self.write_heap(HeapVec { buf, len }, capacity);
+        }
+        Ok(())
+    }
+
+    /// # Safety
+    /// - the vector must not own a heap buffer, or it must have been freed
+    /// - `heap` and `capacity` must uphold the field contracts of the heap variant
+    unsafe fn write_heap(&mut self, heap: HeapVec<T, A>, capacity: usize) {
+        // SAFETY: caller contract
+        unsafe {
+            (*self.data.get_mut().as_ptr()).heap = ManuallyDrop::new(heap);
+            self.capacity.set(capacity);
+        }
+    }
+
+    /// Reserves room for at least `additional` more elements, at least doubling the capacity if
+    /// the vector has to grow
+    pub fn reserve(&mut self, additional: usize) {
+        self.try_reserve(additional)
+            .unwrap_or_else(|err| panic!("allocation error: {err}"))
+    }
+
+    /// Reserves room for at least `additional` more elements, without growing any further
+    pub fn reserve_exact(&mut self, additional: usize) {
+        self.try_reserve_exact(additional)
+            .unwrap_or_else(|err| panic!("allocation error: {err}"))
+    }
+
+    /// The same as [`SsoVec::reserve`], but returns an error instead of panicking if the
+    /// allocation fails. The vector is left untouched on failure.
+    pub fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        let needed = self
+            .len()
+            .checked_add(additional)
+            .ok_or_else(unified_alloc::capacity_overflow)?;
+        if needed <= self.capacity() {
+            return Ok(());
+        }
+        self.try_move_to(cmp::max(needed, self.capacity().saturating_mul(2)))
+    }
+
+    /// The same as [`SsoVec::reserve_exact`], but returns an error instead of panicking if the
+    /// allocation fails. The vector is left untouched on failure.
+    pub fn try_reserve_exact(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        let needed = self
+            .len()
+            .checked_add(additional)
+            .ok_or_else(unified_alloc::capacity_overflow)?;
+        if needed <= self.capacity() {
+            return Ok(());
+        }
+        self.try_move_to(needed)
+    }
+
+    /// Shrinks the capacity of this vector to its length, which moves the elements inline if they
+    /// fit
+    pub fn shrink_to_fit(&mut self) {
+        if self.is_long() && mem::size_of::<T>() != 0 && self.len() < self.capacity() {
+            self.try_move_to(self.len())
+                .unwrap_or_else(|err| panic!("allocation error: {err}"))
+        }
+    }
+
+    pub fn push(&mut self, value: T) {
+        let len = self.len();
+        if len == self.capacity() {
+            self.reserve(1);
+        }
+        // SAFETY: there is room for one more element
+        unsafe {
+            self.as_mut_ptr().add(len).write(value);
+            self.set_len(len + 1);
+        }
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        let len = self.len().checked_sub(1)?;
+        // SAFETY: the last element is initialised, and it is no longer part of the vector
+        unsafe {
+            self.set_len(len);
+            Some(self.as_ptr().add(len).read())
+        }
+    }
+
+    /// Inserts `value` at position `idx`, moving all elements after it to the right
+    ///
+    /// Panics if `idx > len`.
+    pub fn insert(&mut self, idx: usize, value: T) {
+        let len = self.len();
+        assert!(
+            idx <= len,
+            "insertion index (is {idx}) should be <= len (is {len})"
+        );
+        if len == self.capacity() {
+            self.reserve(1);
+        }
+        // SAFETY: there is room for one more element, so both ranges are within the buffer
+        unsafe {
+            let p = self.as_mut_ptr().add(idx);
+            ptr::copy(p, p.add(1), len - idx);
+            p.write(value);
+            self.set_len(len + 1);
+        }
+    }
+
+    /// Removes and returns the element at position `idx`, moving all elements after it to the
+    /// left
+    ///
+    /// Panics if `idx` is out of bounds.
+    pub fn remove(&mut self, idx: usize) -> T {
+        let len = self.len();
+        assert!(
+            idx < len,
+            "removal index (is {idx}) should be < len (is {len})"
+        );
+        // SAFETY: idx < len, so both ranges are within the initialised part of the buffer
+        unsafe {
+            let p = self.as_mut_ptr().add(idx);
+            let value = p.read();
+            ptr::copy(p.add(1), p, len - idx - 1);
+            self.set_len(len - 1);
+            value
+        }
+    }
+
+    /// Removes and returns the element at position `idx`, replacing it with the last element
+    ///
+    /// Panics if `idx` is out of bounds.
+    pub fn swap_remove(&mut self, idx: usize) -> T {
+        let len = self.len();
+        assert!(
+            idx < len,
+            "swap_remove index (is {idx}) should be < len (is {len})"
+        );
+        // SAFETY: both idx and len - 1 are initialised, and the last one is no longer part of the
+        // vector after it is moved
+        unsafe {
+            let base = self.as_mut_ptr();
+            let value = base.add(idx).read();
+            ptr::copy(base.add(len - 1), base.add(idx), 1);
+            self.set_len(len - 1);
+            value
+        }
+    }
+
+    /// Shortens this vector to `new_len` elements, dropping the rest. Does nothing if `new_len`
+    /// is greater than the current length. The storage is never changed.
+    pub fn truncate(&mut self, new_len: usize) {
+        let len = self.len();
+        if new_len >= len {
+            return;
+        }
+        // SAFETY: the length is set first, so a panicking `Drop` leaks the rest instead of
+        // dropping them twice
+        unsafe {
+            self.set_len(new_len);
+            let tail = ptr::slice_from_raw_parts_mut(self.as_mut_ptr().add(new_len), len - new_len);
+            ptr::drop_in_place(tail);
+        }
+    }
+
+    /// Drops all elements, keeping the storage
+    pub fn clear(&mut self) {
+        self.truncate(0);
+    }
+
+    /// Retains only the elements specified by the predicate, the storage never changes
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&T) -> bool,
+    {
+        self.retain_mut(|value| f(value))
+    }
+
+    /// Retains only the elements specified by the predicate, which may also change them. If the
+    /// predicate or a `Drop` panics, the elements that were not processed yet are kept.
+    pub fn retain_mut<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&mut T) -> bool,
+    {
+        let len = self.len();
+        // SAFETY: the guard sets the length again, until then a panic leaks the elements
+        unsafe { self.set_len(0) };
+        let mut guard = BackshiftOnDrop {
+            vec: self,
+            processed: 0,
+            deleted: 0,
+            len,
+        };
+        // the pointer is only taken once, so that it outlives the references to the elements
+        let base = guard.vec.as_mut_ptr();
+        while guard.processed < len {
+            // SAFETY: elements from `processed` on have not been touched yet
+            let cur = unsafe { &mut *base.add(guard.processed) };
+            if !f(cur) {
+                guard.processed += 1;
+                guard.deleted += 1;
+                // SAFETY: the element is dropped once, and never looked at again
+                unsafe { ptr::drop_in_place(cur) };
+                continue;
+            }
+            if guard.deleted > 0 {
+                // SAFETY: the hole at `processed - deleted` has been dropped or moved out of
+                unsafe {
+                    ptr::copy_nonoverlapping(cur, base.add(guard.processed - guard.deleted), 1)
+                };
+            }
+            guard.processed += 1;
+        }
+    }
+
+    /// Appends clones of all elements of `other`
+    pub fn extend_from_slice(&mut self, other: &[T])
+    where
+        T: Clone,
+    {
+        self.reserve(other.len());
+        // pushing one at a time keeps the vector valid if a clone panics
+        other.iter().for_each(|value| self.push(value.clone()));
+    }
+}
+
+/// Moves the kept tail of a vector that is being compacted in place to the front and sets its
+/// length when dropped, so that a panic halfway through leaves every element that is left exactly
+/// once
+struct BackshiftOnDrop<'a, T, const N: usize, A: Allocator> {
+    vec: &'a mut SsoVec<T, N, A>,
+    processed: usize,
+    deleted: usize,
+    len: usize,
+}
+
+impl<T, const N: usize, A: Allocator> Drop for BackshiftOnDrop<'_, T, N, A> {
+    fn drop(&mut self) {
+        // SAFETY:
+        // - elements[processed..len] have not been touched, and are moved right after the kept
+        //   ones, `ptr::copy` allows the regions to overlap
+        // - that leaves len - deleted initialised elements
+        unsafe {
+            let base = self.vec.as_mut_ptr();
+            ptr::copy(
+                base.add(self.processed),
+                base.add(self.processed - self.deleted),
+                self.len - self.processed,
+            );
+            self.vec.set_len(self.len - self.deleted);
+        }
+    }
+}
+
+impl<T, const N: usize, A: Allocator> Drop for SsoVec<T, N, A> {
+    fn drop(&mut self) {
+        self.clear();
+        if self.is_long() && mem::size_of::<T>() != 0 {
+            let capacity = *self.capacity.get();
+            // SAFETY: the heap buffer was allocated by `alloc` with exactly `capacity` elements,
+            // and it is never used again
+            unsafe {
+                self.data
+                    .get()
+                    .heap
+                    .buf
+                    .dealloc_in(capacity, &self.alloc)
+                    .expect("should be the exact capacity");
+            }
+        }
+    }
+}
+
+impl<T, const N: usize, A: Allocator> Deref for SsoVec<T, N, A> {
+    type Target = [T];
+
+    fn deref(&self) -> &Self::Target {
+        self.as_slice()
+    }
+}
+
+impl<T, const N: usize, A: Allocator> DerefMut for SsoVec<T, N, A> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.as_mut_slice()
+    }
+}
+
+impl<T, const N: usize, A: Allocator> AsRef<[T]> for SsoVec<T, N, A> {
+    fn as_ref(&self) -> &[T] {
+        self
+    }
+}
+
+impl<T, const N: usize, A: Allocator> AsMut<[T]> for SsoVec<T, N, A> {
+    fn as_mut(&mut self) -> &mut [T] {
+        self
+    }
+}
+
+impl<T, const N: usize, A: Allocator> Borrow<[T]> for SsoVec<T, N, A> {
+    fn borrow(&self) -> &[T] {
+        self
+    }
+}
+
+impl<T, const N: usize, A: Allocator> BorrowMut<[T]> for SsoVec<T, N, A> {
+    fn borrow_mut(&mut self) -> &mut [T] {
+        self
+    }
+}
+
+impl<T: Clone, const N: usize, A: Allocator + Clone> Clone for SsoVec<T, N, A> {
+    /// The clone is only as big as it has to be, so a long vector that fits in `N` elements
+    /// becomes short
+    fn clone(&self) -> Self {
+        let mut other = Self::with_capacity_in(self.len(), self.alloc.clone());
+        other.extend_from_slice(self);
+        other
+    }
+}
+
+impl<T, const N: usize, A: Allocator + Default> Default for SsoVec<T, N, A> {
+    fn default() -> Self {
+        Self::new_in(A::default())
+    }
+}
+
+impl<T: fmt::Debug, const N: usize, A: Allocator> fmt::Debug for SsoVec<T, N, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_slice(), f)
+    }
+}
+
+impl<T, U, const N: usize, const M: usize, A: Allocator, B: Allocator> PartialEq<SsoVec<U, M, B>>
+    for SsoVec<T, N, A>
+where
+    T: PartialEq<U>,
+{
+    fn eq(&self, other: &SsoVec<U, M, B>) -> bool {
+        self.as_slice() == other.as_slice()
+    }
+}
+
+impl<T: Eq, const N: usize, A: Allocator> Eq for SsoVec<T, N, A> {}
+
+impl<T: PartialOrd, const N: usize, const M: usize, A: Allocator, B: Allocator>
+    PartialOrd<SsoVec<T, M, B>> for SsoVec<T, N, A>
+{
+    fn partial_cmp(&self, other: &SsoVec<T, M, B>) -> Option<cmp::Ordering> {
+        self.as_slice().partial_cmp(other.as_slice())
+    }
+}
+
+impl<T: Ord, const N: usize, A: Allocator> Ord for SsoVec<T, N, A> {
+    fn cmp(&self, other: &Self) -> cmp::Ordering {
+        self.as_slice().cmp(other.as_slice())
+    }
+}
+
+macro_rules! impl_eq_slice {
+    ([$($generics:tt)*] $Other:ty) => {
+        impl<$($generics)* T, U, const N: usize, A: Allocator> PartialEq<$Other> for SsoVec<T, N, A>
+        where
+            T: PartialEq<U>,
+        {
+            fn eq(&self, other: &$Other) -> bool {
+                self.as_slice() == &other[..]
+            }
+        }
+    };
+}
+
+impl_eq_slice!([][U]);
+impl_eq_slice!(['a,] &'a [U]);
+impl_eq_slice!([] Vec<U>);
+impl_eq_slice!([const M: usize,] [U; M]);
+impl_eq_slice!(['a, const M: usize,] &'a [U; M]);
+
+impl<T: Hash, const N: usize, A: Allocator> Hash for SsoVec<T, N, A> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // exactly the same as `[T]`, so that lookups through `Borrow<[T]>` work
+        self.as_slice().hash(state)
+    }
+}
+
+impl<T, const N: usize, A: Allocator> Extend<T> for SsoVec<T, N, A> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        self.reserve(iter.size_hint().0);
+        iter.for_each(|value| self.push(value));
+    }
+}
+
+impl<'a, T: Copy + 'a, const N: usize, A: Allocator> Extend<&'a T> for SsoVec<T, N, A> {
+    fn extend<I: IntoIterator<Item = &'a T>>(&mut self, iter: I) {
+        self.extend(iter.into_iter().copied())
+    }
+}
+
+impl<T, const N: usize> FromIterator<T> for SsoVec<T, N> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut vec = Self::new();
+        vec.extend(iter);
+        vec
+    }
+}
+
+impl<'a, T: Clone, const N: usize> From<&'a [T]> for SsoVec<T, N> {
+    fn from(value: &'a [T]) -> Self {
+        let mut vec = Self::with_capacity(value.len());
+        vec.extend_from_slice(value);
+        vec
+    }
+}
+
+impl<T, const N: usize, const M: usize> From<[T; M]> for SsoVec<T, N> {
+    fn from(value: [T; M]) -> Self {
+        value.into_iter().collect()
+    }
+}
+
+// `Vec` always allocates with `Global`, so we can only adopt its buffer into a vector that uses
+// `Global` as well
+impl<T, const N: usize> From<Vec<T>> for SsoVec<T, N> {
+    /// A vector that does not fit inline hands its buffer over without copying
+    fn from(value: Vec<T>) -> Self {
+        if value.len() <= N || mem::size_of::<T>() == 0 {
+            return value.into_iter().collect();
+        }
+        let mut value = ManuallyDrop::new(value);
+        let mut vec = Self::new();
+        let heap = HeapVec {
+            // SAFETY: the buffer of a non-empty vector is never null
+            buf: RawBuf::from_non_null(unsafe { NonNull::new_unchecked(value.as_mut_ptr()) }),
+            len: value.len(),
+        };
+        // SAFETY:
+        // - the new vector is empty, and owns nothing
+        // - `Vec` allocates with `Global`, with the same layout that `RawBuf` uses for `capacity`
+        //   elements, and `capacity >= len > N`
+        // - `value` is never dropped, so the new vector is the only owner of the buffer
+        unsafe { vec.write_heap(heap, value.capacity()) };
+        vec
+    }
+}
+
+impl<T, const N: usize> From<SsoVec<T, N>> for Vec<T> {
+    /// A long vector hands its buffer over without copying, a short one is moved into a new
+    /// buffer that is exactly as big as it is
+    fn from(value: SsoVec<T, N>) -> Self {
+        if value.is_short() || mem::size_of::<T>() == 0 {
+            return value.into_iter().collect();
+        }
+        let mut value = ManuallyDrop::new(value);
+        let (len, capacity) = (value.len(), value.capacity());
+        // SAFETY:
+        // - the buffer was allocated by `Global` with the same layout that `Vec` uses for
+        //   `capacity` elements, and its first `len` elements are initialised
+        // - `value` is never dropped, so the vector is the only owner of the buffer
+        unsafe { Vec::from_raw_parts(value.as_mut_ptr(), len, capacity) }
+    }
+}
+
+impl<T, const N: usize, A: Allocator> IntoIterator for SsoVec<T, N, A> {
+    type Item = T;
+    type IntoIter = IntoIter<T, N, A>;
+
+    fn into_iter(mut self) -> Self::IntoIter {
+        let end = self.len();
+        // SAFETY: the iterator owns the elements from now on, the vector only frees the buffer
+        unsafe { self.set_len(0) };
+        IntoIter {
+            vec: self,
+            start: 0,
+            end,
+        }
+    }
+}
+
+impl<'a, T, const N: usize, A: Allocator> IntoIterator for &'a SsoVec<T, N, A> {
+    type Item = &'a T;
+    type IntoIter = slice::Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, T, const N: usize, A: Allocator> IntoIterator for &'a mut SsoVec<T, N, A> {
+    type Item = &'a mut T;
+    type IntoIter = slice::IterMut<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+/// An iterator that moves the elements out of a [`SsoVec`]. The elements stay where they are, so
+/// inline elements move along with the iterator.
+pub struct IntoIter<T, const N: usize, A: Allocator = Global> {
+    /// # Safety
+    /// - the vector has a length of 0, but its elements `start..end` are initialised and owned by
+    ///   this iterator
+    vec: SsoVec<T, N, A>,
+    start: usize,
+    end: usize,
+}
+
+impl<T, const N: usize, A: Allocator> IntoIter<T, N, A> {
+    /// Returns the elements that have not been yielded yet
+    pub fn as_slice(&self) -> &[T] {
+        // SAFETY: elements start..end are initialised (field contract)
+        unsafe { slice::from_raw_parts(self.vec.as_ptr().add(self.start), self.end - self.start) }
+    }
+}
+
+impl<T, const N: usize, A: Allocator> Iterator for IntoIter<T, N, A> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        if self.start == self.end {
+            return None;
+        }
+        self.start += 1;
+        // SAFETY: the element is initialised, and no longer owned by the iterator
+        Some(unsafe { self.vec.as_ptr().add(self.start - 1).read() })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.end - self.start;
+        (len, Some(len))
+    }
+}
+
+impl<T, const N: usize, A: Allocator> DoubleEndedIterator for IntoIter<T, N, A> {
+    fn next_back(&mut self) -> Option<T> {
+        if self.start == self.end {
+            return None;
+        }
+        self.end -= 1;
+        // SAFETY: the element is initialised, and no longer owned by the iterator
+        Some(unsafe { self.vec.as_ptr().add(self.end).read() })
+    }
+}
+
+impl<T, const N: usize, A: Allocator> ExactSizeIterator for IntoIter<T, N, A> {}
+
+impl<T, const N: usize, A: Allocator> FusedIterator for IntoIter<T, N, A> {}
+
+impl<T, const N: usize, A: Allocator> Drop for IntoIter<T, N, A> {
+    fn drop(&mut self) {
+        let rest = ptr::slice_from_raw_parts_mut(
+            // SAFETY: start <= end <= capacity
+            unsafe { self.vec.as_mut_ptr().add(self.start) },
+            self.end - self.start,
+        );
+        // SAFETY: the rest is initialised and owned by the iterator, the vector frees the buffer
+        unsafe { ptr::drop_in_place(rest) }
+    }
+}
+
+impl<T: fmt::Debug, const N: usize, A: Allocator> fmt::Debug for IntoIter<T, N, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("IntoIter").field(&self.as_slice()).finish()
+    }
+}
@@ -1,14 +1,27 @@
 use std::{
     borrow::Cow,
+    cell::Cell,
     mem::{self, ManuallyDrop},
     ptr::{self, NonNull},
 };
 
-use crate::sso_string::{RawBuf, SsoStr, TaggedSsoString64Mut};
+#[cfg(unix)]
+use crate::sso_os_string::{SsoOsString, SsoPathBuf};
+use crate::{
+    arc_sso_string::{ArcSsoString, TaggedArcSsoString},
+    archive::{ArchiveWriter, ArchivedSsoString, ArchivedStringTable, CheckArchiveError},
+    char_counted::CharCountedString,
+    intern::{Interner, Symbol, SyncInterner},
+    sso_bytes::SsoBytes,
+    sso_string::{RawBuf, SsoStr, SsoString, TaggedSsoString64, TaggedSsoString64Mut},
+    sso_vec::SsoVec,
+    unified_alloc::{AllocError, Allocator, Global},
+    unsafe_field::{SimultaneousUnsafeAssign, SimultaneousUnsafeAssignment, UnsafeField},
+};
 
 type StdString = std::string::String;
 type String = crate::sso_string::SsoString;
-type ShortString = crate::sso_string::ShortString64;
+type ShortString = crate::sso_string::ShortString;
 type LongString = crate::sso_string::LongString;
 
 fn assert_aligned<T>(ptr: *const T) {
@@ -22,7 +35,9 @@ fn assert_non_null<T>(ptr: *const T) {
 #[test]
 fn raw_buf_clones_correctly() {
     let (buf, ..) = RawBuf::<i32>::new(16);
-    assert_eq!(buf.data, buf.clone().data);
+    #[allow(clippy::clone_on_copy)]
+    let cloned = buf.clone();
+    assert_eq!(buf.data, cloned.data);
 }
 
 #[test]
@@ -35,18 +50,21 @@ fn raw_buf_is_aligned_and_non_null() {
 #[test]
 fn raw_buf_capacity_is_correct() {
     fn assert_raw_buf_capacity_is_correct<T>() {
-        let (_, byte_count) = RawBuf::<i32>::new(16);
-        assert!(byte_count >= 16 * mem::size_of::<i32>());
+        // the capacity counts elements, not bytes
+        let (_, capacity) = RawBuf::<T>::new(16);
+        assert!(capacity >= 16);
+        assert!(capacity < 16 + mem::align_of::<usize>());
 
-        let (buf, byte_count) = RawBuf::<i32>::new(0);
-        assert_eq!(buf.data, NonNull::<i32>::dangling());
-        assert_eq!(byte_count, 0);
+        let (buf, capacity) = RawBuf::<T>::new(0);
+        assert_eq!(buf.data, NonNull::<T>::dangling());
+        assert_eq!(capacity, 0);
     }
     assert_raw_buf_capacity_is_correct::<i32>();
     assert_raw_buf_capacity_is_correct::<u8>();
 }
 
 #[test]
+#[cfg(target_pointer_width = "64")]
 fn test_sso_string_upgrades() {
     let mut s = String::from("Hello, world,");
     assert!(s.is_short());
@@ -61,6 +79,7 @@ fn test_sso_string_upgrades() {
 }
 
 #[test]
+#[cfg(target_pointer_width = "64")]
 fn short_string_64_fills_to_max_capacity() {
     assert_eq!(ShortString::MAX_CAPACITY, 23);
 
@@ -149,12 +168,56 @@ fn long_string_reallocs_automatically() {
     assert_eq!(len + 1, s.len());
 
     let mut correct_s = StdString::from(&tail);
-    correct_s.push_str(".");
+    correct_s.push('.');
     assert_eq!(s.as_str(), &correct_s);
     s.free();
 }
 
 #[test]
+fn shrink_to_never_grows() {
+    let mut s = String::with_capacity(100);
+    s.push_str(&"x".repeat(40));
+    let data = s.as_ptr();
+    s.shrink_to(1000);
+    assert_eq!(s.capacity(), 100);
+    assert_eq!(s.as_ptr(), data);
+    s.shrink_to(50);
+    assert_eq!(s.capacity(), 50);
+    s.shrink_to(0);
+    assert_eq!(s.capacity(), 40);
+    s.truncate(3);
+    s.shrink_to(0);
+    assert!(s.is_short());
+    assert_eq!(&s, "xxx");
+}
+
+#[test]
+fn long_string_as_mut_str_is_only_the_initialised_bytes() {
+    let mut s = LongString::with_capacity(64);
+    s.push_str("HELLO, WORLD");
+    assert_eq!(s.as_mut_str().len(), s.len());
+    s.as_mut_str().make_ascii_lowercase();
+    assert_eq!(s.as_str(), "hello, world");
+    s.free();
+}
+
+#[test]
+fn long_string_can_be_freed_twice() {
+    let mut s = LongString::with_capacity(32);
+    s.push_str("Hello, world!");
+    s.free();
+    assert_eq!((s.len(), s.capacity()), (0, 0));
+    s.free();
+    assert_eq!((s.len(), s.capacity()), (0, 0));
+
+    // never allocated in the first place
+    let mut s = LongString::with_capacity(0);
+    s.free();
+    assert_eq!(s.capacity(), 0);
+}
+
+#[test]
+#[cfg(target_pointer_width = "64")]
 fn as_mut_str_works() {
     let mut s = String::from("Hello, world!");
     s.as_mut_str().make_ascii_uppercase();
@@ -275,6 +338,7 @@ fn boost_coverage() {
 }
 
 #[test]
+#[cfg(target_pointer_width = "64")]
 fn constructable_from_short_std_string() {
     let stdstr = StdString::from("this is small");
     assert!(stdstr.len() < ShortString::MAX_CAPACITY);
@@ -290,4 +354,1538 @@ fn constructable_from_long_string() {
     let ssostr = String::from(stdstr);
     assert_eq!(&ssostr, "this is a very long string");
     assert!(ssostr.is_long());
-}
\ No newline at end of file
+}
+#[test]
+#[cfg(target_pointer_width = "64")]
+fn drain_works() {
+    let mut s = String::from("Hello, world!");
+    let drained: StdString = s.drain(5..12).collect();
+    assert_eq!(drained, ", world");
+    assert_eq!(&s, "Hello!");
+    assert!(s.is_short());
+
+    let mut s = String::from("α is alpha, β is beta, γ is gamma");
+    assert!(s.is_long());
+    let mut drain = s.drain(..s.find(',').unwrap() + 2);
+    assert_eq!(drain.next(), Some('α'));
+    assert_eq!(drain.next_back(), Some(' '));
+    drop(drain);
+    assert_eq!(&s, "β is beta, γ is gamma");
+    assert!(s.is_long());
+}
+
+#[test]
+#[should_panic]
+fn drain_panics_on_char_boundary() {
+    let mut s = String::from("αβγ");
+    s.drain(1..);
+}
+
+#[test]
+#[cfg(target_pointer_width = "64")]
+fn replace_range_moves_between_short_and_long() {
+    let mut s = String::from("Hello, world!");
+    s.replace_range(7..12, "Gregory");
+    assert_eq!(&s, "Hello, Gregory!");
+    assert!(s.is_short());
+
+    s.replace_range(7..14, "Gregory and everyone else");
+    assert_eq!(&s, "Hello, Gregory and everyone else!");
+    assert!(s.is_long());
+
+    s.replace_range(10..=15, "a");
+    assert_eq!(&s, "Hello, Greand everyone else!");
+    assert!(s.is_long());
+
+    s.replace_range(.., "Hi!");
+    assert_eq!(&s, "Hi!");
+    assert!(s.is_short());
+}
+
+#[test]
+#[cfg(target_pointer_width = "64")]
+fn extend_from_within_works() {
+    let mut s = String::from("abcdefghij");
+    s.extend_from_within(2..5);
+    assert_eq!(&s, "abcdefghijcde");
+    assert!(s.is_short());
+
+    s.extend_from_within(..);
+    assert_eq!(&s, "abcdefghijcdeabcdefghijcde");
+    assert!(s.is_long());
+
+    s.extend_from_within(20..);
+    assert_eq!(&s, "abcdefghijcdeabcdefghijcdehijcde");
+}
+
+#[test]
+#[cfg(target_pointer_width = "64")]
+fn insert_upgrades_when_full() {
+    let mut s = String::from("Hello world");
+    s.insert(5, ',');
+    s.insert_str(s.len(), "!");
+    assert_eq!(&s, "Hello, world!");
+    assert!(s.is_short());
+
+    s.insert_str(0, "Oh, by the way: ");
+    assert_eq!(&s, "Oh, by the way: Hello, world!");
+    assert!(s.is_long());
+
+    s.insert(0, 'é');
+    assert_eq!(&s, "éOh, by the way: Hello, world!");
+}
+
+#[test]
+#[should_panic]
+fn insert_panics_on_char_boundary() {
+    let mut s = String::from("αβγ");
+    s.insert(1, 'x');
+}
+
+#[test]
+fn remove_works() {
+    let mut s = String::from("αβγ");
+    assert_eq!(s.remove(2), 'β');
+    assert_eq!(&s, "αγ");
+
+    let mut s = String::from("this is a very long string");
+    assert_eq!(s.remove(0), 't');
+    assert_eq!(&s, "his is a very long string");
+    assert!(s.is_long());
+}
+
+#[test]
+fn truncate_never_demotes() {
+    let mut s = String::from("this is a very long string");
+    s.truncate(4);
+    assert_eq!(&s, "this");
+    assert!(s.is_long());
+    s.truncate(100);
+    assert_eq!(&s, "this");
+    s.shrink_to_fit();
+    assert!(s.is_short());
+
+    let mut s = String::from("αβγ");
+    s.truncate(4);
+    assert_eq!(&s, "αβ");
+}
+
+#[test]
+#[cfg(target_pointer_width = "64")]
+fn split_off_returns_short_tails() {
+    let mut s = String::from("Hello, world!");
+    let tail = s.split_off(5);
+    assert_eq!((&*s, &*tail), ("Hello", ", world!"));
+    assert!(tail.is_short());
+
+    let mut s = String::from("this is a very long string");
+    let tail = s.split_off(10);
+    assert_eq!((&*s, &*tail), ("this is a ", "very long string"));
+    assert!(s.is_long());
+    assert!(tail.is_short());
+
+    let mut s = String::from("this is a very, very, very long string");
+    let tail = s.split_off(4);
+    assert_eq!(
+        (&*s, &*tail),
+        ("this", " is a very, very, very long string")
+    );
+    assert!(tail.is_long());
+}
+
+#[test]
+fn raw_buf_try_new_fails_gracefully() {
+    assert!(RawBuf::<u8>::try_new(usize::MAX).is_err());
+    assert!(RawBuf::<u64>::try_new(usize::MAX / 4).is_err());
+    assert!(RawBuf::<u8, FailingAlloc>::try_new_in(16, &FailingAlloc).is_err());
+    assert!(crate::sso_string::LongString::try_with_capacity_in(16, FailingAlloc).is_err());
+    let (buf, capacity) = RawBuf::<u8>::try_new(16).expect("small allocations succeed");
+    assert!(capacity >= 16);
+    // SAFETY: capacity is exactly what `try_new` returned
+    unsafe { buf.dealloc(capacity).expect("exact capacity") };
+}
+
+/// Fails every allocation
+#[derive(Clone, Copy)]
+struct FailingAlloc;
+
+// SAFETY: no block is ever handed out
+unsafe impl Allocator for FailingAlloc {
+    fn allocate(&self, _layout: std::alloc::Layout) -> Result<NonNull<[u8]>, AllocError> {
+        Err(AllocError)
+    }
+
+    unsafe fn deallocate(&self, _ptr: NonNull<u8>, _layout: std::alloc::Layout) {
+        unreachable!("nothing was ever allocated")
+    }
+}
+
+#[test]
+fn try_reserve_leaves_string_untouched_on_failure() {
+    let mut s = String::from("Hello!");
+    assert!(s.try_reserve(usize::MAX).is_err());
+    assert!(s.try_reserve_exact(isize::MAX as usize).is_err());
+    assert_eq!(&s, "Hello!");
+    assert!(s.is_short());
+
+    s.try_reserve(100).expect("small allocations succeed");
+    assert!(s.is_long());
+    assert!(s.capacity() >= 100);
+    assert!(s.try_reserve(usize::MAX).is_err());
+    assert!(s.try_reserve(isize::MAX as usize).is_err());
+    assert_eq!(&s, "Hello!");
+
+    s.try_reserve_exact(10).expect("small allocations succeed");
+    assert!(s.capacity() - s.len() >= 10);
+    assert_eq!(&s, "Hello!");
+}
+
+#[test]
+fn try_reserve_reports_allocator_failure() {
+    let mut s = crate::sso_string::SsoString::new_in(FailingAlloc);
+    s.push_str("Hello!");
+    assert!(s.try_reserve(100).is_err());
+    assert_eq!(&s, "Hello!");
+    assert!(s.is_short());
+}
+
+#[test]
+fn long_string_try_with_capacity() {
+    assert!(LongString::try_with_capacity(usize::MAX).is_err());
+    let mut long = LongString::try_with_capacity(32).expect("small allocations succeed");
+    assert!(long.capacity() >= 32);
+    long.free();
+}
+
+/// Forwards to `Global`, counting how many blocks are currently allocated
+#[derive(Clone, Copy)]
+struct CountingAlloc<'a> {
+    live: &'a std::cell::Cell<isize>,
+}
+
+// SAFETY: every call is forwarded to `Global`
+unsafe impl Allocator for CountingAlloc<'_> {
+    fn allocate(&self, layout: std::alloc::Layout) -> Result<NonNull<[u8]>, AllocError> {
+        self.live.set(self.live.get() + 1);
+        Global.allocate(layout)
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: std::alloc::Layout) {
+        self.live.set(self.live.get() - 1);
+        // SAFETY: ptr was allocated by `Global` in `allocate`
+        unsafe { Global.deallocate(ptr, layout) }
+    }
+}
+
+#[test]
+fn zero_sized_allocators_take_no_space() {
+    let size = mem::size_of::<StdString>();
+    assert_eq!(mem::size_of::<String>(), size);
+    assert_eq!(mem::size_of::<crate::sso_string::SsoString<Global>>(), size);
+    assert_eq!(
+        mem::size_of::<crate::sso_string::SsoString<CountingAlloc>>(),
+        size + mem::size_of::<CountingAlloc>()
+    );
+}
+
+#[test]
+fn long_strings_use_their_allocator() {
+    let live = std::cell::Cell::new(0);
+    let alloc = CountingAlloc { live: &live };
+    let mut s = SsoString::new_in(alloc);
+    s.push_str("short");
+    assert_eq!(live.get(), 0);
+    s.push_str(", but not for very long");
+    assert!(s.is_long());
+    assert_eq!(live.get(), 1);
+    assert!(ptr::eq(s.allocator().live, &live));
+
+    s.reserve(100);
+    s.reserve_exact(100);
+    s.shrink_to(50);
+    assert_eq!(live.get(), 1);
+    s.truncate(5);
+    s.shrink_to_fit();
+    assert!(s.is_short());
+    assert_eq!(live.get(), 0);
+    assert!(ptr::eq(s.allocator().live, &live));
+
+    s.push_str(" and long again, one more time");
+    drop(s);
+    assert_eq!(live.get(), 0);
+}
+
+#[test]
+fn with_capacity_in_only_allocates_long_strings() {
+    let live = std::cell::Cell::new(0);
+    let alloc = CountingAlloc { live: &live };
+    let short = SsoString::with_capacity_in(ShortString::MAX_CAPACITY, alloc);
+    assert!(short.is_short());
+    assert_eq!(live.get(), 0);
+    let mut long = SsoString::with_capacity_in(64, alloc);
+    assert!(long.is_long());
+    assert_eq!(live.get(), 1);
+
+    long.push_str("a string long enough to stay long after splitting");
+    let tail = long.split_off(2);
+    assert!(tail.is_long());
+    assert_eq!(live.get(), 2);
+    assert_eq!(&long, "a ");
+    assert_eq!(&tail, "string long enough to stay long after splitting");
+    drop((short, long, tail));
+    assert_eq!(live.get(), 0);
+}
+
+#[test]
+#[cfg(target_pointer_width = "64")]
+fn inline_capacity_is_rounded_to_words() {
+    use crate::sso_string::SsoStringN;
+    assert_eq!(mem::size_of::<SsoStringN<15>>(), 24);
+    assert_eq!(mem::size_of::<SsoStringN<23>>(), 24);
+    assert_eq!(mem::size_of::<SsoStringN<31>>(), 32);
+    assert_eq!(mem::size_of::<SsoStringN<63>>(), 64);
+    assert_eq!(mem::size_of::<SsoStringN<126>>(), 128);
+}
+
+#[test]
+fn larger_inline_capacities_stay_short() {
+    let key = "user:42:session:7f3a9c1e-85b2-4d1f";
+    let mut s = crate::sso_string::SsoStringN::<47>::from(key);
+    assert!(s.is_short());
+    assert_eq!(s.capacity(), 47);
+    assert_eq!(&s, key);
+    s.push_str("0123456789abcdef");
+    assert!(s.is_long());
+    assert_eq!(s.len(), key.len() + 16);
+    s.truncate(key.len());
+    s.shrink_to_fit();
+    assert!(s.is_short());
+    assert_eq!(s.pop(), Some('f'));
+}
+
+#[test]
+fn into_inline_capacity_keeps_long_buffers() {
+    let mut long = String::from("a string that is too long to be short");
+    long.reserve(64);
+    let ptr = long.as_ptr();
+    let wide = long.into_inline_capacity::<63>();
+    assert!(wide.is_long());
+    assert_eq!(wide.as_ptr(), ptr);
+    assert_eq!(&wide, "a string that is too long to be short");
+
+    let narrow = String::from("short").into_inline_capacity::<7>();
+    assert!(narrow.is_short());
+    assert_eq!(&narrow, "short");
+    let upgraded = String::from("not short enough").into_inline_capacity::<7>();
+    assert!(upgraded.is_long());
+    assert_eq!(&upgraded, "not short enough");
+    assert_eq!(&upgraded.into_inline_capacity::<23>(), "not short enough");
+}
+
+#[test]
+fn tag_bit_is_in_the_first_byte_on_every_target() {
+    assert_eq!(ShortString::MAX_CAPACITY, 3 * mem::size_of::<usize>() - 1);
+    assert_eq!(mem::size_of::<ShortString>(), mem::size_of::<LongString>());
+
+    #[cfg(target_endian = "little")]
+    let flag = 1;
+    #[cfg(target_endian = "big")]
+    let flag = 1 << 7;
+    // SAFETY: both strings are at least one byte large, and all of their bytes are initialised
+    let first_byte = |s: &String| unsafe { *(s as *const String).cast::<u8>() };
+    let short = String::from("short");
+    let long = String::from("a string that is too long to be short");
+    assert_eq!(first_byte(&short) & flag, flag);
+    assert_eq!(first_byte(&long) & flag, 0);
+    assert_eq!(short.len(), 5);
+    assert_eq!(long.len(), 37);
+}
+
+#[test]
+fn option_uses_the_niche() {
+    use crate::sso_string::SsoStringN;
+    let size = mem::size_of::<String>();
+    assert_eq!(mem::size_of::<Option<String>>(), size);
+    assert_eq!(mem::size_of::<Result<String, ()>>(), size);
+    assert_eq!(
+        mem::size_of::<Option<SsoStringN<31>>>(),
+        mem::size_of::<SsoStringN<31>>()
+    );
+    assert_eq!(
+        mem::size_of::<Option<crate::sso_string::SsoString<CountingAlloc>>>(),
+        size + mem::size_of::<CountingAlloc>()
+    );
+}
+
+#[test]
+fn option_round_trips_both_variants() {
+    let mut short = Some(String::from("short"));
+    let mut long = Some(String::from("a string that is too long to be short"));
+    assert!(short.as_ref().is_some_and(|s| s.is_short()));
+    assert!(long.as_ref().is_some_and(|s| s.is_long()));
+    long.as_mut().unwrap().push_str(", and longer");
+    short.as_mut().unwrap().truncate(0);
+    assert_eq!(short.take().as_deref(), Some(""));
+    assert_eq!(
+        long.take().as_deref(),
+        Some("a string that is too long to be short, and longer")
+    );
+    assert!(short.is_none() && long.is_none());
+}
+
+#[test]
+fn stateful_allocators_are_stored_in_front() {
+    let live = std::cell::Cell::new(0);
+    let alloc = CountingAlloc { live: &live };
+    let mut s = Some(SsoString::new_in(alloc));
+    let inner = s.as_mut().unwrap();
+    inner.push_str("a string that is too long to be short");
+    assert!(inner.is_long());
+    assert!(ptr::eq(inner.allocator().live, &live));
+    inner.truncate(3);
+    inner.shrink_to_fit();
+    assert!(inner.is_short());
+    assert_eq!(inner, "a s");
+    assert!(ptr::eq(inner.allocator().live, &live));
+    drop(s.take());
+    assert_eq!(live.get(), 0);
+}
+
+#[test]
+fn hashes_and_orders_like_str() {
+    use std::collections::{BTreeSet, HashMap};
+
+    let mut map = HashMap::new();
+    map.insert(String::from("short"), 1);
+    map.insert(String::from("a string that is too long to be short"), 2);
+    assert_eq!(map.get("short"), Some(&1));
+    assert_eq!(map.get("a string that is too long to be short"), Some(&2));
+
+    let set: BTreeSet<_> = ["b", "a string that is too long to be short", "c", "a"]
+        .into_iter()
+        .map(String::from)
+        .collect();
+    let sorted: Vec<&str> = set.iter().map(|s| s.as_str()).collect();
+    assert_eq!(
+        sorted,
+        ["a", "a string that is too long to be short", "b", "c"]
+    );
+    let (a, b) = (String::from("a"), String::from("b"));
+    assert!(a < b);
+    assert!(a.cmp(&b).is_lt());
+    let std_b = StdString::from("b");
+    assert!(std_b > a);
+}
+
+#[test]
+fn compares_with_every_kind_of_str() {
+    let s = String::from("hello");
+    let std_s = StdString::from("hello");
+    let cow = Cow::<str>::Owned("hello".into());
+    assert_eq!(s, "hello");
+    assert_eq!("hello", s);
+    assert_eq!(s, *"hello");
+    assert_eq!(*"hello", s);
+    assert_eq!(s, std_s);
+    assert_eq!(std_s, s);
+    assert_eq!(s, cow);
+    assert_eq!(cow, s);
+    assert!(s < "world");
+    assert!(Cow::Borrowed("world") > s);
+}
+
+#[test]
+fn default_from_str_and_index() {
+    #[derive(Default)]
+    struct Named {
+        name: String,
+    }
+    assert_eq!(Named::default().name, "");
+
+    let mut s: String = "hello world".parse().unwrap();
+    assert_eq!(&s[..5], "hello");
+    assert_eq!(&s[6..], "world");
+    s[..5].make_ascii_uppercase();
+    assert_eq!(s, "HELLO world");
+    let bytes: &[u8] = s.as_ref();
+    assert_eq!(bytes, b"HELLO world");
+    let path: &std::path::Path = s.as_ref();
+    assert_eq!(path.to_str(), Some("HELLO world"));
+}
+
+#[test]
+fn clone_from_reuses_the_buffer() {
+    let long = String::from("a string that is too long to be short");
+    let clone = long.clone();
+    assert_eq!(clone, long);
+    assert_ne!(clone.as_ptr(), long.as_ptr());
+
+    let mut target = String::with_capacity(64);
+    let ptr = target.as_ptr();
+    target.clone_from(&long);
+    assert_eq!(target, long);
+    assert_eq!(target.as_ptr(), ptr);
+
+    let mut long_fits = String::from("a string that is too long to be short");
+    long_fits.truncate(5);
+    assert!(long_fits.is_long());
+    assert!(long_fits.clone().is_short());
+}
+
+#[test]
+fn collects_and_extends_from_every_kind_of_str() {
+    let short: String = "hello".chars().collect();
+    assert!(short.is_short());
+    assert_eq!(short, "hello");
+
+    let long: String = std::iter::repeat_n('a', 30).collect();
+    assert!(long.is_long());
+    assert_eq!(long, "a".repeat(30));
+
+    let mut s: String = ["a", "b"].into_iter().collect();
+    s.extend(['c', 'd'].iter());
+    s.extend([StdString::from("e")]);
+    s.extend([Box::<str>::from("f")]);
+    s.extend([Cow::Borrowed("g"), Cow::Owned("h".into())]);
+    s.extend([
+        String::from("i"),
+        String::from("a string that is too long to be short"),
+    ]);
+    assert_eq!(s, "abcdefghia string that is too long to be short");
+}
+
+#[test]
+fn extend_upgrades_up_front_from_the_size_hint() {
+    let live = std::cell::Cell::new(0);
+    let mut s = SsoString::new_in(CountingAlloc { live: &live });
+    s.push_str("abc");
+    s.extend(std::iter::repeat_n('x', 40));
+    assert!(s.is_long());
+    assert!(s.capacity() >= 43);
+    assert_eq!(live.get(), 1);
+
+    let mut short = String::from("abc");
+    short.extend("def".chars());
+    assert!(short.is_short());
+    assert_eq!(short, "abcdef");
+}
+
+#[test]
+fn format_writes_straight_into_the_string() {
+    use std::fmt::Write;
+
+    let short = crate::format!("{}-{}", 1, "two");
+    assert!(short.is_short());
+    assert_eq!(short, "1-two");
+
+    let long = crate::format!("{:>30}", "right");
+    assert!(long.is_long());
+    assert_eq!(long, std::format!("{:>30}", "right"));
+
+    let mut s = String::new();
+    write!(s, "{}", 42).unwrap();
+    s.write_char('!').unwrap();
+    assert_eq!(s, "42!");
+}
+
+#[test]
+fn into_std_string_reuses_long_buffers() {
+    let long = String::from("a string that is too long to be short");
+    let ptr = long.as_ptr();
+    let capacity = long.capacity();
+    let std_long = StdString::from(long);
+    assert_eq!(std_long, "a string that is too long to be short");
+    assert_eq!(std_long.as_ptr(), ptr);
+    assert_eq!(std_long.capacity(), capacity);
+
+    let std_short = StdString::from(String::from("short"));
+    assert_eq!(std_short, "short");
+    assert_eq!(std_short.capacity(), 5);
+
+    // and back again, still without copying
+    let round_trip = String::from(std_long);
+    assert_eq!(round_trip.as_ptr(), ptr);
+}
+
+#[test]
+fn into_boxed_str_and_leak() {
+    let mut long = String::with_capacity(64);
+    long.push_str("a string that is too long to be short");
+    let boxed = long.into_boxed_str();
+    assert_eq!(&*boxed, "a string that is too long to be short");
+    assert_eq!(&*String::from("short").into_boxed_str(), "short");
+
+    let leaked: &'static mut str = String::from("short").leak();
+    leaked.make_ascii_uppercase();
+    assert_eq!(leaked, "SHORT");
+    // SAFETY: the string was leaked from a `String`, which allocated it with `Global`
+    drop(unsafe { Box::from_raw(leaked) });
+}
+
+#[test]
+fn decodes_utf16_into_inline_storage() {
+    let utf16: Vec<u16> = "hello 🦀".encode_utf16().collect();
+    let short = String::from_utf16(&utf16).unwrap();
+    assert!(short.is_short());
+    assert_eq!(short, "hello 🦀");
+
+    let long_text = "a string that is too long to be short";
+    let utf16: Vec<u16> = long_text.encode_utf16().collect();
+    let long = String::from_utf16(&utf16).unwrap();
+    assert!(long.is_long());
+    assert_eq!(long, long_text);
+
+    assert!(String::from_utf16(&[0x68, 0xD800, 0x69]).is_err());
+    assert_eq!(
+        String::from_utf16_lossy(&[0x68, 0xD800, 0x69]),
+        "h\u{FFFD}i"
+    );
+}
+
+#[test]
+fn decodes_utf16_bytes_in_either_order() {
+    let le: Vec<u8> = "hé🦀".encode_utf16().flat_map(u16::to_le_bytes).collect();
+    let be: Vec<u8> = "hé🦀".encode_utf16().flat_map(u16::to_be_bytes).collect();
+    assert_eq!(String::from_utf16le(&le).unwrap(), "hé🦀");
+    assert_eq!(String::from_utf16be(&be).unwrap(), "hé🦀");
+    assert!(String::from_utf16le(&le[1..]).is_err());
+    assert!(String::from_utf16be(&be[..be.len() - 1]).is_err());
+    assert_eq!(String::from_utf16le_lossy(&[b'h', 0, b'i']), "h\u{FFFD}");
+    assert_eq!(
+        String::from_utf16be_lossy(&[0xD8, 0x00, 0, b'i']),
+        "\u{FFFD}i"
+    );
+}
+
+#[test]
+fn from_utf8_lossy_borrows_valid_input() {
+    let valid = String::from_utf8_lossy(b"hello");
+    assert!(matches!(valid, Cow::Borrowed(_)));
+    assert_eq!(&**valid, "hello");
+
+    let invalid = String::from_utf8_lossy(b"hi \xF0\x90\x80!");
+    let Cow::Owned(owned) = invalid else {
+        panic!("invalid input must be decoded into a new string");
+    };
+    assert!(owned.is_short());
+    assert_eq!(owned, "hi \u{FFFD}!");
+
+    // SAFETY: the bytes are valid utf-8
+    let unchecked = unsafe { String::from_utf8_unchecked(b"short".to_vec()) };
+    assert!(unchecked.is_short());
+    assert_eq!(unchecked, "short");
+}
+
+#[test]
+fn retain_compacts_in_place() {
+    let live = std::cell::Cell::new(0);
+    let mut s = SsoString::new_in(CountingAlloc { live: &live });
+    s.push_str("a long string, with commas, that is not short");
+    let ptr = s.as_ptr();
+    s.retain(|ch| ch != ',' && ch != ' ');
+    assert_eq!(s, "alongstringwithcommasthatisnotshort");
+    assert_eq!(s.as_ptr(), ptr);
+    assert_eq!(live.get(), 1);
+
+    let mut short = String::from("h€llo w€");
+    short.retain(|ch| ch != '€');
+    assert_eq!(short, "hllo w");
+}
+
+#[test]
+fn retain_mut_changes_chars_and_grows_if_needed() {
+    let mut s = String::from("a-b-c");
+    s.retain_mut(|ch| {
+        ch.make_ascii_uppercase();
+        *ch != '-'
+    });
+    assert_eq!(s, "ABC");
+
+    // every char grows from one byte to four, which doesn't fit in the freed space
+    let mut s = String::from("xa-ab-bab");
+    s.retain_mut(|ch| match *ch {
+        'a' | 'b' => {
+            *ch = '🦀';
+            true
+        }
+        ch => ch != '-',
+    });
+    assert_eq!(s, "x🦀🦀🦀🦀🦀🦀");
+    assert!(s.is_long());
+}
+
+#[test]
+fn retain_mut_only_reallocates_if_a_char_grows_too_much() {
+    let live = std::cell::Cell::new(0);
+    let mut s = SsoString::new_in(CountingAlloc { live: &live });
+    s.push_str("a long string, with commas, that is not short");
+    let ptr = s.as_ptr();
+    let capacity = s.capacity();
+    // 'é' is two bytes, but every 'o' has a removed ',' or ' ' before it to grow into
+    s.retain_mut(|ch| match *ch {
+        ',' | ' ' => false,
+        'o' => {
+            *ch = 'é';
+            true
+        }
+        _ => true,
+    });
+    assert_eq!(s, "alongstringwithcommasthatisnotshort".replace('o', "é"));
+    assert_eq!(s.as_ptr(), ptr);
+    assert_eq!(s.capacity(), capacity);
+
+    // nothing is removed, so there is no room for a longer char
+    let before = s.capacity();
+    s.shrink_to_fit();
+    let full = s.capacity();
+    assert!(full <= before);
+    s.retain_mut(|ch| {
+        if *ch == 'a' {
+            *ch = '🦀';
+        }
+        true
+    });
+    assert!(s.capacity() > full);
+    assert!(s.starts_with('🦀'));
+    assert_eq!(live.get(), 1);
+}
+
+#[test]
+fn retain_ascii_keeps_non_ascii_chars() {
+    let mut s = String::from("a é b ü c");
+    s.retain_ascii(|byte| byte != b' ');
+    assert_eq!(s, "aébüc");
+    s.retain_ascii(|byte| !byte.is_ascii_alphabetic());
+    assert_eq!(s, "éü");
+}
+
+#[test]
+fn retain_leaves_a_valid_string_if_the_predicate_panics() {
+    let mut s = String::from("ab🦀cd, and then some more");
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        s.retain(|ch| match ch {
+            'b' => false,
+            'c' => panic!("oh no"),
+            _ => true,
+        })
+    }));
+    assert!(result.is_err());
+    assert_eq!(s, "a🦀");
+}
+
+#[test]
+fn arc_strings_store_short_strings_inline() {
+    let short = ArcSsoString::from("short");
+    assert!(short.is_short());
+    assert!(matches!(short.tagged(), TaggedArcSsoString::Short(_)));
+    let clone = short.clone();
+    assert_eq!(clone, "short");
+    assert_ne!(clone.as_ptr(), short.as_ptr());
+    assert_eq!(
+        mem::size_of::<Option<ArcSsoString>>(),
+        mem::size_of::<String>()
+    );
+}
+
+#[test]
+fn arc_strings_share_long_strings() {
+    let long = ArcSsoString::from("a string that is too long to be short");
+    let TaggedArcSsoString::Long(inner) = long.tagged() else {
+        panic!("the string does not fit in a short string");
+    };
+    assert_eq!(inner.ref_count(), 1);
+
+    let clone = long.clone();
+    assert_eq!(clone.as_ptr(), long.as_ptr());
+    assert_eq!(inner.ref_count(), 2);
+    drop(clone);
+    assert_eq!(inner.ref_count(), 1);
+    assert_eq!(long, "a string that is too long to be short");
+}
+
+#[test]
+fn make_mut_copies_shared_strings() {
+    let mut long = ArcSsoString::from("a string that is too long to be short");
+    let ptr = long.as_ptr();
+    long.make_mut().make_ascii_uppercase();
+    assert_eq!(long.as_ptr(), ptr);
+
+    let shared = long.clone();
+    long.make_mut()[..1].make_ascii_lowercase();
+    assert_ne!(long.as_ptr(), ptr);
+    assert_eq!(long, "a STRING THAT IS TOO LONG TO BE SHORT");
+    assert_eq!(shared, "A STRING THAT IS TOO LONG TO BE SHORT");
+
+    let mut short = ArcSsoString::from("short");
+    short.make_mut().make_ascii_uppercase();
+    assert_eq!(short, "SHORT");
+}
+
+#[test]
+fn arc_strings_convert_to_and_from_sso_strings() {
+    let mut s = String::from(ArcSsoString::from("short"));
+    assert!(s.is_short());
+    s.push_str(", and then some more");
+    let arc = ArcSsoString::from(s);
+    assert!(arc.is_long());
+    assert_eq!(String::from(arc.clone()), arc.as_str());
+}
+
+#[test]
+fn into_mut_only_copies_shared_strings() {
+    let long = ArcSsoString::from("a string that is too long to be short");
+    let ptr = long.as_ptr();
+    let shared = long.clone();
+    let mut copy = long.into_mut();
+    assert_ne!(copy.as_ptr(), ptr);
+    copy.push_str(", and then some");
+    assert_eq!(copy, "a string that is too long to be short, and then some");
+
+    let mut unique = shared.into_mut();
+    assert!(unique.is_long());
+    assert_eq!(unique.as_ptr(), ptr);
+    assert!(unique.capacity() >= unique.len() + mem::size_of::<usize>());
+    unique.make_ascii_uppercase();
+    unique.push_str("!!");
+    assert_eq!(unique, "A STRING THAT IS TOO LONG TO BE SHORT!!");
+
+    let short = ArcSsoString::from("short").into_mut();
+    assert!(short.is_short());
+    assert_eq!(short, "short");
+}
+
+#[test]
+fn arc_strings_can_be_shared_between_threads() {
+    let long = ArcSsoString::from("a string that is too long to be short");
+    let handles: Vec<_> = (0..4)
+        .map(|_| {
+            let clone = long.clone();
+            std::thread::spawn(move || clone.len())
+        })
+        .collect();
+    for handle in handles {
+        assert_eq!(handle.join().unwrap(), long.len());
+    }
+    let TaggedArcSsoString::Long(inner) = long.tagged() else {
+        panic!("the string does not fit in a short string");
+    };
+    assert_eq!(inner.ref_count(), 1);
+}
+
+#[test]
+fn static_strings_borrow_without_allocating() {
+    const LONG: String = String::from_static("a string that is too long to be short");
+    let s = LONG;
+    assert!(s.is_static());
+    assert!(!s.is_short() && !s.is_long());
+    let TaggedSsoString64::Static(inner) = s.tagged() else {
+        panic!("a static string is its own variant");
+    };
+    assert_eq!(inner.as_str().as_ptr(), s.as_ptr());
+    s.check_invariants();
+    assert_eq!(s, "a string that is too long to be short");
+    assert_eq!(s.capacity(), s.len());
+    assert_eq!(mem::size_of::<Option<String>>(), mem::size_of::<String>());
+    assert!(Some(LONG).is_some_and(|s| s.is_static()));
+    assert_eq!(s.clone().as_ptr(), s.as_ptr());
+    assert_eq!(StdString::from(s), "a string that is too long to be short");
+
+    let live = std::cell::Cell::new(0);
+    let mut s = SsoString::from_static_in("static", CountingAlloc { live: &live });
+    s.shrink_to_fit();
+    assert!(s.is_static());
+    s.clear();
+    assert!(s.is_short() && s.is_empty());
+    assert_eq!(live.get(), 0);
+}
+
+#[test]
+fn long_strings_are_never_bigger_than_max_capacity() {
+    assert!(LongString::try_with_capacity(LongString::MAX_CAPACITY + 1).is_err());
+    let mut s = String::from("a string that is too long to be short");
+    assert!(s.try_reserve(LongString::MAX_CAPACITY).is_err());
+    s.check_invariants();
+}
+
+#[test]
+fn static_strings_become_owned_when_changed() {
+    let live = std::cell::Cell::new(0);
+    let text = "a string that is too long to be short";
+    let mut long = SsoString::from_static_in(text, CountingAlloc { live: &live });
+    long.push('!');
+    assert!(long.is_long());
+    assert_eq!(live.get(), 1);
+    assert_eq!(long, "a string that is too long to be short!");
+    assert_ne!(long.as_ptr(), text.as_ptr());
+
+    let mut short = String::from_static("static");
+    short.make_ascii_uppercase();
+    assert!(short.is_short());
+    assert_eq!(short, "STATIC");
+
+    let mut truncated = String::from_static(text);
+    truncated.truncate(1);
+    assert!(truncated.is_static());
+    assert_eq!(truncated, "a");
+    truncated.push('b');
+    assert!(truncated.is_short());
+    assert_eq!(truncated, "ab");
+}
+
+#[test]
+fn short_strings_are_built_in_const_contexts() {
+    static EMPTY: String = String::new();
+    static NAME: String = crate::short_string!("Gregory");
+    const TOO_LONG: Option<String> =
+        String::from_short_str("a string that is too long to be short");
+    const LEN: usize = NAME.len();
+
+    assert!(EMPTY.is_short() && EMPTY.is_empty());
+    assert_eq!(NAME.as_str(), "Gregory");
+    assert_eq!(LEN, 7);
+    const { assert!(NAME.is_short()) };
+    assert!(TOO_LONG.is_none());
+
+    let mut name = NAME.clone();
+    name.push_str(" :)");
+    assert_eq!(name, "Gregory :)");
+}
+
+#[test]
+fn short_string_from_str_respects_capacity() {
+    let full = "a".repeat(ShortString::MAX_CAPACITY);
+    let short = ShortString::try_from_str(&full).unwrap();
+    assert_eq!(short.as_str(), full);
+    assert_eq!(short.len(), ShortString::MAX_CAPACITY);
+    assert!(ShortString::try_from_str(&format!("{full}a")).is_none());
+
+    let s = String::from_short_str("héllo").unwrap();
+    assert_eq!(s, String::from("héllo"));
+}
+
+#[test]
+fn interner_keeps_short_strings_inline() {
+    let mut interner = Interner::new();
+    let a = interner.get_or_intern("ident");
+    let b = interner.get_or_intern("ident");
+    assert_eq!(a, b);
+    assert!(a.is_inline());
+    assert_eq!(a.as_inline_str(), Some("ident"));
+    assert_eq!(interner.resolve(&a), "ident");
+
+    let long_text = "an identifier that is too long to be inline";
+    let long = interner.get_or_intern(long_text);
+    assert!(!long.is_inline());
+    assert_eq!(long.as_inline_str(), None);
+    assert_eq!(long.len(), long_text.len());
+    assert_eq!(interner.resolve(&long), long_text);
+    let owned: String = interner.resolve_sso(&long).to_owned();
+    assert_eq!(owned, long_text);
+    assert_eq!(interner.resolve_sso(&a).to_owned(), "ident");
+    assert_eq!(interner.get_or_intern(&StdString::from(long_text)), long);
+    assert_ne!(long, a);
+
+    assert_eq!(interner.len(), 2);
+    assert_eq!(interner.get("ident"), Some(a));
+    assert_eq!(interner.get("other"), None);
+    assert_eq!(interner.get("another identifier that is too long"), None);
+    assert_eq!(mem::size_of::<Option<Symbol>>(), mem::size_of::<String>());
+}
+
+#[test]
+fn interner_arena_strings_stay_put() {
+    let mut interner = Interner::new();
+    let texts: Vec<StdString> = (0..200)
+        .map(|i| format!("a long identifier with a number {i}"))
+        .chain([StdString::from("h"), "x".repeat(5000)])
+        .collect();
+    let symbols: Vec<Symbol> = texts.iter().map(|t| interner.get_or_intern(t)).collect();
+    let first = interner.resolve(&symbols[0]).as_ptr();
+
+    for (symbol, text) in symbols.iter().zip(&texts) {
+        assert_eq!(interner.resolve(symbol), text);
+    }
+    assert_eq!(interner.resolve(&symbols[0]).as_ptr(), first);
+
+    let iterated: Vec<&str> = interner.iter().map(|(_, s)| s).collect();
+    assert_eq!(iterated, texts);
+    assert_eq!(interner.iter().len(), texts.len());
+}
+
+#[test]
+fn sync_interner_is_shared_between_threads() {
+    let interner = SyncInterner::new();
+    let words = ["short", "a long identifier shared by threads"];
+    let symbols: Vec<Vec<Symbol>> = std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..4)
+            .map(|_| scope.spawn(|| words.map(|w| interner.get_or_intern(w)).to_vec()))
+            .collect();
+        handles.into_iter().map(|h| h.join().unwrap()).collect()
+    });
+
+    for other in &symbols[1..] {
+        assert_eq!(other, &symbols[0]);
+    }
+    assert_eq!(interner.resolve(&symbols[0][1]), words[1]);
+    assert_eq!(&**interner.resolve_sso(&symbols[0][1]), words[1]);
+    assert_eq!(interner.len(), 2);
+    assert_eq!(interner.read().iter().count(), 2);
+    assert_eq!(interner.into_inner().resolve(&symbols[0][0]), "short");
+}
+
+#[test]
+fn bytes_grow_like_a_vec() {
+    let mut bytes = SsoBytes::new();
+    bytes.extend_from_slice(b"GET /");
+    bytes.push(0xFF);
+    assert!(bytes.is_short());
+    assert_eq!(bytes, b"GET /\xFF");
+
+    bytes.insert(0, b'>');
+    assert_eq!(bytes.remove(1), b'G');
+    assert_eq!(bytes.pop(), Some(0xFF));
+    assert_eq!(bytes, b">ET /");
+
+    bytes.resize(40, 0);
+    assert!(bytes.is_long());
+    assert_eq!(bytes.len(), 40);
+    bytes.retain(|&b| b != 0);
+    assert_eq!(bytes, b">ET /");
+    bytes.shrink_to_fit();
+    assert!(bytes.is_short());
+
+    let mut long = SsoBytes::with_capacity(100);
+    long.extend_from_slice(&[0; 40]);
+    long.shrink_to(200);
+    assert_eq!(long.capacity(), 100);
+    long.shrink_to(50);
+    assert_eq!(long.capacity(), 50);
+
+    let tail = bytes.split_off(3);
+    assert_eq!(tail, b" /");
+    assert_eq!(bytes.iter().rev().copied().collect::<SsoBytes>(), b"TE>");
+    assert_eq!(mem::size_of::<SsoBytes>(), mem::size_of::<Vec<u8>>());
+}
+
+#[test]
+fn bytes_adopt_and_hand_over_vec_buffers() {
+    let vec = vec![0xC0; 64];
+    let ptr = vec.as_ptr();
+    let bytes = SsoBytes::from(vec);
+    assert!(bytes.is_long());
+    assert_eq!(bytes.as_ptr(), ptr);
+    let vec = Vec::from(bytes);
+    assert_eq!(vec.as_ptr(), ptr);
+    assert_eq!(vec, [0xC0; 64]);
+
+    let short = SsoBytes::from(&[1, 2, 3]);
+    assert!(short.is_short());
+    assert_eq!(Vec::from(short), [1, 2, 3]);
+}
+
+#[test]
+fn bytes_convert_to_strings_with_validation() {
+    let long = "a string that is too long to be short";
+    let bytes = String::from(long).into_bytes();
+    let ptr = bytes.as_ptr();
+    let s = bytes.into_string().unwrap();
+    assert_eq!(s, long);
+    assert_eq!(s.as_ptr(), ptr);
+
+    let err = SsoBytes::from(b"ab\xFF").into_string().unwrap_err();
+    assert_eq!(err.utf8_error().valid_up_to(), 2);
+    assert_eq!(err.into_bytes(), b"ab\xFF");
+}
+
+#[test]
+fn as_mut_vec_changes_the_bytes_of_a_string() {
+    let mut s = String::from("hello");
+    // SAFETY: only ASCII bytes are written
+    unsafe {
+        let bytes = s.as_mut_vec();
+        bytes[0] = b'j';
+        bytes.extend_from_slice(b", world, and everyone in it");
+    }
+    assert!(s.is_long());
+    assert_eq!(s, "jello, world, and everyone in it");
+
+    let mut out = SsoBytes::new();
+    std::io::Write::write_all(&mut out, s.as_bytes()).unwrap();
+    assert_eq!(out, s.as_bytes());
+}
+
+/// Counts how many times it has been dropped, to catch elements that are leaked or dropped twice
+struct DropCounter<'a>(&'a Cell<usize>);
+
+impl Drop for DropCounter<'_> {
+    fn drop(&mut self) {
+        self.0.set(self.0.get() + 1);
+    }
+}
+
+#[test]
+fn sso_vec_spills_to_the_heap_and_back() {
+    let mut vec = SsoVec::<u32, 4>::new();
+    vec.extend([1, 2, 3]);
+    assert!(vec.is_short());
+    assert_eq!(vec.capacity(), 4);
+
+    vec.insert(0, 0);
+    vec.push(4);
+    assert!(vec.is_long());
+    assert_eq!(vec, [0, 1, 2, 3, 4]);
+    assert_eq!(vec.swap_remove(0), 0);
+    assert_eq!(vec.remove(2), 2);
+    vec.retain(|&x| x != 3);
+    assert_eq!(vec, [4, 1]);
+
+    vec.shrink_to_fit();
+    assert!(vec.is_short());
+    assert_eq!(vec.pop(), Some(1));
+    assert_eq!(vec.clone(), [4]);
+    assert_eq!(mem::size_of::<SsoVec<u8, 0>>(), mem::size_of::<Vec<u8>>());
+}
+
+#[test]
+fn sso_vec_drops_every_element_once() {
+    let drops = Cell::new(0);
+    let mut vec = SsoVec::<DropCounter, 2>::new();
+    vec.push(DropCounter(&drops));
+    vec.push(DropCounter(&drops));
+    vec.truncate(1);
+    assert_eq!(drops.get(), 1);
+
+    (0..8).for_each(|_| vec.push(DropCounter(&drops)));
+    assert!(vec.is_long());
+    let mut keep = false;
+    vec.retain(|_| {
+        keep = !keep;
+        keep
+    });
+    assert_eq!(drops.get(), 5);
+
+    let mut iter = vec.into_iter();
+    drop(iter.next());
+    drop(iter.next_back());
+    assert_eq!(drops.get(), 7);
+    drop(iter);
+    assert_eq!(drops.get(), 10);
+}
+
+#[test]
+fn sso_vec_adopts_and_hands_over_vec_buffers() {
+    let vec: Vec<StdString> = (0..10).map(|i| i.to_string()).collect();
+    let ptr = vec.as_ptr();
+    let sso = SsoVec::<StdString, 4>::from(vec);
+    assert!(sso.is_long());
+    assert_eq!(sso.as_ptr(), ptr);
+    let vec = Vec::from(sso);
+    assert_eq!(vec.as_ptr(), ptr);
+    assert_eq!(vec.len(), 10);
+
+    let short = SsoVec::<StdString, 4>::from(vec![StdString::from("a")]);
+    assert!(short.is_short());
+    assert_eq!(Vec::from(short), ["a"]);
+}
+
+#[test]
+fn sso_vec_never_allocates_zero_sized_elements() {
+    let mut vec = SsoVec::<(), 2>::new();
+    vec.extend([(); 3]);
+    assert!(vec.is_long());
+    assert_eq!(vec.capacity(), usize::MAX);
+    assert_eq!(vec.len(), 3);
+    vec.shrink_to_fit();
+    assert_eq!(vec.into_iter().count(), 3);
+}
+
+#[test]
+#[cfg(unix)]
+fn os_strings_keep_short_names_inline() {
+    use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+    let mut name = SsoOsString::from("PATH");
+    name.push(OsStr::from_bytes(b"\xFF"));
+    assert!(name.is_short());
+    assert_eq!(name.as_bytes(), b"PATH\xFF");
+    let name = name.into_string().unwrap_err();
+
+    let long = std::ffi::OsString::from("a name that is too long to be short");
+    let ptr = long.as_bytes().as_ptr();
+    let sso = SsoOsString::from(long);
+    assert!(sso.is_long());
+    assert_eq!(sso.as_bytes().as_ptr(), ptr);
+    let long = std::ffi::OsString::from(sso);
+    assert_eq!(long.as_bytes().as_ptr(), ptr);
+    assert_ne!(name, long);
+}
+
+#[test]
+#[cfg(unix)]
+fn paths_are_edited_like_std_paths() {
+    type StdPathBuf = std::path::PathBuf;
+
+    let cases: [(&str, &str); 6] = [
+        ("", "a"),
+        ("a", "b"),
+        ("a/", "b"),
+        ("a/b", "/c"),
+        ("dir/file.tar.gz", "x"),
+        ("/", ".hidden"),
+    ];
+    for (base, other) in cases {
+        let mut sso = SsoPathBuf::from(base);
+        let mut std = StdPathBuf::from(base);
+        sso.push(other);
+        std.push(other);
+        assert_eq!(sso, std);
+        assert_eq!(sso.as_os_str(), std.as_os_str());
+
+        assert_eq!(sso.set_extension("rs"), std.set_extension("rs"));
+        assert_eq!(sso.as_os_str(), std.as_os_str());
+        assert_eq!(sso.set_extension(""), std.set_extension(""));
+        assert_eq!(sso.as_os_str(), std.as_os_str());
+
+        sso.set_file_name("name.txt");
+        std.set_file_name("name.txt");
+        assert_eq!(sso.as_os_str(), std.as_os_str());
+        assert_eq!(sso.file_name(), std.file_name());
+
+        assert_eq!(sso.pop(), std.pop());
+        assert_eq!(sso.as_os_str(), std.as_os_str());
+    }
+
+    let mut short = SsoPathBuf::from("a.txt");
+    assert!(short.set_extension("md"));
+    assert_eq!(short.as_os_str(), "a.md");
+    assert!(short.is_short());
+}
+
+#[test]
+#[cfg(unix)]
+fn path_bufs_adopt_and_hand_over_std_buffers() {
+    let std = std::path::PathBuf::from("/usr/share/some/deeply/nested/directory");
+    let ptr = std.as_os_str().as_encoded_bytes().as_ptr();
+    let sso = SsoPathBuf::from(std);
+    assert!(sso.is_long());
+    assert_eq!(sso.extension(), None);
+    let std = std::path::PathBuf::from(sso);
+    assert_eq!(std.as_os_str().as_encoded_bytes().as_ptr(), ptr);
+
+    let short: SsoPathBuf = ["src", "lib.rs"].into_iter().collect();
+    assert!(short.is_short());
+    assert_eq!(short, std::path::Path::new("src/lib.rs"));
+}
+
+#[test]
+fn char_counts_follow_every_change() {
+    let mut s = CharCountedString::from("hello");
+    assert!(s.is_ascii());
+    assert_eq!(s.char_at(1), Some('e'));
+
+    s.push_str(", wörld");
+    s.insert(0, '¡');
+    assert!(!s.is_ascii());
+    assert_eq!(s.char_count(), s.chars().count());
+    assert_eq!(s.char_at(8), Some('w'));
+
+    s.replace_range(..3, "H");
+    s.extend_from_within(1..5);
+    assert_eq!(s, "Hello, wörldello");
+    assert_eq!(s.char_count(), 16);
+
+    let tail = s.split_off(5);
+    assert_eq!(tail.char_count(), 11);
+    assert_eq!(s.pop(), Some('o'));
+    s.truncate(1);
+    s.retain(|c| c != 'H');
+    assert!(s.is_empty());
+    assert_eq!(s.char_count(), 0);
+
+    let collected: CharCountedString = "abc".chars().collect();
+    assert!(collected.is_short());
+    assert_eq!(collected.char_count(), 3);
+}
+
+#[test]
+fn char_counts_survive_direct_changes() {
+    let mut s = CharCountedString::from("a string that is too long to be short");
+    s.with_mut(|inner| inner.replace_range(..1, "ünïcödé"));
+    assert!(s.is_long());
+    assert_eq!(s.char_count(), s.chars().count());
+
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        s.with_mut(|inner| {
+            inner.clear();
+            panic!("halfway through");
+        })
+    }));
+    assert!(result.is_err());
+    assert_eq!(s.char_count(), 0);
+    assert!(s.is_ascii());
+}
+
+#[test]
+#[cfg(feature = "serde")]
+fn strings_serialize_like_str() {
+    use serde_test::{assert_de_tokens, assert_ser_tokens, assert_tokens, Token};
+
+    assert_tokens(&String::from("short"), &[Token::Str("short")]);
+    assert_ser_tokens(SsoStr::from_str("borrowed"), &[Token::Str("borrowed")]);
+
+    let long = "a string that is too long to be short";
+    assert_de_tokens(&String::from(long), &[Token::BorrowedStr(long)]);
+    assert_de_tokens(&String::from(long), &[Token::String(long)]);
+    assert_de_tokens(&String::from("bytes"), &[Token::Bytes(b"bytes")]);
+    assert_de_tokens(&SsoStr::from_str(long), &[Token::BorrowedStr(long)]);
+}
+
+#[test]
+#[cfg(feature = "serde")]
+fn deserialized_strings_adopt_owned_buffers() {
+    use serde::de::value::{Error, StrDeserializer, StringDeserializer};
+    use serde::{de::IntoDeserializer, Deserialize};
+
+    let short = String::deserialize(StrDeserializer::<Error>::new("inline")).unwrap();
+    assert!(short.is_short());
+
+    let long = StdString::from("a string that is too long to be short");
+    let ptr = long.as_ptr();
+    let de: StringDeserializer<Error> = long.into_deserializer();
+    let long = String::deserialize(de).unwrap();
+    assert!(long.is_long());
+    assert_eq!(long.as_ptr(), ptr);
+}
+
+#[test]
+fn archived_strings_are_read_in_place() {
+    let long = "a string that is too long to be stored inline";
+    let mut writer = ArchiveWriter::new();
+    let short_pos = writer.write_str("inline");
+    let long_pos = writer.write_str(long);
+    let archive = writer.into_bytes();
+
+    let short = ArchivedSsoString::access(&archive, short_pos).unwrap();
+    assert!(short.is_inline());
+    assert_eq!(short, "inline");
+    assert!(short.to_sso_string().is_short());
+
+    let archived = ArchivedSsoString::access(&archive, long_pos).unwrap();
+    assert!(!archived.is_inline());
+    assert_eq!(&*archived, long);
+    assert!(archive.as_ptr_range().contains(&archived.as_ptr()));
+}
+
+#[test]
+fn archived_string_tables_are_checked_once() {
+    let strings = [
+        "a",
+        "",
+        "a string that is too long to be stored inline",
+        "ü",
+    ];
+    let mut writer = ArchiveWriter::from_vec(b"magic".to_vec());
+    let pos = writer.write_table(strings);
+    let archive = writer.into_bytes();
+
+    let table = ArchivedStringTable::access(&archive, pos, strings.len()).unwrap();
+    assert_eq!(table.len(), 4);
+    assert!(table.iter().map(|s| s.as_str()).eq(strings));
+    assert_eq!(table.get(4), None);
+    assert_eq!(
+        ArchivedStringTable::access(&archive, pos, 5).unwrap_err(),
+        CheckArchiveError::OutOfBounds
+    );
+}
+
+#[test]
+fn invalid_archived_strings_are_rejected() {
+    let check = |archive: &[u8]| ArchivedSsoString::access(archive, 0).map(|s| s.to_sso_string());
+
+    let mut short = [0; 24];
+    short[0] = 24 << 1 | 1;
+    assert_eq!(check(&short), Err(CheckArchiveError::InvalidLength));
+    short[0] = 1 << 1 | 1;
+    short[1] = 0xFF;
+    assert!(matches!(
+        check(&short),
+        Err(CheckArchiveError::InvalidUtf8(_))
+    ));
+
+    let mut long = [0; 54];
+    long[0] = 31 << 1;
+    long[8] = 24;
+    assert_eq!(check(&long), Err(CheckArchiveError::OutOfBounds));
+    long[8..16].copy_from_slice(&(-8i64).to_le_bytes());
+    assert_eq!(check(&long), Err(CheckArchiveError::OutOfBounds));
+    long[0] = 23 << 1;
+    long[8..16].copy_from_slice(&24i64.to_le_bytes());
+    assert_eq!(check(&long), Err(CheckArchiveError::InvalidLength));
+    long[0] = 30 << 1;
+    long[23] = 1;
+    assert_eq!(check(&long), Err(CheckArchiveError::NonZeroPadding));
+    long[23] = 0;
+    assert_eq!(check(&long).unwrap().as_bytes(), &long[24..]);
+    assert_eq!(check(&long[..53]), Err(CheckArchiveError::OutOfBounds));
+}
+
+#[test]
+fn check_invariants_accepts_every_kind_of_string() {
+    let mut s = String::new();
+    s.check_invariants();
+    s.push_str("short");
+    s.check_invariants();
+    s.push_str(" and then long enough to be upgraded");
+    assert!(s.is_long());
+    s.check_invariants();
+    s.truncate(0);
+    s.shrink_to_fit();
+    s.check_invariants();
+    String::from_static("a static string that is long").check_invariants();
+    crate::StringN::<4>::from("four").check_invariants();
+}
+
+#[test]
+#[should_panic(expected = "not utf-8")]
+fn check_invariants_rejects_invalid_utf8() {
+    let mut s = String::from("utf-8");
+    // SAFETY: the string is only checked, and never read as a `str`
+    unsafe { s.as_mut_vec()[0] = 0xFF };
+    s.check_invariants();
+}
+
+#[test]
+#[cfg_attr(not(any(debug_assertions, feature = "strict-invariants")), ignore)]
+#[should_panic(expected = "larger than the capacity")]
+fn layout_is_checked_after_every_change() {
+    let mut s = String::from("short");
+    // SAFETY: not safe at all, but the length is checked before anything reads it
+    unsafe { s.set_len(100) };
+}
+
+#[test]
+#[cfg(feature = "derive")]
+fn unsafe_fields_numbers_fields_and_checks_the_proof() {
+    use crate::unsafe_field::{unsafe_fields, Proof, UnsafeField};
+
+    #[unsafe_fields]
+    struct Span {
+        #[unsafe_field(invariant = "`start <= end`")]
+        start: usize,
+        #[unsafe_field(invariant = "`start <= end`")]
+        #[unsafe_field(invariant = "`end <= text.len()`")]
+        end: usize,
+        text: &'static str,
+    }
+
+    let check = |start: &usize, end: &usize, text: &&str| {
+        // SAFETY: both invariants were just checked
+        (start <= end && *end <= text.len()).then(|| unsafe { Proof::new() })
+    };
+    let span = Span::from_fields(1, 3, "abcd", check).unwrap();
+    let start: &UnsafeField<usize, 0> = &span.start;
+    let end: &UnsafeField<usize, 1> = &span.end;
+    assert_eq!(&span.text[*start.get()..*end.get()], "bc");
+    assert!(Span::from_fields(3, 1, "abcd", check).is_none());
+    assert!(Span::from_fields(1, 5, "abcd", check).is_none());
+}
+
+#[test]
+#[cfg(feature = "derive")]
+fn derive_unsafe_fields_on_numbered_generic_fields() {
+    use crate::unsafe_field::{Proof, UnsafeField, UnsafeFields};
+
+    #[derive(UnsafeFields)]
+    #[unsafe_field(constructor = "try_new")]
+    struct NonEmpty<T> {
+        #[unsafe_field(invariant = "is not empty")]
+        items: UnsafeField<Vec<T>, 0>,
+    }
+
+    let non_empty = |items: &Vec<char>| {
+        // SAFETY: the emptiness was just checked
+        (!items.is_empty()).then(|| unsafe { Proof::new() })
+    };
+    let first = NonEmpty::try_new(vec!['a', 'b'], non_empty).unwrap();
+    assert_eq!(first.items.get()[0], 'a');
+    assert!(NonEmpty::try_new(Vec::new(), non_empty).is_none());
+}
+
+#[test]
+#[cfg(feature = "derive")]
+fn unsafe_fields_may_be_called_proof() {
+    use crate::unsafe_field::{unsafe_fields, Proof};
+
+    #[unsafe_fields]
+    struct Claim {
+        #[unsafe_field(invariant = "`proof` is not zero")]
+        proof: u32,
+    }
+
+    let non_zero = |proof: &u32| {
+        // SAFETY: the zero check was just done
+        (*proof != 0).then(|| unsafe { Proof::new() })
+    };
+    assert_eq!(*Claim::from_fields(4, non_zero).unwrap().proof.get(), 4);
+    assert!(Claim::from_fields(0, non_zero).is_none());
+}
+
+#[test]
+fn simultaneous_unsafe_assignment_returns_old_values() {
+    struct Counted {
+        /// # Safety
+        /// - `len` is the length of `items`
+        items: UnsafeField<Vec<u8>, 0>,
+        len: UnsafeField<usize, 1>,
+        generation: UnsafeField<u32, 2>,
+    }
+
+    // SAFETY: `len` is the length of `items`
+    let mut counted = unsafe {
+        Counted {
+            items: UnsafeField::new(vec![1]),
+            len: UnsafeField::new(1),
+            generation: UnsafeField::new(0),
+        }
+    };
+    // SAFETY: `len` is the length of the new `items`
+    let ((items, len), generation) = unsafe {
+        SimultaneousUnsafeAssignment
+            .with(&mut counted.items, vec![1, 2, 3])
+            .with(&mut counted.len, 3)
+            .with(&mut counted.generation, 1)
+            .replace_all()
+    };
+    assert_eq!((items, len, generation), (vec![1], 1, 0));
+    assert_eq!(counted.items.get(), &[1, 2, 3]);
+    assert_eq!(*counted.len.get(), 3);
+    assert_eq!(*counted.generation.get(), 1);
+
+    // SAFETY: `len` is the length of the new `items`
+    unsafe {
+        SimultaneousUnsafeAssignment
+            .with(&mut counted.items, Vec::new())
+            .with(&mut counted.len, 0)
+            .set_all();
+    }
+    assert!(counted.items.get().is_empty());
+    assert_eq!(*counted.len.get(), 0);
+}
+
+#[test]
+fn long_string_realloc_and_free_swap_buffers() {
+    let mut long = LongString::from_str("a long string that has to be allocated");
+    let len = long.len();
+    long.realloc(100);
+    assert!(long.capacity() >= len + 100);
+    assert_eq!(long.as_str(), "a long string that has to be allocated");
+    long.check_invariants();
+
+    long.free();
+    assert_eq!((long.len(), long.capacity()), (0, 0));
+    assert_eq!(long.as_str(), "");
+    long.check_invariants();
+    // freeing again does nothing
+    long.free();
+    assert_eq!(long.capacity(), 0);
+}
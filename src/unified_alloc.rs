@@ -1,10 +1,97 @@
-#[cfg(feature = "nightly")]
-use std::alloc::{Allocator, Global};
 use std::{
     alloc::Layout,
+    collections::TryReserveError,
     ptr::{self, NonNull},
 };
 
+#[cfg(feature = "nightly")]
+pub use std::alloc::{AllocError, Allocator, Global};
+
+#[cfg(not(feature = "nightly"))]
+pub use allocator_api_shim::{AllocError, Allocator, Global};
+
+/// A stable stand-in for the parts of the `allocator_api` feature that we use, in the same spirit
+/// as the `allocator-api2` crate. The trait has the same contract as `std::alloc::Allocator`, so
+/// everything written against it keeps working when the `nightly` feature swaps in the real one.
+#[cfg(not(feature = "nightly"))]
+mod allocator_api_shim {
+    use std::{alloc::Layout, fmt, ptr::NonNull};
+
+    /// The error returned by [`Allocator::allocate`] when the allocator cannot satisfy a request
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct AllocError;
+
+    impl fmt::Display for AllocError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("memory allocation failed")
+        }
+    }
+
+    impl std::error::Error for AllocError {}
+
+    /// An implementation of `Allocator` can allocate and deallocate arbitrary blocks of data.
+    ///
+    /// # Safety
+    /// The same contract as `std::alloc::Allocator`:
+    /// - memory blocks returned by an allocator must point to valid memory and retain their
+    ///   validity until they are deallocated, or the allocator (and all of its copies) is dropped
+    /// - moving or copying the allocator must not invalidate the blocks it returned
+    /// - any pointer to a block returned by an allocator may be passed to any other method of that
+    ///   allocator, or one of its copies
+    pub unsafe trait Allocator {
+        /// Attempts to allocate a block of memory that fits `layout`. The returned block may be
+        /// larger than `layout.size()`.
+        fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError>;
+
+        /// Deallocates the memory referenced by `ptr`.
+        ///
+        /// # Safety
+        /// - `ptr` must denote a block of memory currently allocated via this allocator
+        /// - `layout` must fit that block of memory
+        unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout);
+    }
+
+    /// The global memory allocator, the same one that `std` uses
+    #[derive(Clone, Copy, Debug, Default)]
+    pub struct Global;
+
+    // SAFETY: we just forward to the global allocator, which upholds all of these invariants
+    unsafe impl Allocator for Global {
+        fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+            if layout.size() == 0 {
+                // SAFETY: the alignment of a layout is never zero
+                let dangling = unsafe {
+                    NonNull::new_unchecked(std::ptr::without_provenance_mut(layout.align()))
+                };
+                return Ok(NonNull::slice_from_raw_parts(dangling, 0));
+            }
+            // SAFETY: layout has a non-zero size
+            let data = unsafe { std::alloc::alloc(layout) };
+            let data = NonNull::new(data).ok_or(AllocError)?;
+            Ok(NonNull::slice_from_raw_parts(data, layout.size()))
+        }
+
+        unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+            if layout.size() != 0 {
+                // SAFETY: ptr was allocated by `std::alloc::alloc` with `layout` (caller contract)
+                unsafe { std::alloc::dealloc(ptr.as_ptr(), layout) }
+            }
+        }
+    }
+
+    // SAFETY: a reference forwards everything to the allocator it points to
+    unsafe impl<A: Allocator + ?Sized> Allocator for &A {
+        fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+            (**self).allocate(layout)
+        }
+
+        unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+            // SAFETY: caller contract is passed on
+            unsafe { (**self).deallocate(ptr, layout) }
+        }
+    }
+}
+
 /// function bodies from the alloc_layout_extra feature that I want to use on stable. Very much
 /// robbery on my part, so credit to whoever wrote these originally
 mod alloc_layout_extra {
@@ -50,67 +137,102 @@ mod alloc_layout_extra {
     }
 }
 
-/// guarantees layout is non-zero
-pub fn new_slice_layout<T>(capacity: usize) -> (Layout, usize) {
+/// A [`TryReserveError`] for a capacity that cannot be represented by a [`Layout`]
+pub fn capacity_overflow() -> TryReserveError {
+    Vec::<u8>::new()
+        .try_reserve_exact(usize::MAX)
+        .expect_err("usize::MAX bytes can never be reserved")
+}
+
+/// A [`TryReserveError`] for an allocator that failed to allocate `layout`
+#[cfg(feature = "nightly")]
+pub fn alloc_error(layout: Layout) -> TryReserveError {
+    std::collections::TryReserveErrorKind::AllocError {
+        layout,
+        non_exhaustive: (),
+    }
+    .into()
+}
+
+/// A [`TryReserveError`] for an allocator that failed to allocate `layout`.
+///
+/// The kind of a [`TryReserveError`] cannot be constructed on stable, and the only one that the
+/// standard library hands out without allocating is a capacity overflow, so that is what an
+/// allocation failure is reported as. Use the `nightly` feature to get the real kind and `layout`.
+#[cfg(not(feature = "nightly"))]
+pub fn alloc_error(_layout: Layout) -> TryReserveError {
+    capacity_overflow()
+}
+
+/// guarantees layout is non-zero, returns a [`capacity_overflow`] error if `capacity` elements of
+/// `T` cannot fit in a [`Layout`]
+pub fn try_new_slice_layout<T>(capacity: usize) -> Result<(Layout, usize), TryReserveError> {
     let (layout, len) =
-        alloc_layout_extra::repeat(&Layout::new::<T>(), capacity).expect("capacity is valid");
+        alloc_layout_extra::repeat(&Layout::new::<T>(), capacity).ok_or_else(capacity_overflow)?;
     if layout.size() == 0 {
         panic!("cannot allocate ZST");
     }
 
-    (layout, len)
+    Ok((layout, len))
+}
+
+/// guarantees layout is non-zero
+pub fn new_slice_layout<T>(capacity: usize) -> (Layout, usize) {
+    try_new_slice_layout::<T>(capacity).expect("capacity is valid")
 }
 
-/// To deallocate this, make sure you multiply by `mem::size_of<T>()`.
+/// Allocates a slice of at least `count` elements of `T`, the length of the slice is the exact
+/// number of elements that fit in the allocation.
 pub fn alloc_slice<T>(count: usize) -> NonNull<[T]> {
-    let (layout, _) = new_slice_layout::<T>(count);
-    let (data, byte_count) = {
-        #[cfg(feature = "nightly")]
-        {
-            let data = Global
-                .allocate(layout)
-                .unwrap_or_else(|_| panic!("allocation error"));
-            (data.cast(), data.len())
-        }
-        #[cfg(not(feature = "nightly"))]
-        {
-            use std::alloc::alloc;
-            // SAFETY: new_slice_layout guarantees that layout is non-zero
-            let data = unsafe { alloc(layout) };
-            let Some(data) = NonNull::new(data) else {
-                panic!("allocation error")
-            };
-            (data, layout.size())
-        }
-    };
-    // offset is the size of each allocation with padding
-    // let capacity = byte_count / offset;
+    try_alloc_slice::<T>(count).unwrap_or_else(|err| panic!("allocation error: {err}"))
+}
+
+/// The same as [`alloc_slice`], but returns an error instead of panicking if the layout is invalid
+/// or the allocator fails.
+pub fn try_alloc_slice<T>(count: usize) -> Result<NonNull<[T]>, TryReserveError> {
+    try_alloc_slice_in::<T, _>(count, &Global)
+}
+
+/// The same as [`try_alloc_slice`], but allocates with `alloc` instead of [`Global`]
+pub fn try_alloc_slice_in<T, A: Allocator + ?Sized>(
+    count: usize,
+    alloc: &A,
+) -> Result<NonNull<[T]>, TryReserveError> {
+    let (layout, stride) = try_new_slice_layout::<T>(count)?;
+    let data = alloc.allocate(layout).map_err(|_| alloc_error(layout))?;
+    // the allocator may hand out more bytes than we asked for, as many elements as fit in them are
+    // ours. The layout of that many elements still fits the block, since it is at least as big as
+    // the layout we asked for, and at most as big as the block.
+    let capacity = data.len() / stride;
+    let data = data.cast::<u8>();
     unsafe {
         // SAFETY:
         // - this should be valid for &'static mut [MaybeUninit], so we need to validate the safety
         //   contract of the creation of that type.
-        // - data
-        let raw = ptr::slice_from_raw_parts_mut(data.as_ptr() as *mut _, byte_count);
+        // - the block is `capacity * stride` bytes or more, and aligned for `T`
+        let raw = ptr::slice_from_raw_parts_mut(data.as_ptr() as *mut T, capacity);
         // SAFETY: ptr is non-null, since `data.as_ptr()` is non-null
-        NonNull::new_unchecked(raw)
+        Ok(NonNull::new_unchecked(raw))
     }
 }
 
 /// # Safety
 /// must be a slice allocated by `unified_alloc::alloc_slice()`
 pub unsafe fn dealloc_slice<T>(ptr: NonNull<[T]>) {
+    // SAFETY: `alloc_slice` allocates with `Global`, so the contract is the same
+    unsafe { dealloc_slice_in(ptr, &Global) }
+}
+
+/// # Safety
+/// must be a slice allocated by `unified_alloc::try_alloc_slice_in()` with the same `alloc` (or a
+/// copy of it)
+pub unsafe fn dealloc_slice_in<T, A: Allocator + ?Sized>(ptr: NonNull<[T]>, alloc: &A) {
     let layout = new_slice_layout::<T>(ptr.len()).0;
-    #[cfg(feature = "nightly")]
-    {
-        Global.deallocate(ptr.cast(), layout);
-    }
-    #[cfg(not(feature = "nightly"))]
-    {
-        use std::alloc::dealloc;
-        // SAFETY: layout should be the same layout we made at the beginning, sicne it comes from
-        // the same function
-        unsafe {
-            dealloc(ptr.as_ptr() as *mut _, layout);
-        }
+    // SAFETY:
+    // - ptr was allocated by `alloc` (caller contract)
+    // - layout fits the block, since it is the same layout we made at the beginning, or the
+    //   larger size that the allocator returned to us
+    unsafe {
+        alloc.deallocate(ptr.cast(), layout);
     }
 }
@@ -1,4 +1,9 @@
-use std::ptr::NonNull;
+use std::ptr::{self, NonNull};
+
+/// Declares a struct with unsafe fields, numbered automatically and with their invariants
+/// documented, see the [`olis_string_derive`] crate
+#[cfg(feature = "derive")]
+pub use olis_string_derive::{unsafe_fields, UnsafeFields};
 
 /// Indicates that a field is unsafe to write to, since we have to uphold certain invariants.
 /// Make sure to document them!
@@ -95,9 +100,18 @@ impl<T, const FIELD_INDEX: usize> UnsafeField<T, FIELD_INDEX> {
     pub fn own(self) -> T {
         self.0
     }
+
+    /// The same as [`UnsafeAssign::new`], but usable in a const fn
+    ///
+    /// # Safety
+    /// - must uphold all invariants of the field
+    pub const unsafe fn new(value: T) -> Self {
+        Self(value)
+    }
 }
 
-impl<T, const FIELD_INDEX: usize> UnsafeAssign<T> for UnsafeField<T, FIELD_INDEX> {
+// SAFETY: `get_mut` points at the value that the field owns
+unsafe impl<T, const FIELD_INDEX: usize> UnsafeAssign<T> for UnsafeField<T, FIELD_INDEX> {
     unsafe fn new(value: T) -> Self {
         Self(value)
     }
@@ -111,7 +125,13 @@ impl<T, const FIELD_INDEX: usize> UnsafeAssign<T> for UnsafeField<T, FIELD_INDEX
     }
 }
 
-pub trait UnsafeAssign<T>
+/// A field whose value may only be changed in ways that uphold its invariants
+///
+/// # Safety
+/// - [`UnsafeAssign::get_mut`] must return a pointer to the value owned by `self`, which is valid
+///   for reads and writes for as long as the `&mut self` borrow it was made from, because the
+///   provided [`UnsafeAssign::replace`] writes through it
+pub unsafe trait UnsafeAssign<T>
 where
     Self: Sized,
 {
@@ -128,127 +148,178 @@ where
     unsafe fn set(&mut self, value: T);
 
     /// Gets a raw pointer to the value
-    /// 
+    ///
     /// # Safety
-    /// - msut uphold all invaraints when assigning the pointer
+    /// - must uphold all invariants when assigning through the pointer
     fn get_mut(&mut self) -> NonNull<T>;
+
+    /// Sets the underlying value to `value`, and returns the old value instead of dropping it
+    ///
+    /// # Safety
+    /// - must uphold all invariants of the field
+    unsafe fn replace(&mut self, value: T) -> T {
+        // SAFETY: the pointer points at the value owned by `self` and is valid for reads and writes
+        // (trait contract), and the invariants of the new value are passed to the caller
+        unsafe { ptr::replace(self.get_mut().as_ptr(), value) }
+    }
+}
+
+/// Vouches that the invariants of every unsafe field of `T` hold, for the values that the
+/// constructor generated by [`UnsafeFields`](derive@UnsafeFields) is about to use. The proof
+/// closure of that constructor returns one of these, so the `unsafe` block that makes it is where
+/// the invariants are checked.
+#[cfg(feature = "derive")]
+pub struct Proof<T: ?Sized>(std::marker::PhantomData<fn() -> T>);
+
+#[cfg(feature = "derive")]
+impl<T: ?Sized> Proof<T> {
+    /// # Safety
+    /// - the invariants of every unsafe field of `T` must hold for the values being checked
+    pub const unsafe fn new() -> Self {
+        Self(std::marker::PhantomData)
+    }
+}
+
+/// Assign several [`UnsafeField`] 'simultaneously'.
+///
+/// There might be occasions where we cannot assign multiple fields simultaenously by
+/// reconstructing the struct (though this should be done in most cases). In this case, we
+/// can enforce a slightly lesser form of safety, by upholding invariants "only when the struct"
+/// is read from. This pattern guarantees that we cannot get a `&self` in between writes to
+/// fields, since every field stays mutably borrowed until all of them are assigned.
+///
+/// ```rs
+/// unsafe {
+///     unsafe_field::SimultaneousUnsafeAssignment
+///         .with(&mut foo.field_1, 5)
+///         .with(&mut foo.field_2, 10)
+///         .with(&mut foo.field_3, 15)
+///         .set_all();
+/// }
+/// ```
+///
+/// # Safety
+///
+/// - ensure that all invariants are upheld after all assignments are complete
+/// - you must not rely on the ordering of the assignments, that is, the Unit state should
+///   be the same no matter the order of the assignments. This should be trivially verifiable,
+///   since I'm pretty sure it's impossible. Just putting it in here in case someon can find
+///   a way of doing this.
+///
+/// # Implementation Notes
+///
+/// The assignments are made with [`UnsafeAssign::replace`], which cannot panic, and the old
+/// values are only dropped once every field has been assigned, so a panicking `Drop` can't leave
+/// the struct half assigned either. [`SimultaneousUnsafeAssign::replace_all`] hands the old values
+/// back instead, e.g. to free a buffer after the struct stops pointing to it.
+pub struct SimultaneousUnsafeAssignment;
+
+impl SimultaneousUnsafeAssignment {
+    /// Defers assigning `value` to `dst` until all assignments are complete
+    pub fn with<Dst: UnsafeAssign<T>, T>(
+        self,
+        dst: &mut Dst,
+        value: T,
+    ) -> DeferredUnsafeAssignment<'_, Dst, T> {
+        DeferredUnsafeAssignment { field: dst, value }
+    }
 }
 
-// /// Assign several [`UnsafeField`] 'simultaneously'.
-// ///
-// /// There might be occasions where we cannot assign multiple fields simultaenously by
-// /// reconstructing the struct (though this should be done in most cases). In this case, we
-// /// can enforce a slightly lesser form of safety, by upholding invariants "only when the struct"
-// /// is read from. This pattern guarantees that we cannot get a `&self` in between writes to
-// /// fields.
-// ///
-// /// ```rs
-// /// unsafe_field::SimultaneousUnsafeAssignment
-// ///     .with(&mut foo.field_1, 5)
-// ///     .with(&mut foo.field_2, 10)
-// ///     .with(&mut foo.field_3, 15)
-// ///     .set_all();
-// /// ```
-// ///
-// /// # Safety
-// ///
-// /// - ensure that all invariants are upheld after all assignments are complete
-// /// - you must not rely on the ordering of the assignments, that is, the Unit state should
-// ///   be the same no matter the order of the assignments. This should be trivially verifiable,
-// ///   since I'm pretty sure it's impossible. Just putting it in here in case someon can find
-// ///   a way of doing this.
-// ///
-// /// # Implementation Notes
-// ///
-// /// This is possible to implement without storing references to the fields, but I don't think it
-// /// should matter in the Unit. This is probably optimised to the same thing? Not sure though.
-// /// I don't think it matters that much.
-// ///
-// /// There's probably some kind of way of doing this with pure functions that inlines functions
-// /// more aggressively, as well.
-// struct SimultaneousUnsafeAssignment;
-
-// impl SimultaneousUnsafeAssignment {
-//     fn with<'b, Dst: UnsafeAssign<T>, T>(
-//         self,
-//         value: T,
-//         dst: &'b mut Dst,
-//     ) -> DeferredSimultaneousUnsafeAssignment<Self, DeferredUnsafeAssignment<'b, Dst, T>> {
-//         DeferredSimultaneousUnsafeAssignment {
-//             first: self,
-//             second: DeferredUnsafeAssignment { field: dst, value },
-//         }
-//     }
-// }
-
-// impl SimultaneousUnsafeAssign for SimultaneousUnsafeAssignment {
-//     unsafe fn set_all(self) {}
-// }
-
-// trait SimultaneousUnsafeAssign {
-//     /// Complete all assignments that have been deferred 'simultaneously'. This is not actually
-//     /// simultaneous, but ensures that all values are assigned, without the struct they are a
-//     /// part of being read in an invalid state
-//     ///
-//     /// # Safety
-//     /// - ensure that all invariants are upheld after all assignments are complete
-//     unsafe fn set_all(self);
-// }
-
-// struct DeferredSimultaneousUnsafeAssignment<
-//     First: SimultaneousUnsafeAssign,
-//     Second: SimultaneousUnsafeAssign,
-// > {
-//     first: First,
-//     second: Second,
-// }
-
-// impl<First: SimultaneousUnsafeAssign, Second: SimultaneousUnsafeAssign>
-//     DeferredSimultaneousUnsafeAssignment<First, Second>
-// {
-//     pub fn with<'b, Dst: UnsafeAssign<T>, T>(
-//         self,
-//         value: T,
-//         dst: &'b mut Dst,
-//     ) -> DeferredSimultaneousUnsafeAssignment<Self, DeferredUnsafeAssignment<'b, Dst, T>> {
-//         DeferredSimultaneousUnsafeAssignment {
-//             first: self,
-//             second: DeferredUnsafeAssignment { field: dst, value },
-//         }
-//     }
-// }
-
-// impl<'a, First: SimultaneousUnsafeAssign, Second: SimultaneousUnsafeAssign> SimultaneousUnsafeAssign
-//     for DeferredSimultaneousUnsafeAssignment<First, Second>
-// {
-//     unsafe fn set_all(self) {
-//         self.first.set_all();
-//         self.second.set_all();
-//     }
-// }
-
-// pub struct DeferredUnsafeAssignment<'a, Dst: UnsafeAssign<T>, T> {
-//     field: &'a mut Dst,
-//     value: T,
-// }
-
-// impl<'a, Dst: UnsafeAssign<T>, T> DeferredUnsafeAssignment<'a, Dst, T> {
-//     pub fn with<'b, UDst: UnsafeAssign<U>, U>(
-//         self,
-//         value: U,
-//         dst: &'b mut UDst,
-//     ) -> DeferredSimultaneousUnsafeAssignment<Self, DeferredUnsafeAssignment<'b, UDst, U>> {
-//         DeferredSimultaneousUnsafeAssignment {
-//             first: self,
-//             second: DeferredUnsafeAssignment { field: dst, value },
-//         }
-//     }
-// }
-
-// impl<'a, Dst: UnsafeAssign<T>, T> SimultaneousUnsafeAssign
-//     for DeferredUnsafeAssignment<'a, Dst, T>
-// {
-//     unsafe fn set_all(self) {
-//         self.field.set(self.value);
-//     }
-// }
+impl SimultaneousUnsafeAssign for SimultaneousUnsafeAssignment {
+    type Replaced = ();
+
+    unsafe fn replace_all(self) -> Self::Replaced {}
+}
+
+pub trait SimultaneousUnsafeAssign
+where
+    Self: Sized,
+{
+    /// The old values of the fields, nested in the order the assignments were deferred in, e.g.
+    /// `((a, b), c)`
+    type Replaced;
+
+    /// Complete all assignments that have been deferred 'simultaneously', and return the old
+    /// values instead of dropping them.
+    ///
+    /// # Safety
+    /// - ensure that all invariants are upheld after all assignments are complete
+    unsafe fn replace_all(self) -> Self::Replaced;
+
+    /// Complete all assignments that have been deferred 'simultaneously'. This is not actually
+    /// simultaneous, but ensures that all values are assigned, without the struct they are a
+    /// part of being read in an invalid state
+    ///
+    /// # Safety
+    /// - ensure that all invariants are upheld after all assignments are complete
+    unsafe fn set_all(self) {
+        // SAFETY: passed to caller
+        drop(unsafe { self.replace_all() });
+    }
+}
+
+/// Two groups of deferred assignments, which are completed together
+pub struct DeferredSimultaneousUnsafeAssignment<
+    First: SimultaneousUnsafeAssign,
+    Second: SimultaneousUnsafeAssign,
+> {
+    first: First,
+    second: Second,
+}
+
+impl<First: SimultaneousUnsafeAssign, Second: SimultaneousUnsafeAssign>
+    DeferredSimultaneousUnsafeAssignment<First, Second>
+{
+    /// Defers assigning `value` to `dst` until all assignments are complete
+    pub fn with<Dst: UnsafeAssign<T>, T>(
+        self,
+        dst: &mut Dst,
+        value: T,
+    ) -> DeferredSimultaneousUnsafeAssignment<Self, DeferredUnsafeAssignment<'_, Dst, T>> {
+        DeferredSimultaneousUnsafeAssignment {
+            first: self,
+            second: DeferredUnsafeAssignment { field: dst, value },
+        }
+    }
+}
+
+impl<First: SimultaneousUnsafeAssign, Second: SimultaneousUnsafeAssign> SimultaneousUnsafeAssign
+    for DeferredSimultaneousUnsafeAssignment<First, Second>
+{
+    type Replaced = (First::Replaced, Second::Replaced);
+
+    unsafe fn replace_all(self) -> Self::Replaced {
+        // SAFETY: passed to caller
+        unsafe { (self.first.replace_all(), self.second.replace_all()) }
+    }
+}
+
+/// An assignment of `value` to `field`, which is completed together with any others that are
+/// deferred after it
+pub struct DeferredUnsafeAssignment<'a, Dst: UnsafeAssign<T>, T> {
+    field: &'a mut Dst,
+    value: T,
+}
+
+impl<'a, Dst: UnsafeAssign<T>, T> DeferredUnsafeAssignment<'a, Dst, T> {
+    /// Defers assigning `value` to `dst` until all assignments are complete
+    pub fn with<'b, UDst: UnsafeAssign<U>, U>(
+        self,
+        dst: &'b mut UDst,
+        value: U,
+    ) -> DeferredSimultaneousUnsafeAssignment<Self, DeferredUnsafeAssignment<'b, UDst, U>> {
+        DeferredSimultaneousUnsafeAssignment {
+            first: self,
+            second: DeferredUnsafeAssignment { field: dst, value },
+        }
+    }
+}
+
+impl<Dst: UnsafeAssign<T>, T> SimultaneousUnsafeAssign for DeferredUnsafeAssignment<'_, Dst, T> {
+    type Replaced = T;
+
+    unsafe fn replace_all(self) -> Self::Replaced {
+        // SAFETY: passed to caller
+        unsafe { self.field.replace(self.value) }
+    }
+}